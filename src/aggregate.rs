@@ -0,0 +1,152 @@
+// Copyright 2025 Redglyph
+//
+
+//! Incrementally maintained subtree aggregates.
+
+use std::marker::PhantomData;
+use crate::VecTree;
+
+/// A value computed for each node of a [VecTree] from its item and the values of its children,
+/// like a subtree sum or count, which is updated incrementally when the tree is modified.
+///
+/// The aggregate enables the tracking of the modified items in the tree (see [`VecTree::set_dirty_tracking()`]).
+/// When [`SubtreeAggregate::update()`] is called, only the values of the modified nodes and of their
+/// ancestors are computed again. If the structure of the tree has been modified, for example by adding,
+/// attaching or detaching nodes, the structure is scanned to find the nodes whose children have changed,
+/// and only the values of those nodes, of the new nodes, and of their ancestors are computed again. If
+/// nodes have been moved or removed (see [`VecTree::generation()`]), all the values are computed again.
+///
+/// Since the aggregate takes the modified nodes from the tree with [`VecTree::take_dirty()`], that
+/// method mustn't be called by another user of the tree, and only one aggregate can follow a tree.
+///
+/// # Example
+///
+/// ```
+/// use vectree::{SubtreeAggregate, VecTree};
+/// let mut tree = VecTree::new();
+/// let root = tree.add_root(1);
+/// let a = tree.add(Some(root), 2);
+/// tree.add_iter(Some(a), [3, 4]);
+/// tree.add(Some(root), 5);
+/// let mut sums = SubtreeAggregate::new(&mut tree, |item, children: &[i32]| item + children.iter().sum::<i32>());
+/// assert_eq!(sums.get(root), Some(&15));
+/// *tree.get_mut(3) = 10; // 4 -> 10
+/// sums.update(&mut tree);
+/// assert_eq!(sums.get(a), Some(&15));
+/// assert_eq!(sums.get(root), Some(&21));
+/// ```
+pub struct SubtreeAggregate<T, A, F> {
+    values: Vec<Option<A>>,
+    parents: Vec<Option<usize>>,
+    depths: Vec<u32>,
+    children: Vec<Vec<usize>>,          // children of each node when its value was computed
+    tree_id: u64,
+    version: u64,
+    generation: u64,
+    f: F,
+    _marker: PhantomData<fn(&T)>
+}
+
+impl<T, A: Clone, F: Fn(&T, &[A]) -> A> SubtreeAggregate<T, A, F> {
+    /// Computes the value of each node under the root of `tree` with `f`, which is called with the
+    /// item of the node and the values of its children, in order.
+    pub fn new(tree: &mut VecTree<T>, f: F) -> Self {
+        tree.set_dirty_tracking(true);
+        let mut aggregate = SubtreeAggregate {
+            values: Vec::new(),
+            parents: Vec::new(),
+            depths: Vec::new(),
+            children: Vec::new(),
+            tree_id: tree.tree_id(),
+            version: 0,
+            generation: 0,
+            f,
+            _marker: PhantomData
+        };
+        aggregate.compute_all(tree);
+        aggregate
+    }
+
+    /// Returns the value of the node `index`, or `None` if the node isn't under the root.
+    pub fn get(&self, index: usize) -> Option<&A> {
+        self.values.get(index)?.as_ref()
+    }
+
+    /// Updates the values after modifications of `tree`, which must be the tree given to
+    /// [`SubtreeAggregate::new()`].
+    pub fn update(&mut self, tree: &mut VecTree<T>) {
+        assert_eq!(tree.tree_id(), self.tree_id, "the aggregate doesn't belong to that tree");
+        tree.set_dirty_tracking(true);
+        if tree.generation() != self.generation {
+            self.compute_all(tree);
+            return;
+        }
+        if tree.version != self.version {
+            self.update_structure(tree);
+            return;
+        }
+        // the modified nodes and their ancestors, updated from the deepest
+        let mut stale = vec![false; self.values.len()];
+        let mut nodes = Vec::new();
+        for mut index in tree.take_dirty() {
+            while self.values[index].is_some() && !stale[index] {
+                stale[index] = true;
+                nodes.push(index);
+                match self.parents[index] {
+                    Some(parent) => index = parent,
+                    None => break
+                }
+            }
+        }
+        nodes.sort_unstable_by_key(|&index| std::cmp::Reverse(self.depths[index]));
+        for index in nodes {
+            self.values[index] = Some(self.compute(tree, index));
+        }
+    }
+
+    fn compute(&self, tree: &VecTree<T>, index: usize) -> A {
+        let children = tree.children(index).iter().map(|&child| self.values[child].clone().unwrap()).collect::<Vec<_>>();
+        (self.f)(tree.get(index), &children)
+    }
+
+    fn compute_all(&mut self, tree: &mut VecTree<T>) {
+        self.values.clear();
+        self.children.clear();
+        self.generation = tree.generation();
+        self.update_structure(tree);
+    }
+
+    /// Scans the structure of the tree and computes the values of the nodes which are new, modified,
+    /// or whose children have changed, and of their ancestors. The other values are kept.
+    fn update_structure(&mut self, tree: &mut VecTree<T>) {
+        let mut modified = vec![false; tree.len()];
+        for index in tree.take_dirty() {
+            modified[index] = true;
+        }
+        self.version = tree.version;
+        let mut old_values = std::mem::take(&mut self.values);
+        old_values.resize(tree.len(), None);
+        self.values.resize(tree.len(), None);
+        self.children.resize(tree.len(), Vec::new());
+        self.parents = vec![None; tree.len()];
+        self.depths = vec![0; tree.len()];
+        let mut computed = vec![false; tree.len()];
+        // in post-order, the children are visited before their parent
+        for &(index, depth) in tree.post_order() {
+            self.depths[index] = depth;
+            let children = tree.children(index);
+            for &child in children {
+                self.parents[child] = Some(index);
+            }
+            let stale = modified[index] || old_values[index].is_none() || self.children[index] != children
+                || children.iter().any(|&child| computed[child]);
+            if stale {
+                self.values[index] = Some(self.compute(tree, index));
+                self.children[index] = children.to_vec();
+                computed[index] = true;
+            } else {
+                self.values[index] = old_values[index].take();
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+// Copyright 2025 Redglyph
+//
+
+//! Attribute evaluation, in the manner of attribute grammars.
+
+use std::ops::Index;
+use crate::VecTree;
+
+/// A side table holding an attribute for some of the nodes of a [VecTree], indexed like the
+/// nodes. It's created by the attribute evaluation methods, like [`VecTree::evaluate_synthesized()`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttrTable<A> {
+    values: Vec<Option<A>>
+}
+
+impl<A> AttrTable<A> {
+    /// Returns the attribute of the node `index`, or `None` if it hasn't been evaluated, because the
+    /// node isn't under the root.
+    pub fn get(&self, index: usize) -> Option<&A> {
+        self.values.get(index)?.as_ref()
+    }
+
+    /// Iterates over the evaluated attributes as `(index, &attribute)` tuples, in the order of the
+    /// node indices.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &A)> {
+        self.values.iter().enumerate().filter_map(|(index, value)| Some((index, value.as_ref()?)))
+    }
+
+    /// Converts the table into a vector with one optional attribute per node.
+    pub fn into_vec(self) -> Vec<Option<A>> {
+        self.values
+    }
+}
+
+impl<A> Index<usize> for AttrTable<A> {
+    type Output = A;
+
+    /// Returns the attribute of the node `index`.
+    ///
+    /// Panics if the attribute hasn't been evaluated.
+    fn index(&self, index: usize) -> &Self::Output {
+        self.get(index).unwrap_or_else(|| panic!("no attribute for node {index}"))
+    }
+}
+
+/// The rules computing a synthesized attribute, which is computed bottom-up: the attribute of a
+/// node depends on its item and on the attributes of its children.
+///
+/// It's implemented by the closures `FnMut(&T, &[&A]) -> A`. Several attributes can be computed in
+/// the same pass by using a structure for `A`.
+pub trait SynthesizedRules<T, A> {
+    /// Computes the attribute of a node from its item and the attributes of its children, in order.
+    fn synthesize(&mut self, item: &T, children: &[&A]) -> A;
+}
+
+impl<T, A, F: FnMut(&T, &[&A]) -> A> SynthesizedRules<T, A> for F {
+    fn synthesize(&mut self, item: &T, children: &[&A]) -> A {
+        self(item, children)
+    }
+}
+
+/// The rules computing an inherited attribute, which is computed top-down: the attribute of a node
+/// depends on its item and on the attribute of its parent.
+///
+/// It's implemented by the closures `FnMut(&T, Option<&I>) -> I`.
+pub trait InheritedRules<T, I> {
+    /// Computes the attribute of a node from its item and the attribute of its parent, which is
+    /// `None` for the root.
+    fn inherit(&mut self, item: &T, parent: Option<&I>) -> I;
+}
+
+impl<T, I, F: FnMut(&T, Option<&I>) -> I> InheritedRules<T, I> for F {
+    fn inherit(&mut self, item: &T, parent: Option<&I>) -> I {
+        self(item, parent)
+    }
+}
+
+impl<T> VecTree<T> {
+    /// Evaluates a synthesized attribute for each node under the root, with the given `rules`, and
+    /// returns the table of the attributes.
+    ///
+    /// The nodes are evaluated in post-order, so the attributes of the children are always available
+    /// when the attribute of their parent is computed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::{SynthesizedRules, VecTree};
+    ///
+    /// #[derive(Debug, PartialEq)]
+    /// struct Attr { value: i32, size: usize }
+    ///
+    /// struct Rules;
+    ///
+    /// impl SynthesizedRules<&str, Attr> for Rules {
+    ///     fn synthesize(&mut self, item: &&str, children: &[&Attr]) -> Attr {
+    ///         let size = 1 + children.iter().map(|c| c.size).sum::<usize>();
+    ///         let value = match *item {
+    ///             "+" => children.iter().map(|c| c.value).sum(),
+    ///             "*" => children.iter().map(|c| c.value).product(),
+    ///             number => number.parse().unwrap(),
+    ///         };
+    ///         Attr { value, size }
+    ///     }
+    /// }
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("*");
+    /// let plus = tree.add(Some(root), "+");
+    /// tree.add_iter(Some(plus), ["2", "3"]);
+    /// tree.add(Some(root), "4");
+    /// let attrs = tree.evaluate_synthesized(Rules);
+    /// assert_eq!(attrs[root], Attr { value: 20, size: 5 });
+    /// assert_eq!(attrs[plus], Attr { value: 5, size: 3 });
+    /// ```
+    pub fn evaluate_synthesized<A, R: SynthesizedRules<T, A>>(&self, mut rules: R) -> AttrTable<A> {
+        let mut values = Vec::new();
+        values.resize_with(self.len(), || None);
+        for inode in self.iter_depth_simple() {
+            let attr = {
+                let children = self.children(inode.index).iter().map(|&child| values[child].as_ref().unwrap()).collect::<Vec<_>>();
+                rules.synthesize(&inode, &children)
+            };
+            values[inode.index] = Some(attr);
+        }
+        AttrTable { values }
+    }
+
+    /// Evaluates an inherited attribute for each node under the root, with the given `rules`, and
+    /// returns the table of the attributes.
+    ///
+    /// The nodes are evaluated in pre-order, so the attribute of the parent is always available
+    /// when the attribute of its children are computed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// let a1 = tree.add(Some(a), "a1");
+    /// let paths = tree.evaluate_inherited(|item: &&str, parent: Option<&String>| match parent {
+    ///     Some(path) => format!("{path}/{item}"),
+    ///     None => item.to_string(),
+    /// });
+    /// assert_eq!(paths[a1], "root/a/a1");
+    /// ```
+    pub fn evaluate_inherited<I, R: InheritedRules<T, I>>(&self, mut rules: R) -> AttrTable<I> {
+        let mut values = Vec::new();
+        values.resize_with(self.len(), || None);
+        if let Some(root) = self.get_root() {
+            values[root] = Some(rules.inherit(self.get(root), None));
+            let mut stack = vec![root];
+            while let Some(index) = stack.pop() {
+                for &child in self.children(index) {
+                    let attr = rules.inherit(self.get(child), values[index].as_ref());
+                    values[child] = Some(attr);
+                    stack.push(child);
+                }
+            }
+        }
+        AttrTable { values }
+    }
+
+    /// Evaluates an inherited attribute with `inherited` then a synthesized attribute with `synthesized`
+    /// for each node under the root, and returns both tables of attributes.
+    ///
+    /// The inherited attributes are evaluated first, top-down, so that each synthesized attribute
+    /// can depend on the inherited attribute of its node, on top of its item and the synthesized
+    /// attributes of its children.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root(1);
+    /// let a = tree.add(Some(root), 2);
+    /// tree.add_iter(Some(a), [3, 4]);
+    /// // sum of the items weighted by their depth
+    /// let (depths, sums) = tree.evaluate_attributes(
+    ///     |_: &i32, parent: Option<&i32>| parent.map_or(0, |depth| depth + 1),
+    ///     |item: &i32, depth: &i32, children: &[&i32]| item * depth + children.iter().copied().sum::<i32>());
+    /// assert_eq!(depths[a], 1);
+    /// assert_eq!(sums[root], 2 + 2 * (3 + 4));
+    /// ```
+    pub fn evaluate_attributes<I, S, RI, RS>(&self, inherited: RI, mut synthesized: RS) -> (AttrTable<I>, AttrTable<S>)
+    where
+        RI: InheritedRules<T, I>,
+        RS: FnMut(&T, &I, &[&S]) -> S
+    {
+        let inherited = self.evaluate_inherited(inherited);
+        let mut values = Vec::new();
+        values.resize_with(self.len(), || None);
+        for inode in self.iter_depth_simple() {
+            let attr = {
+                let children = self.children(inode.index).iter().map(|&child| values[child].as_ref().unwrap()).collect::<Vec<_>>();
+                synthesized(&inode, &inherited[inode.index], &children)
+            };
+            values[inode.index] = Some(attr);
+        }
+        (inherited, AttrTable { values })
+    }
+}
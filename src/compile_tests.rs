@@ -130,4 +130,53 @@ mod borrows {
     /// println!("value: {value1}, {value2}");   // |                   ^^^^ immutable borrow occurs here
     /// ````
     fn must_not_compile9() {}
-}
\ No newline at end of file
+
+    /// ```compile_fail,E0502
+    /// use vectree::VecTree;
+    ///
+    /// let mut tree = VecTree::<String>::new();
+    /// let (mut sub_a, _sub_b) = tree.split_subtrees_mut(1, 2); // | let (mut sub_a, _sub_b) = tree.split_subtrees_mut(1, 2);
+    /// let a_borrowed = tree.get(1);                            // |                           ---- mutable borrow occurs here
+    /// for mut inode in sub_a.iter_depth_simple_mut() {         // | let a_borrowed = tree.get(1);
+    ///     *inode = "new".to_string();                          // |                  ^^^^ immutable borrow occurs here
+    /// }
+    /// println!("value: {a_borrowed}");
+    /// ```
+    fn must_not_compile10() {}
+
+    /// ```compile_fail,E0599
+    /// use vectree::{Traversal, VecTree};
+    ///
+    /// let mut tree = VecTree::<String>::new();
+    /// let mut iter = tree.iter_traverse_mut(Traversal::PreOrder);
+    /// let parent = iter.next().unwrap();                  // | let first = parent.iter_children().next().unwrap();
+    /// let first = parent.iter_children().next().unwrap(); // |                    ^^^^^^^^^^^^^ method not found in `NodeProxySimpleMut<'_, String>`
+    /// let name: &str = &first;
+    /// let mut child = iter.next().unwrap();               // the same node, given mutably after its parent
+    /// *child = "new".to_string();
+    /// println!("value: {name}");
+    /// ```
+    fn must_not_compile11() {}
+
+    /// ```compile_fail,E0599
+    /// use vectree::VecTree;
+    ///
+    /// let mut tree = VecTree::<String>::new();
+    /// let mut iter = tree.iter_depth_simple_mut();
+    /// let checkpoint = iter.checkpoint();           // | let checkpoint = iter.checkpoint();
+    /// let a1 = iter.next().unwrap();                // |                       ^^^^^^^^^^ method not found in `VecTreePoDfsIter<IterDataSimpleMut<'_, String>>`
+    /// let mut iter = iter.resume(checkpoint);
+    /// let a1_again = iter.next().unwrap();
+    /// ```
+    fn must_not_compile12() {}
+
+    /// ```compile_fail,E0599
+    /// use vectree::VecTree;
+    ///
+    /// let mut tree = VecTree::<String>::new();
+    /// let checkpoint = tree.iter_depth().checkpoint();
+    /// let iter = tree.iter_depth_mut().resume(checkpoint); // | let iter = tree.iter_depth_mut().resume(checkpoint);
+    ///                                                       // |                                  ^^^^^^ method not found in `VecTreePoDfsIter<IterDataMut<'_, String>>`
+    /// ```
+    fn must_not_compile13() {}
+}
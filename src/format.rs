@@ -0,0 +1,181 @@
+// Copyright 2025 Redglyph
+//
+
+//! Pluggable text formats for trees.
+
+use std::fmt::{self, Display, Write};
+use crate::{TreeSink, VecTree};
+
+/// A text format for [`VecTree::format_with()`]. Each node is written as
+///
+/// `prefix` `open` *item* `children_start` *child* `separator` *child* ... `close`
+///
+/// where `open`, `children_start` and `close` are only written for nodes with children.
+/// Only [`TreeFormat::write_item()`] must be implemented; the default tokens give
+/// the `root(a(a1,a2),b)` format.
+pub trait TreeFormat<T> {
+    /// Writes the item of a node.
+    fn write_item(&self, out: &mut dyn Write, item: &T) -> fmt::Result;
+
+    /// Token written before every node.
+    fn prefix(&self) -> &str {
+        ""
+    }
+
+    /// Token written before the item of a node with children.
+    fn open(&self) -> &str {
+        ""
+    }
+
+    /// Token written between the item of a node and its first child.
+    fn children_start(&self) -> &str {
+        "("
+    }
+
+    /// Token written between two siblings.
+    fn separator(&self) -> &str {
+        ","
+    }
+
+    /// Token written after the last child of a node.
+    fn close(&self) -> &str {
+        ")"
+    }
+}
+
+/// Formats a tree as `root(a(a1,a2),b)`, using the [`Display`] trait of the items.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Parenthesized;
+
+impl<T: Display> TreeFormat<T> for Parenthesized {
+    fn write_item(&self, out: &mut dyn Write, item: &T) -> fmt::Result {
+        write!(out, "{item}")
+    }
+}
+
+/// Formats a tree as an S-expression `(root (a a1 a2) b)`, using the [`Display`] trait of the items.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SExpression;
+
+impl<T: Display> TreeFormat<T> for SExpression {
+    fn write_item(&self, out: &mut dyn Write, item: &T) -> fmt::Result {
+        write!(out, "{item}")
+    }
+
+    fn open(&self) -> &str {
+        "("
+    }
+
+    fn children_start(&self) -> &str {
+        " "
+    }
+
+    fn separator(&self) -> &str {
+        " "
+    }
+}
+
+/// Sink writing the events with a [`TreeFormat`]; the first error stops the output.
+struct FormatSink<'f, F, W> {
+    format: &'f F,
+    out: &'f mut W,
+    /// true if the next node is the first child of its parent (or the top node)
+    first: bool,
+    result: fmt::Result,
+}
+
+impl<F, W: Write> FormatSink<'_, F, W> {
+    fn write_node<T>(&mut self, item: &T, has_children: bool) -> fmt::Result
+    where
+        F: TreeFormat<T>,
+    {
+        if !self.first {
+            self.out.write_str(self.format.separator())?;
+        }
+        self.out.write_str(self.format.prefix())?;
+        if has_children {
+            self.out.write_str(self.format.open())?;
+        }
+        self.format.write_item(self.out, item)?;
+        if has_children {
+            self.out.write_str(self.format.children_start())?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, F: TreeFormat<T>, W: Write> TreeSink<&T> for FormatSink<'_, F, W> {
+    fn start_node(&mut self, value: &T) {
+        if self.result.is_ok() {
+            self.result = self.write_node(value, true);
+            self.first = true;
+        }
+    }
+
+    fn end_node(&mut self) {
+        if self.result.is_ok() {
+            self.result = self.out.write_str(self.format.close());
+            self.first = false;
+        }
+    }
+
+    fn leaf(&mut self, value: &T) {
+        if self.result.is_ok() {
+            self.result = self.write_node(value, false);
+            self.first = false;
+        }
+    }
+}
+
+impl<T> VecTree<T> {
+    /// Writes the tree under the root to `out` in the given `format`. Nothing is written if
+    /// the tree has no root.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::fmt::{self, Write};
+    /// use vectree::{Parenthesized, SExpression, TreeFormat, VecTree};
+    ///
+    /// // custom format: {"item": [children]}
+    /// struct Json;
+    ///
+    /// impl TreeFormat<&str> for Json {
+    ///     fn write_item(&self, out: &mut dyn Write, item: &&str) -> fmt::Result {
+    ///         write!(out, "\"{item}\"")
+    ///     }
+    ///     fn open(&self) -> &str { "{" }
+    ///     fn children_start(&self) -> &str { ": [" }
+    ///     fn separator(&self) -> &str { ", " }
+    ///     fn close(&self) -> &str { "]}" }
+    /// }
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("*");
+    /// let plus = tree.add(Some(root), "+");
+    /// tree.add_iter(Some(plus), ["2", "3"]);
+    /// tree.add(Some(root), "4");
+    /// let mut text = String::new();
+    /// tree.format_with(&Parenthesized, &mut text).unwrap();
+    /// assert_eq!(text, "*(+(2,3),4)");
+    /// text.clear();
+    /// tree.format_with(&SExpression, &mut text).unwrap();
+    /// assert_eq!(text, "(* (+ 2 3) 4)");
+    /// text.clear();
+    /// tree.format_with(&Json, &mut text).unwrap();
+    /// assert_eq!(text, r#"{"*": [{"+": ["2", "3"]}, "4"]}"#);
+    /// ```
+    pub fn format_with<F: TreeFormat<T>, W: Write>(&self, format: &F, out: &mut W) -> fmt::Result {
+        match self.get_root() {
+            Some(root) => self.format_at_with(root, format, out),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the subtree under the node `top` to `out` in the given `format` (see [`VecTree::format_with()`]).
+    pub fn format_at_with<F: TreeFormat<T>, W: Write>(&self, top: usize, format: &F, out: &mut W) -> fmt::Result {
+        let mut sink = FormatSink { format, out, first: true, result: Ok(()) };
+        self.emit_at(top, &mut sink);
+        sink.result
+    }
+}
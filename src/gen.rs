@@ -0,0 +1,105 @@
+// Copyright 2025 Redglyph
+//
+
+//! Tree generators, for tests and benchmarks.
+//!
+//! The random generators take the random number generator as a closure returning `u64` values, so
+//! any crate can be used. [`XorShift64`] is a minimal generator provided for convenience.
+//!
+//! # Example
+//!
+//! ```
+//! use vectree::gen::{perfect_k_ary, random_tree, XorShift64};
+//! let tree = perfect_k_ary(3, 2);
+//! assert_eq!(tree.len(), 15);
+//! let mut rng = XorShift64::new(42);
+//! let tree = random_tree(100, 4, &mut || rng.next_u64());
+//! assert_eq!(tree.iter_depth_simple().count(), 100);
+//! ```
+
+use crate::VecTree;
+
+/// Creates a random tree of `n_nodes` nodes, where each node has at most `max_arity` children. The
+/// value of each node is its index.
+///
+/// Panics if `n_nodes` is greater than 1 and `max_arity` is 0.
+pub fn random_tree<R: FnMut() -> u64>(n_nodes: usize, max_arity: usize, rng: &mut R) -> VecTree<u32> {
+    random_tree_with(n_nodes, max_arity, rng, |index| index as u32)
+}
+
+/// Creates a random tree of `n_nodes` nodes, where each node has at most `max_arity` children. The
+/// value of each node is given by `value`, which is called with the node index.
+///
+/// Panics if `n_nodes` is greater than 1 and `max_arity` is 0.
+pub fn random_tree_with<T, R, F>(n_nodes: usize, max_arity: usize, rng: &mut R, mut value: F) -> VecTree<T>
+where
+    R: FnMut() -> u64,
+    F: FnMut(usize) -> T
+{
+    assert!(n_nodes <= 1 || max_arity > 0, "max_arity must be greater than 0");
+    let mut tree = VecTree::with_capacity(n_nodes);
+    if n_nodes == 0 {
+        return tree;
+    }
+    let root = tree.add_root(value(0));
+    // the nodes that can still receive a child
+    let mut open = vec![root];
+    for index in 1..n_nodes {
+        let pos = (rng() % open.len() as u64) as usize;
+        let parent = open[pos];
+        tree.add(Some(parent), value(index));
+        if tree.children(parent).len() == max_arity {
+            open.swap_remove(pos);
+        }
+        open.push(index);
+    }
+    tree
+}
+
+/// Creates a perfect tree of depth `depth`, where each node that isn't a leaf has `k` children. The
+/// root is at depth 0, so the tree has `(k^(depth + 1) - 1) / (k - 1)` nodes. The nodes are created in
+/// breadth-first order, and the value of each node is its index.
+pub fn perfect_k_ary(depth: u32, k: usize) -> VecTree<u32> {
+    perfect_k_ary_with(depth, k, |index| index as u32)
+}
+
+/// Creates a perfect tree of depth `depth`, where each node that isn't a leaf has `k` children (see
+/// [`perfect_k_ary()`]). The value of each node is given by `value`, which is called with the node index.
+pub fn perfect_k_ary_with<T, F: FnMut(usize) -> T>(depth: u32, k: usize, mut value: F) -> VecTree<T> {
+    let mut tree = VecTree::new();
+    tree.add_root(value(0));
+    let mut level = 0..1;
+    for _ in 0..depth {
+        let start = tree.len();
+        for parent in level {
+            for _ in 0..k {
+                tree.add(Some(parent), value(tree.len()));
+            }
+        }
+        level = start..tree.len();
+    }
+    tree
+}
+
+/// A minimal xorshift pseudo-random number generator, which can be used with [`random_tree()`].
+/// It's not suited to cryptography.
+#[derive(Clone, Debug)]
+pub struct XorShift64(u64);
+
+impl XorShift64 {
+    /// Creates a generator from a seed. A seed of 0 is replaced by another value, since the generator
+    /// would only produce 0.
+    pub fn new(seed: u64) -> Self {
+        XorShift64(if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed })
+    }
+
+    /// Returns the next pseudo-random number.
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+}
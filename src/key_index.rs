@@ -0,0 +1,152 @@
+// Copyright 2025 Redglyph
+//
+
+//! Secondary index from keys derived from the items to the node indices.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::hash::Hash;
+use crate::VecTree;
+
+/// A secondary index kept by the tree, which is updated when nodes are added, moved or removed.
+pub(crate) trait KeyIndex<T>: Debug + Send + Sync {
+    /// Adds the node `index`, whose item is `item`, keeping the indices of each key in increasing order.
+    fn insert(&mut self, index: usize, item: &T);
+    /// Removes the node `index`, whose item was `item` when it was inserted, if it's in the index.
+    fn remove(&mut self, index: usize, item: &T);
+    fn clear(&mut self);
+    /// Returns the map from the keys to the node indices, a `HashMap<K, Vec<usize>>`.
+    fn map(&self) -> &dyn Any;
+    fn clone_box(&self) -> Box<dyn KeyIndex<T>>;
+}
+
+struct HashKeyIndex<K, F> {
+    map: HashMap<K, Vec<usize>>,
+    key: F
+}
+
+impl<T, K, F> KeyIndex<T> for HashKeyIndex<K, F>
+where
+    K: Hash + Eq + Clone + Send + Sync + 'static,
+    F: Fn(&T) -> K + Clone + Send + Sync + 'static
+{
+    fn insert(&mut self, index: usize, item: &T) {
+        let indices = self.map.entry((self.key)(item)).or_default();
+        let position = indices.partition_point(|&i| i < index);
+        indices.insert(position, index);
+    }
+
+    fn remove(&mut self, index: usize, item: &T) {
+        let key = (self.key)(item);
+        if let Some(indices) = self.map.get_mut(&key) {
+            if let Ok(position) = indices.binary_search(&index) {
+                indices.remove(position);
+                if indices.is_empty() {
+                    self.map.remove(&key);
+                }
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    fn map(&self) -> &dyn Any {
+        &self.map
+    }
+
+    fn clone_box(&self) -> Box<dyn KeyIndex<T>> {
+        Box::new(HashKeyIndex { map: self.map.clone(), key: self.key.clone() })
+    }
+}
+
+impl<K, F> Debug for HashKeyIndex<K, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HashKeyIndex").field("keys", &self.map.len()).finish()
+    }
+}
+
+impl<T> VecTree<T> {
+    /// Builds a secondary index from the keys given by `key` for each item to the indices of the
+    /// nodes, which allows to find nodes by key with [`VecTree::lookup()`] instead of scanning the tree.
+    /// It replaces the index built previously, if any.
+    ///
+    /// The index is updated when nodes are added, moved or removed, but not when the items are modified:
+    /// if the modification of an item changes its key, [`VecTree::rebuild_index()`] must be called.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// tree.build_index(|item: &&str| item.len());
+    /// let a = tree.add(Some(root), "a");
+    /// let b = tree.add(Some(root), "b");
+    /// assert_eq!(tree.lookup(&1_usize), &[a, b]);
+    /// assert_eq!(tree.lookup(&4_usize), &[root]);
+    /// assert!(tree.lookup(&2_usize).is_empty());
+    /// ```
+    pub fn build_index<K, F>(&mut self, key: F)
+    where
+        K: Hash + Eq + Clone + Send + Sync + 'static,
+        F: Fn(&T) -> K + Clone + Send + Sync + 'static
+    {
+        self.key_index = Some(Box::new(HashKeyIndex { map: HashMap::new(), key }));
+        self.rebuild_index();
+    }
+
+    /// Returns the indices of the nodes whose key is `key`, in increasing order (see [`VecTree::build_index()`]).
+    ///
+    /// Panics if there's no index or if its keys aren't of type `K`.
+    pub fn lookup<K: Hash + Eq + 'static>(&self, key: &K) -> &[usize] {
+        let key_index = self.key_index.as_ref().expect("no index has been built");
+        let map = key_index.map().downcast_ref::<HashMap<K, Vec<usize>>>().expect("the index doesn't have keys of that type");
+        map.get(key).map_or(&[], |indices| indices.as_slice())
+    }
+
+    /// Builds the index again from all the items, after they've been modified (see [`VecTree::build_index()`]).
+    ///
+    /// The whole buffer is scanned, so [`VecTree::swap()`], which only moves two items, updates their
+    /// entries instead.
+    pub fn rebuild_index(&mut self) {
+        if let Some(key_index) = &mut self.key_index {
+            key_index.clear();
+            for (index, node) in self.nodes.iter().enumerate() {
+                if !self.removed.get(index).copied().unwrap_or(false) {
+                    key_index.insert(index, node.value());
+                }
+            }
+        }
+    }
+
+    /// Removes the index (see [`VecTree::build_index()`]).
+    pub fn drop_index(&mut self) {
+        self.key_index = None;
+    }
+
+    /// Updates the entries of the nodes `a` and `b` in the index, if any, after their items have been swapped.
+    pub(crate) fn index_swapped(&mut self, a: usize, b: usize) {
+        if let Some(key_index) = &mut self.key_index {
+            // each item was indexed at the position of the other one
+            key_index.remove(b, self.nodes[a].value());
+            key_index.remove(a, self.nodes[b].value());
+            for index in [a, b] {
+                if !self.removed.get(index).copied().unwrap_or(false) {
+                    key_index.insert(index, self.nodes[index].value());
+                }
+            }
+        }
+    }
+
+    /// Adds the nodes from `first` to the end of the buffer to the index, if any.
+    pub(crate) fn index_added(&mut self, first: usize) {
+        if let Some(key_index) = &mut self.key_index {
+            for (index, node) in self.nodes.iter().enumerate().skip(first) {
+                key_index.insert(index, node.value());
+            }
+        }
+    }
+}
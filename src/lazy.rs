@@ -0,0 +1,84 @@
+// Copyright 2025 Redglyph
+//
+
+//! Lazily materialized trees.
+
+use crate::VecTree;
+
+/// A [VecTree] whose nodes are materialized lazily: the children of a node are produced by a
+/// user closure, the expander, the first time the node is visited.
+///
+/// It allows to traverse virtual trees, like a file system or the data of a remote API, without
+/// loading them entirely. The materialized part is a regular [VecTree], available with
+/// [`LazyVecTree::tree()`].
+///
+/// # Example
+///
+/// ```
+/// use vectree::LazyVecTree;
+/// // the binary decomposition of the numbers:
+/// let mut tree = LazyVecTree::new(1, |n: &u32| vec![2 * n, 2 * n + 1]);
+/// let mut result = vec![];
+/// tree.walk(2, |_, depth, n| result.push(format!("{depth}:{n}")));
+/// assert_eq!(result, ["2:4", "2:5", "1:2", "2:6", "2:7", "1:3", "0:1"]);
+/// assert_eq!(tree.tree().len(), 7);
+/// ```
+pub struct LazyVecTree<T, F> {
+    tree: VecTree<T>,
+    expanded: Vec<bool>,
+    expander: F
+}
+
+impl<T, F: FnMut(&T) -> Vec<T>> LazyVecTree<T, F> {
+    /// Creates a lazy tree with the given root item. The children of each node will be produced
+    /// by `expander` the first time the node is visited.
+    pub fn new(root: T, expander: F) -> Self {
+        let mut tree = VecTree::new();
+        tree.add_root(root);
+        LazyVecTree { tree, expanded: vec![false], expander }
+    }
+
+    /// Returns `true` if the children of the node `index` have been materialized.
+    pub fn is_expanded(&self, index: usize) -> bool {
+        self.expanded[index]
+    }
+
+    /// Materializes the children of the node `index`, if it's not done yet, and returns their indices.
+    pub fn expand(&mut self, index: usize) -> &[usize] {
+        if !self.expanded[index] {
+            self.expanded[index] = true;
+            let children = (self.expander)(self.tree.get(index));
+            self.tree.add_iter(Some(index), children);
+            self.expanded.resize(self.tree.len(), false);
+        }
+        self.tree.children(index)
+    }
+
+    /// Post-order, depth-first walk over the nodes from the root, down to the depth `max_depth`. The
+    /// nodes are materialized when they're visited, except the children of the nodes at `max_depth`.
+    ///
+    /// `f` is called with the index, the depth and a reference to each node.
+    pub fn walk<G: FnMut(usize, u32, &T)>(&mut self, max_depth: u32, mut f: G) {
+        // (index, depth, children visited)
+        let mut stack = vec![(0, 0, false)];
+        while let Some((index, depth, visited)) = stack.pop() {
+            if visited || depth == max_depth {
+                f(index, depth, self.tree.get(index));
+            } else {
+                stack.push((index, depth, true));
+                let children = self.expand(index);
+                stack.extend(children.iter().rev().map(|&child| (child, depth + 1, false)));
+            }
+        }
+    }
+
+    /// Returns the materialized part of the tree.
+    pub fn tree(&self) -> &VecTree<T> {
+        &self.tree
+    }
+
+    /// Converts the lazy tree into the materialized [VecTree].
+    pub fn into_tree(self) -> VecTree<T> {
+        self.tree
+    }
+}
@@ -39,6 +39,8 @@
 //! * [VecTree::addci_iter]`(&mut self, parent_index: Option<usize>, item: T, children_id: IntoIterator<Item = usize>)`
 //! * [VecTree::add_iter]`(&mut self, parent_index: Option<usize>, items: IntoIterator<Item = T>)`
 //! * [VecTree::addc_iter]`(&mut self, parent_index: Option<usize>, item: T, children: IntoIterator<Item = T>)`
+//! * [VecTree::add_subtree]`(&mut self, parent_index: Option<usize>, subtree: SubTree<T>)`
+//! * [VecTree::insert_child_at]`(&mut self, parent_index: usize, position: usize, item: T)`
 //!
 //! The key to the names is
 //! * "c" when a child or children can be specified
@@ -69,6 +71,15 @@
 //! * [NodeProxy::iter_children_simple()], to iterate over the children
 //! * [NodeProxy::iter_depth_simple()], to iterate the subtree under the node
 //!
+//! [VecTree::iter_traverse] and its `_at` variant give the same proxies as the full-fledged iterators, in a
+//! pre-order, post-order or breadth-first order selected at runtime by a [Traversal] argument; the `_mut` variants
+//! give the same proxies as the simple mutable iterators. [VecTree::iter_breadth] and [VecTree::iter_breadth_at] are
+//! shortcuts for the breadth-first order, and [VecTree::iter_breadth_mut] gives the same proxies as the simple mutable
+//! iterators in that order. [VecTree::walk] returns a pre-order [TreeWalker], which
+//! can be told not to descend into the subtree of the last node with [VecTreeTraversalIter::skip_children].
+//! [VecTree::visit] calls a closure when entering and when leaving each node, which gives both the pre-order and
+//! the post-order in one pass, and [VecTree::iter_euler] iterates over the Euler tour of the tree.
+//!
 //! Examples
 //!
 //! Simple iterator:
@@ -109,27 +120,115 @@
 //! assert_eq!(result, "ROOT(a(a1,a2),b,C(c1,c2))");
 //! ```
 //!
-//! ## Important limitation
+//! [NodeProxyMut::iter_children_mut()] and [NodeProxyMut::child_mut()] give mutable proxies on the children, which borrow
+//! the node's proxy until they're dropped.
+//!
+//! [NodeProxyMut::iter_children()] panics if other mutable proxies of the same iteration are still alive, for example if
+//! they were collected in a vector. [NodeProxyMut::active_mut_proxies()] gives the number of mutable proxies alive,
+//! and the `debug-borrows` feature records the node and the creation backtrace of each of them, which are added to
+//! the panic message and given by `VecTree::live_mut_proxies()`.
+//!
+//! ## Integrity checks
+//!
+//! The methods that attach existing nodes, like [VecTree::attach_child] or [VecTree::children_mut], don't verify
+//! the integrity of the tree. The following tools are available to catch mistakes:
+//! * [VecTree::validate] verifies the whole tree,
+//! * [VecTree::try_attach_child] refuses an attachment that would corrupt the tree,
+//! * [VecTree::set_strict] verifies every structural modification,
+//! * in debug builds, the cheap verifications are made when nodes are attached (the child must exist and be different
+//!   from the parent),
+//! * with the `debug-validate` feature, the whole tree is verified each time a traversal of the tree starts.
 //!
-//! The [VecTree] object doesn't provide methods to delete nodes.
+//! ## Safe alternative
+//!
+//! [VecTree] relies on unsafe code to give the iterators access to the node's children while the node is mutably
+//! borrowed. [SafeVecTree] is a simpler alternative implemented without unsafe code, for projects that don't
+//! allow it; its nodes are visited by index with a [PostOrder] cursor.
+//!
+//! ## Removing nodes
+//!
+//! [VecTree::remove_subtree] removes a node and all the nodes below it, and [VecTree::retain] removes the nodes rejected
+//! by a predicate. The removed nodes are only marked as such and stay in the buffer, so the indices of the other nodes
+//! don't change; the iterators don't visit them. [VecTree::compact] drops them, with the other nodes that can't be
+//! reached from the root, and reports the new index of the kept nodes.
 
 use std::cell::{Cell, UnsafeCell};
-use std::fmt::{Display, Formatter};
+#[cfg(feature = "debug-borrows")]
+use std::backtrace::Backtrace;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt::{Debug, Display, Formatter};
+use std::iter::FusedIterator;
 use std::marker::PhantomData;
-use std::ops::{Deref, DerefMut, Index, IndexMut};
+use std::ops::{ControlFlow, Deref, DerefMut, Index, IndexMut};
 use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::OnceLock;
+#[cfg(feature = "debug-borrows")]
+use std::sync::Mutex;
+use key_index::KeyIndex;
 
 mod tests;
 mod compile_tests;
+mod lazy;
+mod aggregate;
+mod attr;
+mod rows;
+mod key_index;
+mod sink;
+mod format;
+mod safe;
+mod traverse;
+pub mod gen;
+#[cfg(feature = "futures")]
+mod stream;
+
+pub use lazy::LazyVecTree;
+pub use aggregate::SubtreeAggregate;
+pub use attr::{AttrTable, InheritedRules, SynthesizedRules};
+pub use rows::{RowInfo, RowIter};
+pub use sink::{TreeBuilder, TreeSink};
+pub use format::{Parenthesized, SExpression, TreeFormat};
+pub use safe::{Children, PostOrder, SafeVecTree};
+pub use traverse::{Traversal, TreeWalker, VecTreeEulerIter, VecTreeTraversalIter};
+#[cfg(feature = "futures")]
+pub use stream::VecTreeStream;
 
 /// A vector-based tree collection type. Each node is of type [`Node<T>`].
+///
+/// A `VecTree<T>` is `Send` if `T` is `Send`, and `Sync` if `T` is `Sync`, so several threads can
+/// traverse the same tree concurrently with the immutable iterators.
 #[derive(Debug)]
 pub struct VecTree<T> {
     nodes: Vec<Node<T>>,
-    borrows: Cell<u32>,
-    root: Option<usize>
+    borrows: BorrowTracker,
+    root: Option<usize>,
+    strict: bool,
+    order: OnceLock<Vec<(usize, u32)>>,
+    levels: OnceLock<Vec<Option<u32>>>,
+    descendants: OnceLock<Vec<usize>>,
+    dirty: Option<Vec<Cell<bool>>>,
+    version: u64,
+    expanded: Vec<bool>,
+    removed: Vec<bool>,
+    visible_counts: OnceLock<Vec<usize>>,
+    key_index: Option<Box<dyn KeyIndex<T>>>,
+    children_pool: Vec<Vec<usize>>,
+    handles: Vec<Option<usize>>,
+    generation: u64,
+    id: u64
 }
 
+// SAFETY: The interior mutability of `VecTree` is only used through a mutable reference:
+//         - the node data in `UnsafeCell` is only modified through `&mut VecTree` (`get_mut`, the
+//           mutable iterators and `split_subtrees_mut`),
+//         - the `borrows` tracker and the dirty flags are only modified by the mutable iterators, which
+//           hold `&mut VecTree`, or directly through `&mut VecTree`; the proxies recorded by the tracker
+//           with the `debug-borrows` feature, which can be read through `&VecTree`, are behind a `Mutex`.
+//         A shared `&VecTree` only gives immutable access, so it can be used by several threads at
+//         once, like a `&Vec<T>`, as long as `T` can be shared.
+unsafe impl<T: Sync> Sync for VecTree<T> {}
+
 /// A node of a [`VecTree<T>`] collection. It holds a data of type `<T>` and a list
 /// of indices to its children in the tree collection.
 #[derive(Debug)]
@@ -138,9 +237,74 @@ pub struct Node<T> {
     children: Vec<usize>
 }
 
+// SAFETY: A shared `&Node` is only obtained from a shared `&VecTree` (see the `Sync` implementation
+//         of `VecTree`), and its data is only modified through `&mut Node`.
+unsafe impl<T: Sync> Sync for Node<T> {}
+
+/// An error reported by the methods that verify the integrity of a [VecTree].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TreeError {
+    /// The node index doesn't exist.
+    BadIndex(usize),
+    /// Attaching `child` to `parent` would create a cycle, because `parent` is `child` itself
+    /// or one of its descendants. When swapping two subtrees, `parent` is an ancestor of `child`.
+    Cycle { parent: usize, child: usize },
+    /// The node `child` already has a parent, `parent`.
+    MultipleParents { child: usize, parent: usize },
+    /// The root node `root` has a parent, `parent`.
+    RootHasParent { root: usize, parent: usize },
+    /// The permutation doesn't have one entry per node, or it doesn't give each node a distinct
+    /// and existing index.
+    BadPermutation,
+    /// The node ID belongs to the tree `node_tree`, not to the tree `tree`.
+    ForeignNode { node_tree: u64, tree: u64 },
+}
+
+/// What happens to the descendants of a removed node (see [`VecTree::filtered()`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrphanPolicy {
+    /// The descendants are removed with the node.
+    Drop,
+    /// The descendants that are kept are attached to the nearest kept ancestor, in place of the
+    /// removed node.
+    Hoist,
+}
+
+/// A lightweight, owned tree branch that can be attached to a [VecTree] in one call with
+/// [`VecTree::add_subtree()`]. It allows to assemble a branch with plain Rust data.
+///
+/// Unlike a [VecTree], a `SubTree` is a recursive structure: dropping or printing a very deep
+/// `SubTree` may overflow the call stack. [`VecTree::add_subtree()`] consumes it without recursion.
+///
+/// # Example
+///
+/// ```
+/// use vectree::{SubTree, VecTree};
+/// let branch = SubTree::new("a", vec![SubTree::leaf("a1"), SubTree::leaf("a2")]);
+/// let mut tree = VecTree::new();
+/// let root = tree.add_root("root");
+/// let a = tree.add_subtree(Some(root), branch);
+/// assert_eq!(tree.children(a), &[2, 3]);
+/// ```
+#[derive(Debug)]
+pub struct SubTree<T> {
+    pub value: T,
+    pub children: Vec<SubTree<T>>
+}
+
+/// An event of the traversal by [`VecTree::visit()`], which gives the pre-order and the post-order
+/// positions of each node in a single pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VisitEvent<P> {
+    /// The traversal enters the node, before visiting its children.
+    Enter(P),
+    /// The traversal leaves the node, after visiting its children.
+    Leave(P)
+}
+
 /// An index holder indicating the direction of the search: up or down. This type is stored
 /// in the stack used by the post-order, depth-first search loop.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 enum VisitNode<T> {
     Down(T),
     Up(T)
@@ -153,7 +317,7 @@ impl<T> VecTree<T> {
     ///
     /// If the number of items is known in advance, prefer the [`VecTree::with_capacity()`] method.
     pub fn new() -> Self {
-        VecTree { nodes: Vec::new(), borrows: Cell::new(0), root: None }
+        VecTree::from_nodes(Vec::new(), None)
     }
 
     /// Creates a new and empty tree with pre-allocated buffer of the specified initial capacity.
@@ -164,7 +328,69 @@ impl<T> VecTree<T> {
     /// `capacity` is not a hard limit; once pre-allocated, it's still possible to add data
     /// beyond the pre-allocated number of items.
     pub fn with_capacity(capacity: usize) -> Self {
-        VecTree { nodes: Vec::with_capacity(capacity), borrows: Cell::new(0), root: None }
+        VecTree::from_nodes(Vec::with_capacity(capacity), None)
+    }
+
+    /// Creates a tree from its raw parts: the value and the children indices of each node, and the
+    /// optional index of the root. It's the reverse of [`VecTree::into_raw_parts()`].
+    ///
+    /// See [`VecTree::try_from_parts()`] for a checked version.
+    ///
+    /// # Safety
+    ///
+    /// The caller must guarantee that the parts describe a valid tree, as verified by [`VecTree::validate()`]:
+    /// all the indices must exist, a node must have at most one parent, the root must have no parent,
+    /// and there must be no cycle. Otherwise, the methods of the tree may panic or never return.
+    pub unsafe fn from_raw_parts(nodes: Vec<(T, Vec<usize>)>, root: Option<usize>) -> Self {
+        VecTree::from_nodes(
+            nodes.into_iter().map(|(value, children)| Node { data: UnsafeCell::new(value), children }).collect(),
+            root
+        )
+    }
+
+    /// Creates a tree from its raw parts: the value and the children indices of each node, and the
+    /// optional index of the root. It's the reverse of [`VecTree::into_raw_parts()`].
+    ///
+    /// Returns an error if the parts don't describe a valid tree (see [`VecTree::validate()`]).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::{TreeError, VecTree};
+    /// let tree = VecTree::try_from_parts(vec![("root", vec![1]), ("a", vec![])], Some(0)).unwrap();
+    /// assert_eq!(tree.children(0), &[1]);
+    /// let (nodes, root) = tree.into_raw_parts();
+    /// assert_eq!(nodes, vec![("root", vec![1]), ("a", vec![])]);
+    /// assert_eq!(root, Some(0));
+    /// let error = VecTree::try_from_parts(vec![("root", vec![1]), ("a", vec![0])], Some(0)).unwrap_err();
+    /// assert_eq!(error, TreeError::RootHasParent { root: 0, parent: 1 });
+    /// ```
+    pub fn try_from_parts(nodes: Vec<(T, Vec<usize>)>, root: Option<usize>) -> Result<Self, TreeError> {
+        // SAFETY: The tree is verified before it's returned.
+        let tree = unsafe { VecTree::from_raw_parts(nodes, root) };
+        tree.validate()?;
+        Ok(tree)
+    }
+
+    /// Deconstructs the tree into its raw parts: the value and the children indices of each node, in
+    /// the order of the buffer, and the optional index of the root.
+    pub fn into_raw_parts(self) -> (Vec<(T, Vec<usize>)>, Option<usize>) {
+        let nodes = self.nodes.into_iter().map(|node| (node.data.into_inner(), node.children)).collect();
+        (nodes, self.root)
+    }
+
+    fn from_nodes(nodes: Vec<Node<T>>, root: Option<usize>) -> Self {
+        VecTree { nodes, borrows: BorrowTracker::default(), root, strict: false, order: OnceLock::new(), levels: OnceLock::new(), descendants: OnceLock::new(), dirty: None, version: 0, expanded: Vec::new(), removed: Vec::new(), visible_counts: OnceLock::new(), key_index: None, children_pool: Vec::new(), handles: Vec::new(), generation: 0, id: new_tree_id() }
+    }
+
+    /// Must be called by every method that modifies the structure of the tree (the nodes, their
+    /// children or the root), before modifying it.
+    fn structure_changed(&mut self) {
+        self.order.take();
+        self.levels.take();
+        self.descendants.take();
+        self.visible_counts.take();
+        self.version += 1;
     }
 
     /// Returns the index of the tree root item, if it exists.
@@ -185,6 +411,12 @@ impl<T> VecTree<T> {
     ///   the user is responsible for preserving the integrity of the tree when doing so.
     pub fn set_root(&mut self, index: usize) -> usize {
         assert!(index < self.nodes.len(), "node index {index} doesn't exist");
+        assert!(!self.is_removed(index), "node {index} has been removed");
+        self.structure_changed();
+        self.check_strict(|tree| match tree.find_parent(index) {
+            Some(parent) => Err(TreeError::RootHasParent { root: index, parent }),
+            None => Ok(())
+        });
         self.root = Some(index);
         index
     }
@@ -204,6 +436,451 @@ impl<T> VecTree<T> {
         self.root.unwrap()
     }
 
+    /// Adds an item above the current root, which becomes its only child, and defines it as the new
+    /// root of the tree. If there's no root, the item is simply added as root. The method returns the
+    /// index of the item.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let expr = tree.add_root("expr");
+    /// let root = tree.add_parent("statement");
+    /// assert_eq!(tree.get_root(), Some(root));
+    /// assert_eq!(tree.children(root), &[expr]);
+    /// ```
+    pub fn add_parent(&mut self, item: T) -> usize {
+        let index = self.push_node(Node::with_children(item, self.root.into_iter().collect()));
+        self.set_root(index)
+    }
+
+    /// Adds an item above the node `node`: the item takes the place of the node in the children of its
+    /// parent, or becomes the root if the node was the root, and the node becomes its only child. The
+    /// method returns the index of the item.
+    ///
+    /// The parent is found by scanning the whole buffer.
+    ///
+    /// Panics if the node doesn't exist or has been removed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("call");
+    /// tree.add(Some(root), "f");
+    /// let arg = tree.add(Some(root), "x");
+    /// let paren = tree.insert_above(arg, "()");
+    /// assert_eq!(tree.children(root), &[1, paren]);
+    /// assert_eq!(tree.children(paren), &[arg]);
+    /// ```
+    pub fn insert_above(&mut self, node: usize, item: T) -> usize {
+        assert!(node < self.nodes.len(), "node index {node} doesn't exist");
+        assert!(!self.is_removed(node), "node {node} has been removed");
+        let parent = self.find_parent(node);
+        let index = self.push_node(Node::with_children(item, vec![node]));
+        if let Some(parent) = parent {
+            for child in self.nodes[parent].children.iter_mut().filter(|child| **child == node) {
+                *child = index;
+            }
+        }
+        if self.root == Some(node) {
+            self.root = Some(index);
+        }
+        index
+    }
+
+    /// Removes the root if it has exactly one child, which becomes the new root, and returns the item
+    /// of the removed root. If there's no root, or if it doesn't have exactly one child, the tree isn't
+    /// modified and the method returns `None`.
+    ///
+    /// The nodes after the removed root are shifted down in the buffer, so their index changes.
+    /// The [handles](Handle) are updated, but [weak references](WeakNodeRef) become invalid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("statement");
+    /// tree.add(Some(root), "expr");
+    /// assert_eq!(tree.take_root(), Some("statement"));
+    /// assert_eq!(tree.get_root(), Some(0));
+    /// assert_eq!(*tree.get(0), "expr");
+    /// assert_eq!(tree.take_root(), None);
+    /// ```
+    pub fn take_root(&mut self) -> Option<T> {
+        let root = self.root?;
+        let &[child] = self.nodes[root].children.as_slice() else {
+            return None;
+        };
+        let mut removed = vec![false; self.nodes.len()];
+        removed[root] = true;
+        self.root = Some(child);
+        self.remove_nodes(&removed).pop()
+    }
+
+    /// Removes the node `index` and returns its item. Its children take its place in the children of
+    /// its parent, in the same order. If the node is the root, it must have exactly one child, which
+    /// becomes the new root.
+    ///
+    /// The nodes after the removed node are shifted down in the buffer, so their index changes.
+    /// The [handles](Handle) are updated, but [weak references](WeakNodeRef) become invalid. The parent
+    /// is found by scanning the whole buffer.
+    ///
+    /// Panics if the node doesn't exist or has been removed, or if it's the root and doesn't have
+    /// exactly one child.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let group = tree.add(Some(root), "group");
+    /// tree.add_iter(Some(group), ["a", "b"]);
+    /// tree.add(Some(root), "c");
+    /// assert_eq!(tree.remove_splice(group), "group");
+    /// let items = tree.iter_children(root).map(|(_, item)| *item).collect::<Vec<_>>();
+    /// assert_eq!(items, ["a", "b", "c"]);
+    /// ```
+    pub fn remove_splice(&mut self, index: usize) -> T {
+        assert!(index < self.nodes.len(), "node index {index} doesn't exist");
+        assert!(!self.is_removed(index), "node {index} has been removed");
+        if self.root == Some(index) {
+            let &[child] = self.nodes[index].children.as_slice() else {
+                panic!("the root node {index} can't be removed unless it has exactly one child");
+            };
+            self.root = Some(child);
+        } else if let Some(parent) = self.find_parent(index) {
+            let children = std::mem::take(&mut self.nodes[index].children);
+            let position = self.nodes[parent].children.iter().position(|&child| child == index).unwrap();
+            self.nodes[parent].children.splice(position..=position, children);
+        }
+        let mut removed = vec![false; self.nodes.len()];
+        removed[index] = true;
+        self.remove_nodes(&removed).pop().unwrap()
+    }
+
+    /// Moves the node `index` and all the nodes below it to a new tree, of which the node is the root,
+    /// and returns that tree. The node is detached from its parent, or the tree has no root any more if
+    /// it was the root. The items are moved, not cloned.
+    ///
+    /// The moved nodes keep their relative order in the buffer of the new tree. The nodes after them are
+    /// shifted down in the buffer of the current tree, so their index changes. The [handles](Handle) are
+    /// updated, but [weak references](WeakNodeRef) become invalid. The parent is found by scanning the
+    /// whole buffer.
+    ///
+    /// Panics if the node doesn't exist or has been removed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root".to_string());
+    /// let a = tree.add(Some(root), "a".to_string());
+    /// tree.add(Some(root), "b".to_string());
+    /// tree.add(Some(a), "a1".to_string());
+    /// let branch = tree.split_off(a);
+    /// assert_eq!(tree.iter_depth_simple().map(|n| n.clone()).collect::<Vec<_>>(), ["b", "root"]);
+    /// assert_eq!(branch.iter_depth_simple().map(|n| n.clone()).collect::<Vec<_>>(), ["a1", "a"]);
+    /// ```
+    pub fn split_off(&mut self, index: usize) -> VecTree<T> {
+        assert!(index < self.nodes.len(), "node index {index} doesn't exist");
+        assert!(!self.is_removed(index), "node {index} has been removed");
+        if self.root == Some(index) {
+            self.root = None;
+        } else if let Some(parent) = self.find_parent(index) {
+            self.nodes[parent].children.retain(|&child| child != index);
+        }
+        let mut moved = vec![false; self.nodes.len()];
+        self.visit_post_order_at(index, |i, _, _| moved[i] = true);
+        let mut new_index = vec![0; self.nodes.len()];
+        for (new, old) in moved.iter().enumerate().filter_map(|(i, &m)| m.then_some(i)).enumerate() {
+            new_index[old] = new;
+        }
+        let children = (0..self.nodes.len())
+            .filter(|&i| moved[i])
+            .map(|i| std::mem::take(&mut self.nodes[i].children).into_iter().map(|child| new_index[child]).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let items = self.remove_nodes(&moved);
+        let nodes = items.into_iter().zip(children).map(|(item, children)| Node::with_children(item, children)).collect();
+        VecTree::from_nodes(nodes, Some(new_index[index]))
+    }
+
+    /// Removes the node `index` and all the nodes below it: the node is detached from its parent's
+    /// children, or the tree has no root any more if it was the root, and the nodes of the subtree are
+    /// marked as removed.
+    ///
+    /// The removed nodes stay in the buffer, so the indices of the other nodes don't change, but they
+    /// aren't visited by the iterators, even those starting at a removed node, and they can't be attached
+    /// again. The [handles](Handle) to the removed nodes can't be resolved any more, and the
+    /// [weak references](WeakNodeRef) become invalid.
+    ///
+    /// The parent is found by scanning the whole buffer.
+    ///
+    /// Panics if the node doesn't exist or if it has already been removed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// let a1 = tree.add(Some(a), "a1");
+    /// let b = tree.add(Some(root), "b");
+    /// tree.remove_subtree(a);
+    /// assert_eq!(tree.children(root), &[b]);
+    /// assert!(tree.is_removed(a1));
+    /// assert_eq!(tree.iter_depth_simple().map(|inode| *inode).collect::<Vec<_>>(), ["b", "root"]);
+    /// assert_eq!(tree.iter_depth_simple_at(a).count(), 0);
+    /// ```
+    pub fn remove_subtree(&mut self, index: usize) {
+        assert!(index < self.nodes.len(), "node index {index} doesn't exist");
+        assert!(!self.is_removed(index), "node {index} has already been removed");
+        self.structure_changed();
+        if let Some(parent) = self.find_parent(index) {
+            self.nodes[parent].children.retain(|&child| child != index);
+        }
+        if self.root == Some(index) {
+            self.root = None;
+        }
+        let mut removed = vec![false; self.nodes.len()];
+        let mut stack = vec![index];
+        while let Some(index) = stack.pop() {
+            // the flag also prevents from looping if the children links are cyclic
+            if !removed[index] {
+                removed[index] = true;
+                stack.extend(&self.nodes[index].children);
+            }
+        }
+        self.mark_removed(&removed);
+    }
+
+    /// Removes the nodes under the root for which `f` returns `false`, keeping the rest of the tree
+    /// consistent. `f` isn't called on the nodes that aren't under the root, which are kept.
+    ///
+    /// `orphan_policy` chooses what happens to the descendants of the removed nodes, like in
+    /// [`VecTree::filtered()`]: they're either removed too, or the kept ones are hoisted to the nearest kept
+    /// ancestor, in place of the removed node. If the root is removed, the tree has no root any more;
+    /// with [`OrphanPolicy::Hoist`], the top kept nodes are then left without parent.
+    ///
+    /// The nodes are removed like with [`VecTree::remove_subtree()`]: they're marked as removed but stay in the
+    /// buffer until [`VecTree::compact()`] is called.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::{OrphanPolicy, VecTree};
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "-a");
+    /// tree.add_iter(Some(a), ["a1", "-a2"]);
+    /// tree.add(Some(root), "b");
+    /// tree.retain(|item| !item.starts_with('-'), OrphanPolicy::Hoist);
+    /// assert_eq!(tree.iter_depth_simple().map(|n| *n).collect::<Vec<_>>(), ["a1", "b", "root"]);
+    /// assert!(tree.is_removed(a));
+    /// ```
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F, orphan_policy: OrphanPolicy) {
+        let Some(root) = self.root else {
+            return;
+        };
+        self.structure_changed();
+        // first pass, top-down, so that `f` isn't called on the descendants of dropped nodes
+        let mut keep = vec![true; self.nodes.len()];
+        let mut order = Vec::new();
+        let mut stack = vec![(root, true)];
+        while let Some((index, alive)) = stack.pop() {
+            keep[index] = alive && f(self.nodes[index].value());
+            order.push(index);
+            let alive = keep[index] || orphan_policy == OrphanPolicy::Hoist;
+            stack.extend(self.nodes[index].children.iter().map(|&child| (child, alive)));
+        }
+        // second pass, bottom-up: each node gives the indices to attach to its parent
+        let mut tops = vec![Vec::new(); self.nodes.len()];
+        for &index in order.iter().rev() {
+            let children = std::mem::take(&mut self.nodes[index].children);
+            let kept_children = children.iter().flat_map(|&child| std::mem::take(&mut tops[child])).collect::<Vec<_>>();
+            if keep[index] {
+                self.nodes[index].children = kept_children;
+                tops[index] = vec![index];
+            } else {
+                // with OrphanPolicy::Drop, `kept_children` is always empty
+                tops[index] = kept_children;
+            }
+        }
+        if !keep[root] {
+            self.root = None;
+        }
+        let removed = keep.iter().map(|&k| !k).collect::<Vec<_>>();
+        self.mark_removed(&removed);
+    }
+
+    /// Removes the subtrees under the root whose top node matches `f`, and returns the number of removed
+    /// nodes. The nodes are visited top-down, and `f` isn't called on the nodes below a matching node,
+    /// which are removed with it. If the root matches, the whole tree is removed and has no root any more.
+    ///
+    /// The nodes are removed like with [`VecTree::remove_subtree()`]: they're marked as removed but stay in the
+    /// buffer until [`VecTree::compact()`] is called.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("doc");
+    /// let comment = tree.add(Some(root), "#comment");
+    /// tree.add_iter(Some(comment), ["a", "b"]);
+    /// tree.add(Some(root), "p");
+    /// assert_eq!(tree.prune(|item| item.starts_with('#')), 3);
+    /// assert_eq!(tree.iter_depth_simple().map(|n| *n).collect::<Vec<_>>(), ["p", "doc"]);
+    /// ```
+    pub fn prune<F: FnMut(&T) -> bool>(&mut self, mut f: F) -> usize {
+        let Some(root) = self.root else {
+            return 0;
+        };
+        self.structure_changed();
+        let mut tops = Vec::new();
+        let mut stack = vec![(root, None)];
+        while let Some((index, parent)) = stack.pop() {
+            if f(self.nodes[index].value()) {
+                tops.push((index, parent));
+            } else {
+                stack.extend(self.nodes[index].children.iter().rev().map(|&child| (child, Some(index))));
+            }
+        }
+        let mut removed = vec![false; self.nodes.len()];
+        let mut count = 0;
+        for (top, parent) in tops {
+            match parent {
+                Some(parent) => self.nodes[parent].children.retain(|&child| child != top),
+                None => self.root = None
+            }
+            let mut stack = vec![top];
+            while let Some(index) = stack.pop() {
+                // the flag also prevents from looping if the children links are cyclic
+                if !removed[index] {
+                    removed[index] = true;
+                    count += 1;
+                    stack.extend(&self.nodes[index].children);
+                }
+            }
+        }
+        self.mark_removed(&removed);
+        count
+    }
+
+    /// Removes all the nodes under the root that are deeper than `max_depth`, the root being at depth 0.
+    /// The nodes at depth `max_depth` become leaves.
+    ///
+    /// The nodes are removed like with [`VecTree::remove_subtree()`]: they're marked as removed but stay in the
+    /// buffer until [`VecTree::compact()`] is called.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// let a1 = tree.add(Some(a), "a1");
+    /// tree.add(Some(a1), "a11");
+    /// tree.truncate_depth(1);
+    /// assert_eq!(tree.iter_depth_simple().map(|n| *n).collect::<Vec<_>>(), ["a", "root"]);
+    /// assert!(tree.is_removed(a1));
+    /// ```
+    pub fn truncate_depth(&mut self, max_depth: u32) {
+        let Some(root) = self.root else {
+            return;
+        };
+        self.structure_changed();
+        let mut removed = vec![false; self.nodes.len()];
+        let mut stack = vec![(root, 0)];
+        while let Some((index, depth)) = stack.pop() {
+            if depth > max_depth {
+                // the flag also prevents from looping if the children links are cyclic
+                if removed[index] {
+                    continue;
+                }
+                removed[index] = true;
+            }
+            let children = &mut self.nodes[index].children;
+            stack.extend(children.iter().map(|&child| (child, depth + 1)));
+            if depth == max_depth {
+                children.clear();
+            }
+        }
+        self.mark_removed(&removed);
+    }
+
+    /// Marks the nodes for which `removed[i]` is `true` as removed, and drops the references to them.
+    fn mark_removed(&mut self, removed: &[bool]) {
+        if self.removed.len() < self.nodes.len() {
+            self.removed.resize(self.nodes.len(), false);
+        }
+        for (flag, &r) in self.removed.iter_mut().zip(removed) {
+            *flag |= r;
+        }
+        for handle in self.handles.iter_mut() {
+            if handle.is_some_and(|index| removed[index]) {
+                *handle = None;
+            }
+        }
+        self.rebuild_index();
+        self.generation += 1;
+    }
+
+    /// Drops the nodes that can't be reached from the root, like the nodes removed by [`VecTree::remove_subtree()`]
+    /// or those left behind when the root is redefined, and shifts the other nodes down in the buffer, keeping
+    /// their order. If the tree has no root, all the nodes are dropped.
+    ///
+    /// `on_remap(old, new)` is called for each node that is kept, in increasing order, with its index before
+    /// and after the compaction, so that the indices stored outside the tree can be updated. The children
+    /// indices, the root and the [handles](Handle) are updated by the tree, and the [weak references](WeakNodeRef)
+    /// become invalid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add(Some(a), "a1");
+    /// let b = tree.add(Some(root), "b");
+    /// tree.remove_subtree(a);
+    /// let mut remap = vec![];
+    /// tree.compact(|old, new| remap.push((old, new)));
+    /// assert_eq!(remap, [(0, 0), (3, 1)]);
+    /// assert_eq!(tree.len(), 2);
+    /// assert_eq!(*tree.get(1), "b");
+    /// assert_eq!(tree.children(0), &[1]);
+    /// ```
+    pub fn compact<F: FnMut(usize, usize)>(&mut self, mut on_remap: F) {
+        let mut removed = vec![true; self.nodes.len()];
+        if let Some(root) = self.root {
+            self.visit_post_order_at(root, |index, _, _| removed[index] = false);
+        }
+        let kept = removed.iter().enumerate().filter_map(|(index, &r)| (!r).then_some(index));
+        for (new, old) in kept.enumerate() {
+            on_remap(old, new);
+        }
+        self.remove_nodes(&removed);
+    }
+
+    /// Returns `true` if the node `index` has been removed by [`VecTree::remove_subtree()`].
+    pub fn is_removed(&self, index: usize) -> bool {
+        self.removed.get(index).copied().unwrap_or(false)
+    }
+
+    /// Returns `top` unless it's a removed node, from which there's nothing to visit.
+    fn live_top(&self, top: Option<usize>) -> Option<usize> {
+        top.filter(|&top| !self.is_removed(top))
+    }
+
     /// Adds an item to the tree and returns its index.
     ///
     /// If `parent_index` is provided (not `None`), the item is added to the parent's list of children.
@@ -211,15 +888,72 @@ impl<T> VecTree<T> {
     /// buffer size, the method panics. If `parent_index` is `None`, the item must be attached to
     /// the tree another way.
     pub fn add(&mut self, parent_index: Option<usize>, item: T) -> usize {
+        self.structure_changed();
         let index = self.nodes.len();
         if let Some(parent_index) = parent_index {
             self.nodes[parent_index].children.push(index);
         }
-        let node = Node { data: UnsafeCell::new(item), children: Vec::new() };
+        let node = Node { data: UnsafeCell::new(item), children: self.children_pool.pop().unwrap_or_default() };
         self.nodes.push(node);
+        self.index_added(index);
+        index
+    }
+
+    /// Adds an item to the tree as a child of the node `parent_index`, at `position` in its children, and
+    /// returns the item's index. The children at `position` and after are shifted to the right.
+    ///
+    /// Panics if the parent doesn't exist or if `position` is greater than its number of children.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("ul");
+    /// let b = tree.add(Some(root), "li b");
+    /// let a = tree.insert_child_at(root, 0, "li a");
+    /// assert_eq!(tree.children(root), &[a, b]);
+    /// ```
+    pub fn insert_child_at(&mut self, parent_index: usize, position: usize, item: T) -> usize {
+        self.check_position(parent_index, position);
+        let index = self.add(None, item);
+        self.nodes[parent_index].children.insert(position, index);
         index
     }
 
+    /// Panics if `position` isn't a valid insertion position in the children of the node `parent_index`.
+    fn check_position(&self, parent_index: usize, position: usize) {
+        assert!(parent_index < self.nodes.len(), "node index {parent_index} doesn't exist");
+        let num_children = self.nodes[parent_index].children.len();
+        assert!(position <= num_children, "position {position} is out of bounds (node {parent_index} has {num_children} children)");
+    }
+
+    /// Adds a node at the end of the buffer and returns its index. The node isn't attached to any
+    /// parent.
+    ///
+    /// The children indices of the node aren't verified, since they may refer to nodes that are
+    /// pushed later. [`VecTree::validate()`] can verify the tree once all the nodes are pushed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::{Node, VecTree};
+    /// let nodes = vec![Node::with_children("root", vec![1, 2]), Node::new("a"), Node::new("b")];
+    /// let mut tree = VecTree::new();
+    /// for node in nodes {
+    ///     tree.push_node(node);
+    /// }
+    /// tree.set_root(0);
+    /// assert_eq!(tree.validate(), Ok(()));
+    /// assert_eq!(tree.children(0), &[1, 2]);
+    /// ```
+    pub fn push_node(&mut self, node: Node<T>) -> usize {
+        self.structure_changed();
+        self.nodes.push(node);
+        self.index_added(self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
     /// Adds an item and its child to the tree, and returns the item's index.
     ///
     /// If `parent_index` is provided (not `None`), the item is added to the parent's list of children.
@@ -240,6 +974,7 @@ impl<T> VecTree<T> {
     /// the tree another way.
     pub fn addci(&mut self, parent_index: Option<usize>, item: T, child_id: usize) -> usize {
         assert!(child_id < self.len(), "child node index {child_id} doesn't exist");
+        self.check_strict(|tree| tree.check_orphan(child_id));
         let node_id = self.add(parent_index, item);
         self.nodes[node_id].children.push(child_id);
         node_id
@@ -255,6 +990,7 @@ impl<T> VecTree<T> {
         let node_id = self.add(parent_index, item);
         for child_id in children_id {
             assert!(child_id < self.len(), "child node index {child_id} doesn't exist");
+            self.check_strict(|tree| tree.check_orphan(child_id));
             self.nodes[node_id].children.push(child_id);
         }
         node_id
@@ -286,513 +1022,3410 @@ impl<T> VecTree<T> {
         index
     }
 
-    /// Attaches one extra existing child to an existing parent.
-    pub fn attach_child(&mut self, parent_index: usize, child_index: usize) {
-        self.nodes[parent_index].children.push(child_index);
-    }
-
-    /// Attaches extra existing children to an existing parent.
-    pub fn attach_children<U: IntoIterator<Item = usize>>(&mut self, parent_index: usize, children_index: U) {
-        self.nodes[parent_index].children.extend(children_index);
+    /// Adds a branch to the tree and returns the index of its top item. The items are moved from
+    /// the [SubTree], and each parent is added before its children.
+    ///
+    /// If `parent_index` is provided (not `None`), the top item is added to the parent's list of children.
+    /// If that parent doesn't exist, or in other words, if the value of `parent_index` is too big for the
+    /// buffer size, the method panics. If `parent_index` is `None`, the top item must be attached to
+    /// the tree another way.
+    pub fn add_subtree(&mut self, parent_index: Option<usize>, subtree: SubTree<T>) -> usize {
+        self.add_from_recursive(parent_index, subtree, |subtree| (subtree.value, subtree.children))
     }
 
-    /// Returns the number of items in the tree buffer.
+    /// Adds the nodes of an external recursive structure to the tree and returns the index of the top
+    /// item. `f` converts each element of the structure, starting with `top`, into an item and the
+    /// elements of its children.
     ///
-    /// Note that this method only returns the number of items in the tree, as defined by its current root, if
-    /// all items are children of the root to some degree. If there are loose items that have no relationship
-    /// with the root, the actual number of items (nodes) in the tree can be obtained by counting the iterations.
-    pub fn len(&self) -> usize {
-        self.nodes.len()
+    /// The structure is converted with a work stack instead of recursion, so it can't overflow the
+    /// call stack, whatever its depth. The reverse conversion can be made with [`VecTree::walk_recursive()`].
+    ///
+    /// If `parent_index` is provided (not `None`), the top item is added to the parent's list of children.
+    /// If that parent doesn't exist, or in other words, if the value of `parent_index` is too big for the
+    /// buffer size, the method panics. If `parent_index` is `None`, the top item must be attached to
+    /// the tree another way.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// enum List { Cons(i32, Box<List>), Nil }
+    /// let list = List::Cons(1, Box::new(List::Cons(2, Box::new(List::Nil))));
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_from_recursive(None, list, |item| match item {
+    ///     List::Cons(value, next) => (value, vec![*next]),
+    ///     List::Nil => (0, vec![])
+    /// });
+    /// tree.set_root(root);
+    /// assert_eq!(tree.iter_depth_simple().map(|inode| *inode).collect::<Vec<_>>(), [0, 2, 1]);
+    /// ```
+    pub fn add_from_recursive<S, F>(&mut self, parent_index: Option<usize>, top: S, mut f: F) -> usize
+    where
+        F: FnMut(S) -> (T, Vec<S>)
+    {
+        let (value, children) = f(top);
+        let top_index = self.add(parent_index, value);
+        let mut stack = vec![(top_index, children.into_iter())];
+        while let Some((parent, children)) = stack.last_mut() {
+            let parent = *parent;
+            if let Some(child) = children.next() {
+                let (value, children) = f(child);
+                let index = self.add(Some(parent), value);
+                stack.push((index, children.into_iter()));
+            } else {
+                stack.pop();
+            }
+        }
+        top_index
     }
 
-    /// Returns `true` if the tree buffer contains no items.
-    pub fn is_empty(&self) -> bool {
-        self.nodes.is_empty()
+    /// Creates a tree from an external recursive structure, with `top` as root (see
+    /// [`VecTree::add_from_recursive()`]).
+    pub fn from_recursive<S, F>(top: S, f: F) -> Self
+    where
+        F: FnMut(S) -> (T, Vec<S>)
+    {
+        let mut tree = VecTree::new();
+        let root = tree.add_from_recursive(None, top, f);
+        tree.set_root(root);
+        tree
     }
 
-    /// Calculates the tree depth, which is the maximum number of levels (not including the root).
+    /// Moves all the items of another `VecTree` into the current one and returns the new index of
+    /// the other tree's root. Unlike [`VecTree::add_from_tree()`], this method doesn't require
+    /// `T: Clone` since it consumes the other tree.
     ///
-    /// Notes:
-    /// * The depth returned by the iterators are zero-based, and thus `iterator.depth` is between `0` and `tree.depth()`.
-    /// * This method iterates over all the nodes, so it's not time-effective.
+    /// The items are appended to the buffer in the same order, so their indices are simply offset by
+    /// the size of the current buffer before the call.
     ///
-    /// Returns `None` if the tree has no root.
-    pub fn depth(&self) -> Option<u32> {
-        self.iter_depth_simple().map(|x| x.depth).max()
+    /// If `parent_index` is provided (not `None`), the other root is added to the parent's list of children.
+    /// If that parent doesn't exist, or in other words, if the value of `parent_index` is too big for the
+    /// buffer size, the method panics. If `parent_index` is `None`, the other root must be attached to
+    /// the current tree another way.
+    ///
+    /// The method panics if the other tree has no root.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let mut other = VecTree::new();
+    /// let a = other.add_root("a");
+    /// other.add(Some(a), "a1");
+    /// let a = tree.append_tree(Some(root), other);
+    /// assert_eq!(a, 1);
+    /// assert_eq!(tree.children(a), &[2]);
+    /// ```
+    pub fn append_tree(&mut self, parent_index: Option<usize>, other: VecTree<T>) -> usize {
+        let other_root = other.root.expect("the other tree has no root");
+        if let Some(parent_index) = parent_index {
+            assert!(parent_index < self.len(), "node index {parent_index} doesn't exist");
+        }
+        let offset = self.append_nodes(other);
+        let index = other_root + offset;
+        if let Some(parent_index) = parent_index {
+            self.nodes[parent_index].children.push(index);
+        }
+        index
     }
 
-    /// Returns a reference to the item stored at the given index.
+    /// Creates a tree by moving the items of several trees under a new root, `root_item`, in the order
+    /// given by `trees`. The roots of the trees are the children of the new root, which is the last
+    /// item of the buffer. The trees without root are ignored.
     ///
-    /// Panics if the index is out of the buffer bounds.
-    pub fn get(&self, index: usize) -> &T {
-        // SAFETY: The access to the `UnsafeCell<T> data` field is secured by the compiler:
-        //         the method can't be called if a mutable borrow is alive (either given by .get_mut or
-        //         by a NodeProxyMut)
-        unsafe { &*self.nodes.get(index).unwrap().data.get() }
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let trees = ["a", "b"].map(|s| {
+    ///     let mut tree = VecTree::new();
+    ///     let root = tree.add_root(s.to_string());
+    ///     tree.add(Some(root), format!("{s}1"));
+    ///     tree
+    /// });
+    /// let tree = VecTree::concat("root".to_string(), trees);
+    /// assert_eq!(tree.get_root(), Some(4));
+    /// assert_eq!(tree.children(4), &[0, 2]);
+    /// ```
+    pub fn concat<U: IntoIterator<Item = VecTree<T>>>(root_item: T, trees: U) -> Self {
+        let (mut tree, roots) = VecTree::concat_forest(trees);
+        let root = tree.addci_iter(None, root_item, roots.into_iter().flatten());
+        tree.set_root(root);
+        tree
     }
 
-    /// Returns a mutable reference to the item stored at the given index.
+    /// Creates a forest by moving the items of several trees in the same buffer, in the order given
+    /// by `trees`. The method returns the new tree, which has no root, and the new index of each
+    /// tree's root (or `None` for the trees that had none).
     ///
-    /// Panics if the index is out of the buffer bounds.
-    pub fn get_mut(&mut self, index: usize) -> &mut T {
-        self.nodes.get_mut(index).unwrap().data.get_mut()
+    /// The roots can then be attached to other items, or one of them can be selected as root with
+    /// [`VecTree::set_root()`].
+    pub fn concat_forest<U: IntoIterator<Item = VecTree<T>>>(trees: U) -> (Self, Vec<Option<usize>>) {
+        let trees = trees.into_iter().collect::<Vec<_>>();
+        let mut forest = VecTree::with_capacity(trees.iter().map(|t| t.len()).sum::<usize>() + 1);
+        let mut roots = Vec::with_capacity(trees.len());
+        for tree in trees {
+            let root = tree.root;
+            let offset = forest.append_nodes(tree);
+            roots.push(root.map(|r| r + offset));
+        }
+        (forest, roots)
     }
 
-    /// Returns a reference to the item's children.
-    ///
-    /// Panics if the index is out of the buffer bounds.
-    pub fn children(&self, index: usize) -> &[usize] {
-        self.nodes.get(index).unwrap().children.as_slice()
+    /// Moves all the nodes of another tree at the end of the buffer, offsetting their children indices,
+    /// and returns the offset. The removed nodes of the other tree remain removed.
+    fn append_nodes(&mut self, other: VecTree<T>) -> usize {
+        self.structure_changed();
+        let offset = self.nodes.len();
+        if !other.removed.is_empty() {
+            self.removed.resize(offset, false);
+            self.removed.extend(&other.removed);
+        }
+        self.nodes.extend(other.nodes.into_iter().map(|mut node| {
+            for child in node.children.iter_mut() {
+                *child += offset;
+            }
+            node
+        }));
+        self.index_added(offset);
+        offset
     }
 
-    /// Returns a mutable reference to the item's children.
-    ///
-    /// Panics if the index is out of the buffer bounds.
-    pub fn children_mut(&mut self, index: usize) -> &mut Vec<usize> {
-        &mut self.nodes.get_mut(index).unwrap().children
+    /// Attaches one extra existing child to an existing parent.
+    pub fn attach_child(&mut self, parent_index: usize, child_index: usize) {
+        self.structure_changed();
+        self.check_strict(|tree| tree.check_attach(parent_index, child_index));
+        self.debug_check_attach(parent_index, child_index);
+        self.nodes[parent_index].children.push(child_index);
     }
 
-    /// Returns an iterator to the item's children, by reference.
+    /// Attaches one extra existing child to an existing parent, at `position` in its children. The
+    /// children at `position` and after are shifted to the right.
     ///
-    /// Panics if the index is out of the buffer bounds.
-    pub fn iter_children(&self, index: usize) -> impl DoubleEndedIterator<Item = &Node<T>> {
-        self.nodes.get(index).unwrap().children.iter().map(|&i| self.nodes.get(i).unwrap())
+    /// Panics if `position` is greater than the number of children of the parent.
+    pub fn attach_child_at(&mut self, parent_index: usize, position: usize, child_index: usize) {
+        self.check_position(parent_index, position);
+        self.structure_changed();
+        self.check_strict(|tree| tree.check_attach(parent_index, child_index));
+        self.debug_check_attach(parent_index, child_index);
+        self.nodes[parent_index].children.insert(position, child_index);
     }
-}
 
-impl<T: Clone> VecTree<T> {
-    /// Adds items from another `VecTree` and returns the index of the top item. This method
-    /// can be used to copy another tree or part of another tree into the current one.
-    ///
-    /// The items are cloned from the other tree. If `top` is not `None`, it contains the index of
-    /// the top element that is copied from the other tree. If `top` is `None`, the whole tree is
-    /// added.
+    /// Attaches extra existing children to an existing parent.
+    pub fn attach_children<U: IntoIterator<Item = usize>>(&mut self, parent_index: usize, children_index: U) {
+        if self.strict {
+            for child_index in children_index {
+                self.attach_child(parent_index, child_index);
+            }
+        } else {
+            self.structure_changed();
+            let start = self.nodes[parent_index].children.len();
+            self.nodes[parent_index].children.extend(children_index);
+            if cfg!(debug_assertions) {
+                for i in start..self.nodes[parent_index].children.len() {
+                    self.debug_check_attach(parent_index, self.nodes[parent_index].children[i]);
+                }
+            }
+        }
+    }
+
+    /// Detaches the node `child_index` from the children of the node `parent_index`, without removing it
+    /// from the tree, so that it can be attached again elsewhere with its subtree. Returns `false` if
+    /// `child_index` isn't a child of `parent_index`.
     ///
-    /// If `parent_index` is provided (not `None`), the top item is added to the parent's list of children.
-    /// If that parent doesn't exist, or in other words, if the value of `parent_index` is too big for the
-    /// buffer size, the method panics. If `parent_index` is `None`, the top item must be attached to
-    /// the current tree another way.
+    /// Panics if the parent doesn't exist.
     ///
     /// # Example
     ///
-    /// ```ignore
+    /// ```
     /// use vectree::VecTree;
     /// let mut tree = VecTree::new();
-    /// let root = tree.add_root("root".to_string());
-    /// let a = tree.add(Some(root), "a".to_string());
-    /// let b = tree.add(Some(root), "b".to_string());
-    /// let _ = tree.add(Some(root), "c".to_string());
-    /// tree.add_iter(Some(a), ["a1".to_string(), "a2".to_string()]);
-    /// // => tree:  root(a(a1, a2), b, c)
-    /// let other = tree.clone();
-    /// tree.add_from_tree(Some(b), &other, Some(a));
-    /// // => tree: root(a(a1, a2), b(a(a1, a2)), c)
-    /// //                           ^^^^^^^^^^^
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// let b = tree.add(Some(root), "b");
+    /// let a1 = tree.add(Some(a), "a1");
+    /// assert!(tree.detach_child(a, a1));
+    /// assert!(!tree.detach_child(a, a1));
+    /// tree.attach_child(b, a1);
+    /// assert_eq!(tree.children(b), &[a1]);
     /// ```
-    pub fn add_from_tree(&mut self, parent_index: Option<usize>, tree: &VecTree<T>, top: Option<usize>) -> usize {
-        self.add_from_tree_iter(parent_index, tree.iter_depth_at(top.unwrap_or_else(|| tree.get_root().unwrap())))
+    pub fn detach_child(&mut self, parent_index: usize, child_index: usize) -> bool {
+        assert!(parent_index < self.nodes.len(), "node index {parent_index} doesn't exist");
+        match self.nodes[parent_index].children.iter().position(|&child| child == child_index) {
+            Some(position) => {
+                self.detach_child_at(parent_index, position);
+                true
+            }
+            None => false
+        }
     }
 
-    /// Adds items from a `VecTree` iterator and returns the index of the top item. This method
-    /// can be used to copy another tree or part of another tree into the current one.
-    ///
-    /// # Example
+    /// Detaches the child at `position` in the children of the node `parent_index`, without removing it
+    /// from the tree, and returns its index (see [`VecTree::detach_child()`]).
     ///
-    /// ```ignore
-    /// use vectree::VecTree;
-    /// let mut tree = VecTree::new();
-    /// let root = tree.add_root("root".to_string());
-    /// let a = tree.add(Some(root), "a".to_string());
-    /// let b = tree.add(Some(root), "b".to_string());
-    /// let _ = tree.add(Some(root), "c".to_string());
-    /// tree.add_iter(Some(a), ["a1".to_string(), "a2".to_string()]);
-    /// // => tree:  root(a(a1, a2), b, c)
-    /// let other = tree.clone();
-    /// tree.add_from_tree_iter(Some(b), other.iter_depth_at(a));
-    /// // => tree: root(a(a1, a2), b(a(a1, a2)), c)
-    /// //                           ^^^^^^^^^^^
-    /// ```
-    pub fn add_from_tree_iter<'a, U>(&mut self, parent_index: Option<usize>, items: U) -> usize
-    where
-        U: IntoIterator<Item=NodeProxy<'a, T>>,
-        T: 'a
-    {
-        self.add_from_tree_iter_callback(parent_index, items, move |_, _, _| {})
+    /// Panics if the parent doesn't exist or if `position` is out of bounds.
+    pub fn detach_child_at(&mut self, parent_index: usize, position: usize) -> usize {
+        assert!(parent_index < self.nodes.len(), "node index {parent_index} doesn't exist");
+        let num_children = self.nodes[parent_index].children.len();
+        assert!(position < num_children, "position {position} is out of bounds (node {parent_index} has {num_children} children)");
+        self.structure_changed();
+        self.nodes[parent_index].children.remove(position)
     }
 
-    /// Adds items from another `VecTree` and returns the index of the top item. This method
-    /// can be used to copy another tree or part of another tree into the current one.
+    /// Moves the node `node` and its subtree from its current parent, if any, to the children of the node
+    /// `new_parent`, at `position` or at the end if `position` is `None`. The position is taken in the
+    /// children of `new_parent` once `node` has been detached from them, if it was one of them.
     ///
-    /// For further details, see [VecTree::add_from_tree].
+    /// If one of the nodes doesn't exist or has been removed, if `node` is the root, or if `new_parent` is
+    /// `node` itself or one of its descendants, the tree isn't modified and the method returns an error.
     ///
-    /// The callback function, `f(to, from, item)` gives the following parameters:
-    /// * `to: usize` is the index where the new item will be stored in the destination tree
-    /// * `from: usize` is the index where the original item is in the source tree
-    /// * `item: &T` is a reference to the item in the source tree
+    /// The current parent is found by scanning the whole buffer.
+    ///
+    /// Panics if `position` is greater than the number of children of `new_parent`.
     ///
     /// # Example
     ///
     /// ```
-    /// use vectree::VecTree;
-    /// # fn main() {
+    /// use vectree::{TreeError, VecTree};
     /// let mut tree = VecTree::new();
-    /// let root = tree.add_root("root".to_string());
-    /// let a = tree.add(Some(root), "a".to_string());
-    /// tree.add(Some(a), "a1".to_string());
-    /// tree.add(Some(root), "b".to_string());
-    /// let mut result = Vec::<(usize, usize, String)>::new();
-    /// let mut other = VecTree::new();
-    /// other.add_from_tree_callback(
-    ///     None,
-    ///     &tree,
-    ///     Some(a),
-    ///     |to, from, item| result.push((to, from, item.clone())) );
-    /// assert_eq!(result, vec![(0, 2, "a1".to_string()), (1, 1, "a".to_string())]);
-    /// # }
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// let b = tree.add(Some(root), "b");
+    /// let a1 = tree.add(Some(a), "a1");
+    /// assert_eq!(tree.reparent(a1, root, Some(0)), Ok(()));
+    /// assert_eq!(tree.children(root), &[a1, a, b]);
+    /// assert_eq!(tree.reparent(root, a, None), Err(TreeError::RootHasParent { root, parent: a }));
+    /// tree.reparent(b, a, None).unwrap();
+    /// assert_eq!(tree.reparent(a, b, None), Err(TreeError::Cycle { parent: b, child: a }));
     /// ```
-    pub fn add_from_tree_callback<'a, F>(&mut self, parent_index: Option<usize>, tree: &'a VecTree<T>, top: Option<usize>, f: F) -> usize
-    where
-        F: FnMut(usize, usize, &T),
-        T: 'a
-    {
-        self.add_from_tree_iter_callback(parent_index, tree.iter_depth_at(top.unwrap_or_else(|| tree.get_root().unwrap())), f)
+    pub fn reparent(&mut self, node: usize, new_parent: usize, position: Option<usize>) -> Result<(), TreeError> {
+        for index in [node, new_parent] {
+            if index >= self.nodes.len() || self.is_removed(index) {
+                return Err(TreeError::BadIndex(index));
+            }
+        }
+        if self.root == Some(node) {
+            return Err(TreeError::RootHasParent { root: node, parent: new_parent });
+        }
+        self.check_cycle(new_parent, node)?;
+        let old_parent = self.find_parent(node);
+        let num_children = self.nodes[new_parent].children.len() - (old_parent == Some(new_parent)) as usize;
+        let position = position.unwrap_or(num_children);
+        assert!(position <= num_children, "position {position} is out of bounds (node {new_parent} has {num_children} other children)");
+        self.structure_changed();
+        if let Some(old_parent) = old_parent {
+            self.nodes[old_parent].children.retain(|&child| child != node);
+        }
+        self.nodes[new_parent].children.insert(position, node);
+        Ok(())
     }
 
-    /// Adds items from a `VecTree` iterator and returns the index of the top item. This method
-    /// can be used to copy another tree or part of another tree into the current one.
+    /// Swaps the positions of the nodes `a` and `b`, with their subtrees: each node takes the place of the
+    /// other in the children of its parent. If one of them is the root, the other one becomes the root.
     ///
-    /// The callback function, `f(to, from, item)` gives the following parameters:
-    /// * `to: usize` is the index where the new item will be stored in the destination tree
-    /// * `from: usize` is the index where the original item is in the source tree
-    /// * `item: &T` is a reference to the item in the source tree
+    /// If one of the nodes doesn't exist or has been removed, the tree isn't modified and the method returns
+    /// [TreeError::BadIndex]. If one of them is under the other, it returns [TreeError::Cycle] with the
+    /// ancestor as `parent` and the descendant as `child`.
     ///
+    /// The parents are found by scanning the whole buffer.
     ///
     /// # Example
     ///
     /// ```
-    /// use vectree::VecTree;
-    /// # fn main() {
+    /// use vectree::{TreeError, VecTree};
     /// let mut tree = VecTree::new();
-    /// let root = tree.add_root("root".to_string());
-    /// let a = tree.add(Some(root), "a".to_string());
-    /// tree.add(Some(a), "a1".to_string());
-    /// tree.add(Some(root), "b".to_string());
-    /// let mut result = Vec::<(usize, usize, String)>::new();
-    /// let mut other = VecTree::new();
-    /// other.add_from_tree_iter_callback(
-    ///     None,
-    ///     tree.iter_depth_at(a),
-    ///     |to, from, item| result.push((to, from, item.clone())) );
-    /// assert_eq!(result, vec![(0, 2, "a1".to_string()), (1, 1, "a".to_string())]);
-    /// # }
+    /// let root = tree.add_root("+");
+    /// let a = tree.add(Some(root), "*");
+    /// let b = tree.add(Some(root), "c");
+    /// let a1 = tree.add(Some(a), "a");
+    /// tree.add(Some(a), "b");
+    /// assert_eq!(tree.swap_subtrees(a, b), Ok(()));
+    /// assert_eq!(tree.children(root), &[b, a]);
+    /// assert_eq!(tree.swap_subtrees(a1, a), Err(TreeError::Cycle { parent: a, child: a1 }));
     /// ```
-    pub fn add_from_tree_iter_callback<'a, U, F>(&mut self, parent_index: Option<usize>, items: U, mut f: F) -> usize
-    where
-        U: IntoIterator<Item=NodeProxy<'a, T>>,
-        T: 'a,
-        F: FnMut(usize, usize, &T),
-    {
-        let mut stack = Vec::<usize>::new();
-        for item in items {
-            let node = item.deref().clone();
-            let num_children = item.num_children();
-            f(self.nodes.len(), item.index, item.deref());
-            let index = if num_children > 0 {
-                let children = stack.split_off(stack.len() - num_children);
-                self.addci_iter(None, node, children)
-            } else {
-                self.add(None, node)
-            };
-            stack.push(index);
+    pub fn swap_subtrees(&mut self, a: usize, b: usize) -> Result<(), TreeError> {
+        for index in [a, b] {
+            if index >= self.nodes.len() || self.is_removed(index) {
+                return Err(TreeError::BadIndex(index));
+            }
         }
-        assert_eq!(stack.len(), 1, "something is wrong with the structure of the provided items");
-        let index = stack.pop().unwrap();
-        if let Some(parent) = parent_index {
-            self.nodes[parent].children.push(index);
+        if a == b {
+            return Ok(());
         }
-        index
+        for (ancestor, descendant) in [(a, b), (b, a)] {
+            if self.iter_depth_simple_at(ancestor).any(|inode| inode.index == descendant) {
+                return Err(TreeError::Cycle { parent: ancestor, child: descendant });
+            }
+        }
+        let parent_a = self.find_parent(a);
+        let parent_b = self.find_parent(b);
+        self.structure_changed();
+        let position = |tree: &Self, parent: Option<usize>, child: usize| {
+            parent.map(|parent| (parent, tree.nodes[parent].children.iter().position(|&c| c == child).unwrap()))
+        };
+        let position_a = position(self, parent_a, a);
+        let position_b = position(self, parent_b, b);
+        if let Some((parent, pos)) = position_a {
+            self.nodes[parent].children[pos] = b;
+        }
+        if let Some((parent, pos)) = position_b {
+            self.nodes[parent].children[pos] = a;
+        }
+        if self.root == Some(a) {
+            self.root = Some(b);
+        } else if self.root == Some(b) {
+            self.root = Some(a);
+        }
+        Ok(())
     }
-}
 
-impl<T> Node<T> {
-    /// Returns `true` if the node has children.
-    pub fn has_children(&self) -> bool {
-        !self.children.is_empty()
+    /// Makes the cheap verifications of an attachment in debug builds.
+    fn debug_check_attach(&self, parent_index: usize, child_index: usize) {
+        debug_assert!(child_index < self.nodes.len(), "node index {child_index} doesn't exist");
+        debug_assert_ne!(parent_index, child_index, "node {child_index} can't be attached to itself");
+        debug_assert!(!self.is_removed(child_index), "node {child_index} has been removed");
     }
 
-    /// Returns a reference slice to the children's indices.
-    pub fn children(&self) -> &[usize] {
-        &self.children
+    /// Verifies the whole tree if the `debug-validate` feature is enabled.
+    fn debug_validate(&self) {
+        #[cfg(feature = "debug-validate")]
+        if let Err(e) = self.validate() {
+            panic!("tree integrity: {e}");
+        }
     }
-}
 
-impl<T> Index<usize> for VecTree<T> {
-    type Output = Node<T>;
+    /// Attaches one extra existing child to an existing parent, after verifying that
+    /// * both nodes exist,
+    /// * the child doesn't already have a parent,
+    /// * the attachment doesn't create a cycle, which happens if the parent is the child or one of its descendants.
+    ///
+    /// If one of those conditions isn't met, the tree isn't modified and the method returns an error.
+    ///
+    /// Since the method must find whether the child already has a parent, it scans the whole buffer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::{TreeError, VecTree};
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// let b = tree.add(None, "b");
+    /// assert_eq!(tree.try_attach_child(a, root), Err(TreeError::Cycle { parent: a, child: root }));
+    /// assert_eq!(tree.try_attach_child(root, a), Err(TreeError::MultipleParents { child: a, parent: root }));
+    /// assert_eq!(tree.try_attach_child(a, b), Ok(()));
+    /// assert_eq!(tree.children(a), &[b]);
+    /// ```
+    pub fn try_attach_child(&mut self, parent_index: usize, child_index: usize) -> Result<(), TreeError> {
+        self.check_attach(parent_index, child_index)?;
+        self.structure_changed();
+        self.nodes[parent_index].children.push(child_index);
+        Ok(())
+    }
 
-    fn index(&self, index: usize) -> &Self::Output {
-        self.nodes.get(index).unwrap()
+    /// Verifies that the node `child_index` can be attached to the node `parent_index` without
+    /// breaking the integrity of the tree.
+    fn check_attach(&self, parent_index: usize, child_index: usize) -> Result<(), TreeError> {
+        for index in [parent_index, child_index] {
+            if index >= self.nodes.len() || self.is_removed(index) {
+                return Err(TreeError::BadIndex(index));
+            }
+        }
+        self.check_orphan(child_index)?;
+        self.check_cycle(parent_index, child_index)
     }
-}
 
-impl<T> IndexMut<usize> for VecTree<T> {
-    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        self.nodes.get_mut(index).unwrap()
+    /// Verifies that attaching the node `child_index` to the node `parent_index` doesn't create a cycle.
+    fn check_cycle(&self, parent_index: usize, child_index: usize) -> Result<(), TreeError> {
+        if self.iter_depth_simple_at(child_index).any(|inode| inode.index == parent_index) {
+            return Err(TreeError::Cycle { parent: parent_index, child: child_index });
+        }
+        Ok(())
     }
-}
 
-impl<T: Clone> Clone for VecTree<T> {
-    fn clone(&self) -> Self {
-        VecTree {
-            nodes: self.nodes.clone(),
-            borrows: Cell::new(0),
-            root: self.root
+    /// Verifies that the node `index` exists and has no parent.
+    fn check_orphan(&self, index: usize) -> Result<(), TreeError> {
+        if index >= self.nodes.len() || self.is_removed(index) {
+            return Err(TreeError::BadIndex(index));
+        }
+        match self.find_parent(index) {
+            Some(parent) => Err(TreeError::MultipleParents { child: index, parent }),
+            None => Ok(())
         }
     }
-}
 
-impl<T> Default for VecTree<T> {
-    fn default() -> Self {
-        VecTree::new()
+    /// Finds the first node that has `index` as a child by scanning the whole buffer.
+    fn find_parent(&self, index: usize) -> Option<usize> {
+        self.nodes.iter().position(|node| node.children.contains(&index))
     }
-}
 
-impl<T: Clone> Clone for Node<T> {
-    fn clone(&self) -> Self {
-        Node {
-            // SAFETY: We're cloning, so there is no reference to the newly created field.
-            data: UnsafeCell::new(unsafe { (*self.data.get()).clone() }),
-            children: self.children.clone()
-        }
+    /// Enables or disables the strict mode, in which every structural modification of the tree is
+    /// verified. The method panics at once if a modification breaks the integrity of the tree,
+    /// which catches the corruption where it happens rather than during a later traversal.
+    ///
+    /// The following modifications are verified:
+    /// * the children given to [`VecTree::addci()`] and [`VecTree::addci_iter()`] must have no parent,
+    /// * the children given to [`VecTree::attach_child()`], [`VecTree::attach_child_at()`] and
+    ///   [`VecTree::attach_children()`] must exist, have no parent, and must not create a cycle (see
+    ///   [`VecTree::try_attach_child()`]); in particular, a child can't be attached twice to the same parent,
+    /// * the node given to [`VecTree::set_root()`] must have no parent.
+    ///
+    /// Those verifications scan the whole buffer to find the parent of a node, so the strict mode is
+    /// meant to debug the code that builds the tree.
+    ///
+    /// The modifications made through [`VecTree::children_mut()`] can't be verified, but the whole tree
+    /// can be verified at any time with [`VecTree::validate()`].
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
     }
-}
 
-// This trait is used as a bound for both usize and &usize. It would be otherwise impossible
-// to implement From for iterators on both types (except by using Borrow, which produces
-// the same optimized code but is conceptually contradictory with what we do).
-trait IntoUsize {
-    fn into_usize(self) -> usize;
-}
+    /// Returns `true` if the strict mode is enabled (see [`VecTree::set_strict()`]).
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
 
-impl IntoUsize for usize {
-    fn into_usize(self) -> usize { self }
-}
+    /// Panics if the strict mode is enabled and `check` returns an error.
+    fn check_strict<F: FnOnce(&Self) -> Result<(), TreeError>>(&self, check: F) {
+        if self.strict {
+            if let Err(e) = check(self) {
+                panic!("strict mode: {e}");
+            }
+        }
+    }
+
+    /// Verifies the integrity of the whole tree:
+    /// * the root and all the children indices must exist,
+    /// * a node must have at most one parent,
+    /// * the root must have no parent,
+    /// * there must be no cycle.
+    ///
+    /// The nodes that aren't attached to the root are allowed, since they may be attached later.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::{TreeError, VecTree};
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// assert_eq!(tree.validate(), Ok(()));
+    /// tree.children_mut(a).push(root);
+    /// assert_eq!(tree.validate(), Err(TreeError::RootHasParent { root, parent: a }));
+    /// ```
+    pub fn validate(&self) -> Result<(), TreeError> {
+        let size = self.nodes.len();
+        if let Some(root) = self.root {
+            if root >= size {
+                return Err(TreeError::BadIndex(root));
+            }
+        }
+        let mut parents = vec![None; size];
+        for (index, node) in self.nodes.iter().enumerate() {
+            for &child in &node.children {
+                if child >= size {
+                    return Err(TreeError::BadIndex(child));
+                }
+                if let Some(parent) = parents[child] {
+                    return Err(TreeError::MultipleParents { child, parent });
+                }
+                parents[child] = Some(index);
+            }
+        }
+        if let Some(root) = self.root {
+            if let Some(parent) = parents[root] {
+                return Err(TreeError::RootHasParent { root, parent });
+            }
+        }
+        // Since each node has at most one parent, the nodes that can't be reached
+        // from a node without parent are in a cycle.
+        let mut reached = vec![false; size];
+        let mut stack = (0..size).filter(|&index| parents[index].is_none()).collect::<Vec<_>>();
+        while let Some(index) = stack.pop() {
+            reached[index] = true;
+            stack.extend(&self.nodes[index].children);
+        }
+        match reached.iter().position(|&r| !r) {
+            Some(child) => Err(TreeError::Cycle { parent: parents[child].unwrap(), child }),
+            None => Ok(())
+        }
+    }
+
+    /// Returns the number of items in the tree buffer.
+    ///
+    /// Note that this method only returns the number of items in the tree, as defined by its current root, if
+    /// all items are children of the root to some degree. If there are loose items that have no relationship
+    /// with the root, the actual number of items (nodes) in the tree can be obtained by counting the iterations.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if the tree buffer contains no items.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Calculates the tree depth, which is the maximum number of levels (not including the root).
+    ///
+    /// Notes:
+    /// * The depth returned by the iterators are zero-based, and thus `iterator.depth` is between `0` and `tree.depth()`.
+    /// * This method iterates over all the nodes, so it's not time-effective.
+    ///
+    /// Returns `None` if the tree has no root.
+    pub fn depth(&self) -> Option<u32> {
+        self.iter_depth_simple().map(|x| x.depth).max()
+    }
+
+    /// Returns the depth of the node of index `index` relative to the root, which is the same as the
+    /// `depth` given by the iterators, or `None` if the node isn't under the root.
+    ///
+    /// The first call builds a table of the node levels from the cached post-order sequence given by
+    /// [`VecTree::post_order()`], which takes O(n) if that sequence isn't cached yet; the next calls
+    /// take O(1), until the structure of the tree is modified.
+    ///
+    /// Panics if the index is out of the buffer bounds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// let a1 = tree.add(Some(a), "a1");
+    /// let other = tree.add(None, "other");
+    /// assert_eq!(tree.level_of(root), Some(0));
+    /// assert_eq!(tree.level_of(a1), Some(2));
+    /// assert_eq!(tree.level_of(other), None);
+    /// ```
+    pub fn level_of(&self, index: usize) -> Option<u32> {
+        assert!(index < self.len(), "node index {index} doesn't exist");
+        let levels = self.levels.get_or_init(|| {
+            let mut levels = vec![None; self.nodes.len()];
+            for &(index, depth) in self.post_order() {
+                levels[index] = Some(depth);
+            }
+            levels
+        });
+        levels[index]
+    }
+
+    /// Returns a reference to the item stored at the given index.
+    ///
+    /// Panics if the index is out of the buffer bounds.
+    pub fn get(&self, index: usize) -> &T {
+        // SAFETY: The access to the `UnsafeCell<T> data` field is secured by the compiler:
+        //         the method can't be called if a mutable borrow is alive (either given by .get_mut or
+        //         by a NodeProxyMut)
+        unsafe { &*self.nodes.get(index).unwrap().data.get() }
+    }
+
+    /// Returns a mutable reference to the item stored at the given index.
+    ///
+    /// Panics if the index is out of the buffer bounds.
+    pub fn get_mut(&mut self, index: usize) -> &mut T {
+        self.mark_dirty(index);
+        self.nodes.get_mut(index).unwrap().data.get_mut()
+    }
+
+    /// Swaps the items of the nodes `a` and `b`, without modifying the structure of the tree: the
+    /// children stay in place.
+    ///
+    /// Panics if one of the indices is out of the buffer bounds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// let a1 = tree.add(Some(a), "a1");
+    /// tree.swap(a, a1);
+    /// assert_eq!((*tree.get(a), *tree.get(a1)), ("a1", "a"));
+    /// assert_eq!(tree.children(a), &[a1]);
+    /// ```
+    pub fn swap(&mut self, a: usize, b: usize) {
+        assert!(a < self.nodes.len(), "node index {a} doesn't exist");
+        assert!(b < self.nodes.len(), "node index {b} doesn't exist");
+        if a != b {
+            self.mark_dirty(a);
+            self.mark_dirty(b);
+            let (low, high) = if a < b { (a, b) } else { (b, a) };
+            let (left, right) = self.nodes.split_at_mut(high);
+            std::mem::swap(left[low].data.get_mut(), right[0].data.get_mut());
+            self.index_swapped(a, b);
+        }
+    }
+
+    /// Returns a view of the items that can be indexed directly, unlike the tree itself, which
+    /// gives the [Node] at each index.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// let values = tree.values();
+    /// assert_eq!(values[a], "a");
+    /// assert_eq!(values.len(), 2);
+    /// tree.values_mut()[a] = "A";
+    /// assert_eq!(tree.values()[a], "A");
+    /// ```
+    pub fn values(&self) -> Values<'_, T> {
+        Values { tree: self }
+    }
+
+    /// Returns a view of the items that can be indexed directly to read or modify them (see [`VecTree::values()`]).
+    pub fn values_mut(&mut self) -> ValuesMut<'_, T> {
+        ValuesMut { tree: self }
+    }
+
+    /// Returns a reference to the item's children.
+    ///
+    /// Panics if the index is out of the buffer bounds.
+    pub fn children(&self, index: usize) -> &[usize] {
+        self.nodes.get(index).unwrap().children.as_slice()
+    }
+
+    /// Returns the buffer of nodes, in the order of their indices. Each [Node] gives
+    /// access to its value and to its children indices.
+    ///
+    /// It's the quickest way to scan all the nodes when the order of the traversal doesn't matter;
+    /// it includes the nodes that aren't attached to the root.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root(1);
+    /// tree.add_iter(Some(root), [2, 3]);
+    /// let leaves_sum = tree.nodes().iter().filter(|node| !node.has_children()).map(|node| node.value()).sum::<i32>();
+    /// assert_eq!(leaves_sum, 5);
+    /// ```
+    pub fn nodes(&self) -> &[Node<T>] {
+        &self.nodes
+    }
+
+    /// Returns a mutable reference to the item's children.
+    ///
+    /// Panics if the index is out of the buffer bounds.
+    ///
+    /// The modifications of the children aren't verified, even in strict mode (see [`VecTree::set_strict()`]).
+    pub fn children_mut(&mut self, index: usize) -> &mut Vec<usize> {
+        self.structure_changed();
+        &mut self.nodes.get_mut(index).unwrap().children
+    }
+
+    /// Returns an iterator to the index and the item of the node's children, by reference.
+    ///
+    /// Panics if the index is out of the buffer bounds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// tree.add_iter(Some(root), ["a", "b"]);
+    /// assert_eq!(tree.iter_children(root).collect::<Vec<_>>(), [(1, &"a"), (2, &"b")]);
+    /// ```
+    pub fn iter_children(&self, index: usize) -> impl DoubleEndedIterator<Item = (usize, &T)> {
+        self.nodes.get(index).unwrap().children.iter().map(|&i| (i, self.nodes.get(i).unwrap().value()))
+    }
+
+    /// Returns an iterator to the items of the node's children, by mutable reference.
+    ///
+    /// Panics if the index is out of the buffer bounds, or if the node has the same child twice in
+    /// its list of children.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root(0);
+    /// tree.add_iter(Some(root), [1, 2, 3]);
+    /// for item in tree.iter_children_mut(root) {
+    ///     *item *= 10;
+    /// }
+    /// assert_eq!(tree.iter_depth_simple().map(|n| *n).collect::<Vec<_>>(), [10, 20, 30, 0]);
+    /// ```
+    pub fn iter_children_mut(&mut self, index: usize) -> impl DoubleEndedIterator<Item = &mut T> {
+        let mut sorted = self.nodes.get(index).unwrap().children.clone();
+        sorted.sort_unstable();
+        assert!(sorted.windows(2).all(|w| w[0] != w[1]), "node {index} has duplicate children");
+        for &child in &sorted {
+            self.mark_dirty(child);
+        }
+        let nodes = &self.nodes;
+        let children = &nodes[index].children;
+        // SAFETY: - The items are in `UnsafeCell`, and the tree is mutably borrowed by the iterator.
+        //         - The children are distinct, so each item is only mutably borrowed once.
+        children.iter().map(|&i| unsafe { &mut *nodes.get(i).unwrap().data.get() })
+    }
+
+    /// Returns an iterator to the indices of the other children of the node's parent, in order. The
+    /// iterator is empty if the node has no parent.
+    ///
+    /// There's no link from a node to its parent, so the parent is found by scanning the buffer, which
+    /// is O(n).
+    ///
+    /// Panics if the index is out of the buffer bounds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let children = tree.add_iter(Some(root), ["a", "b", "c"]);
+    /// assert_eq!(tree.siblings_of(children[1]).collect::<Vec<_>>(), [children[0], children[2]]);
+    /// assert_eq!(tree.siblings_of(root).count(), 0);
+    /// ```
+    pub fn siblings_of(&self, index: usize) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        assert!(index < self.len(), "node index {index} doesn't exist");
+        let siblings = match self.find_parent(index) {
+            Some(parent) => self.nodes[parent].children.as_slice(),
+            None => &[],
+        };
+        siblings.iter().copied().filter(move |&i| i != index)
+    }
+
+    /// Sorts the children of the node `index` with the comparator function `compare`, which compares
+    /// their items. The sort is stable: the children with equal items keep their order.
+    ///
+    /// Panics if the index is out of the buffer bounds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// tree.add_iter(Some(root), ["c", "a", "b"]);
+    /// tree.sort_children_by(root, |a, b| a.cmp(b));
+    /// assert_eq!(tree.iter_children(root).map(|(_, item)| *item).collect::<Vec<_>>(), ["a", "b", "c"]);
+    /// ```
+    pub fn sort_children_by<F: FnMut(&T, &T) -> Ordering>(&mut self, index: usize, mut compare: F) {
+        assert!(index < self.len(), "node index {index} doesn't exist");
+        self.structure_changed();
+        let mut children = std::mem::take(&mut self.nodes[index].children);
+        children.sort_by(|&a, &b| compare(self.nodes[a].value(), self.nodes[b].value()));
+        self.nodes[index].children = children;
+    }
+
+    /// Sorts the children of every node with the comparator function `compare`, which compares their
+    /// items (see [`VecTree::sort_children_by()`]).
+    pub fn sort_all_children_by<F: FnMut(&T, &T) -> Ordering>(&mut self, mut compare: F) {
+        for index in 0..self.nodes.len() {
+            self.sort_children_by(index, &mut compare);
+        }
+    }
+
+    /// Removes the duplicate indices from the children of the node `index`, keeping the first occurrence
+    /// of each child. The duplicates can be created by [`VecTree::attach_children()`] or
+    /// [`VecTree::children_mut()`] when the strict mode is disabled (see [`VecTree::set_strict()`]), and
+    /// make the iterators visit the same child several times.
+    ///
+    /// Panics if the index is out of the buffer bounds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// let b = tree.add(Some(root), "b");
+    /// tree.attach_children(root, [a, b]);
+    /// assert_eq!(tree.children(root), &[a, b, a, b]);
+    /// tree.dedup_children(root);
+    /// assert_eq!(tree.children(root), &[a, b]);
+    /// ```
+    pub fn dedup_children(&mut self, index: usize) {
+        assert!(index < self.len(), "node index {index} doesn't exist");
+        self.structure_changed();
+        let mut seen = HashSet::new();
+        self.nodes[index].children.retain(|&child| seen.insert(child));
+    }
+
+    /// Reverses the order of the children of the node `index`.
+    ///
+    /// Panics if the index is out of the buffer bounds.
+    pub fn reverse_children(&mut self, index: usize) {
+        assert!(index < self.len(), "node index {index} doesn't exist");
+        self.structure_changed();
+        self.nodes[index].children.reverse();
+    }
+
+    /// Reverses the order of the children of the node `index` and of all the nodes below it, which
+    /// mirrors the subtree.
+    ///
+    /// Panics if the index is out of the buffer bounds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add_iter(Some(a), ["a1", "a2"]);
+    /// tree.add(Some(root), "b");
+    /// tree.reverse_subtree(root);
+    /// assert_eq!(tree.iter_depth_simple().map(|n| *n).collect::<Vec<_>>(), ["b", "a2", "a1", "a", "root"]);
+    /// ```
+    pub fn reverse_subtree(&mut self, index: usize) {
+        assert!(index < self.len(), "node index {index} doesn't exist");
+        self.structure_changed();
+        let mut reversed = vec![false; self.nodes.len()];
+        let mut stack = vec![index];
+        while let Some(index) = stack.pop() {
+            // the flag also prevents from looping if the children links are cyclic
+            if !reversed[index] {
+                reversed[index] = true;
+                self.nodes[index].children.reverse();
+                stack.extend(&self.nodes[index].children);
+            }
+        }
+    }
+
+    /// Reverses the order of the children of every node.
+    pub fn reverse_all_children(&mut self) {
+        self.structure_changed();
+        for node in self.nodes.iter_mut() {
+            node.children.reverse();
+        }
+    }
+}
+
+impl<T: Clone> VecTree<T> {
+    /// Creates a balanced tree from sorted items, where each node has at most `arity` children.
+    ///
+    /// Each node holds one item of a range of the slice, and the other items of the range are divided
+    /// evenly between its `arity` children, so that the subtrees of the children cover contiguous,
+    /// increasing ranges of the slice and the depth of the tree is minimal. The item of the node lies
+    /// between the ranges of its first `arity / 2` children and those of the other children; with an
+    /// arity of 2, the tree is a balanced binary search tree.
+    ///
+    /// The nodes are created in breadth-first order, so the root is at index 0, each level of the
+    /// tree is contiguous in the buffer, and so are the children of each node.
+    ///
+    /// Panics if `arity` is smaller than 2.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let tree = VecTree::from_sorted_slice(&[1, 2, 3, 4, 5, 6, 7], 2);
+    /// assert_eq!(tree.iter_depth_simple().map(|inode| *inode).collect::<Vec<_>>(), [1, 3, 2, 5, 7, 6, 4]);
+    /// assert_eq!(tree.depth(), Some(2));
+    /// ```
+    pub fn from_sorted_slice(items: &[T], arity: usize) -> Self {
+        assert!(arity >= 2, "arity must be at least 2");
+        let mut tree = VecTree::with_capacity(items.len());
+        if items.is_empty() {
+            return tree;
+        }
+        // ranges of items waiting to be added, with their parent
+        let mut queue = std::collections::VecDeque::from([(None, 0, items.len())]);
+        while let Some((parent, start, end)) = queue.pop_front() {
+            // the node's item is taken between the chunks of the first `arity / 2` children and the others
+            let size = end - start - 1;
+            let chunk_size = |i: usize| size / arity + usize::from(i < size % arity);
+            let middle = start + (0..arity / 2).map(chunk_size).sum::<usize>();
+            let index = tree.add(parent, items[middle].clone());
+            let mut chunk_start = start;
+            for i in 0..arity.min(size) {
+                let chunk_end = chunk_start + chunk_size(i);
+                queue.push_back((Some(index), chunk_start, chunk_end));
+                chunk_start = if chunk_end == middle { middle + 1 } else { chunk_end };
+            }
+        }
+        tree.set_root(0);
+        tree
+    }
+
+    /// Creates a tree with the nodes of the tree under the root for which `pred` is `true`, keeping
+    /// their relative order. The items are cloned from the current tree.
+    ///
+    /// `orphan_policy` chooses what happens to the descendants of the nodes for which `pred` is `false`:
+    /// they're either removed too, or the kept ones are hoisted to the nearest kept ancestor.
+    /// If the root is removed, the new tree has no root; with [`OrphanPolicy::Hoist`], the top kept
+    /// nodes are then left without parent.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::{OrphanPolicy, VecTree};
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "-a");
+    /// tree.add_iter(Some(a), ["a1", "-a2"]);
+    /// tree.add(Some(root), "b");
+    /// let to_string = |tree: &VecTree<&str>| tree.iter_depth_simple().map(|n| *n).collect::<Vec<_>>().join(",");
+    /// let keep = |item: &&str| !item.starts_with('-');
+    /// assert_eq!(to_string(&tree.filtered(keep, OrphanPolicy::Drop)), "b,root");
+    /// assert_eq!(to_string(&tree.filtered(keep, OrphanPolicy::Hoist)), "a1,b,root");
+    /// ```
+    pub fn filtered<F: FnMut(&T) -> bool>(&self, mut pred: F, orphan_policy: OrphanPolicy) -> Self {
+        let mut tree = VecTree::new();
+        let Some(root) = self.root else {
+            return tree;
+        };
+        // first pass, top-down, so that `pred` isn't called on the descendants of dropped nodes
+        let mut keep = vec![false; self.nodes.len()];
+        let mut stack = vec![root];
+        while let Some(index) = stack.pop() {
+            keep[index] = pred(self.nodes[index].value());
+            if keep[index] || orphan_policy == OrphanPolicy::Hoist {
+                stack.extend(&self.nodes[index].children);
+            }
+        }
+        // second pass, bottom-up: each node gives the new indices to attach to its parent
+        let mut tops: Vec<Vec<usize>> = Vec::new();
+        for inode in self.iter_depth_simple() {
+            let children = tops.split_off(tops.len() - inode.num_children()).concat();
+            if keep[inode.index] {
+                tops.push(vec![tree.push_node(Node::with_children((*inode).clone(), children))]);
+            } else {
+                // with OrphanPolicy::Drop, `children` is always empty
+                tops.push(children);
+            }
+        }
+        if keep[root] {
+            tree.set_root(tree.len() - 1);
+        }
+        tree
+    }
+
+    /// Creates a tree with a copy of the node `index` and all the nodes below it. The items are cloned
+    /// from the current tree, which isn't modified.
+    ///
+    /// The nodes of the new tree are numbered in breadth-first order, so the root is at index 0 and the
+    /// children of each node occupy contiguous, increasing indices (see [`VecTree::renumber()`]).
+    ///
+    /// Panics if the node doesn't exist or has been removed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// tree.add(Some(root), "b");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add_iter(Some(a), ["a1", "a2"]);
+    /// let fragment = tree.subtree(a);
+    /// assert_eq!(fragment.get_root(), Some(0));
+    /// assert_eq!(fragment.children(0), &[1, 2]);
+    /// assert_eq!(fragment.values()[2], "a2");
+    /// assert_eq!(tree.len(), 5);
+    /// ```
+    pub fn subtree(&self, index: usize) -> Self {
+        assert!(index < self.nodes.len(), "node index {index} doesn't exist");
+        assert!(!self.is_removed(index), "node {index} has been removed");
+        let order = self.iter_traverse_at(index, Traversal::BreadthFirst).map(|inode| inode.index).collect::<Vec<_>>();
+        let mut new_index = vec![0; self.nodes.len()];
+        for (new, &old) in order.iter().enumerate() {
+            new_index[old] = new;
+        }
+        let nodes = order.iter()
+            .map(|&old| {
+                let node = &self.nodes[old];
+                Node::with_children(node.value().clone(), node.children.iter().map(|&child| new_index[child]).collect())
+            })
+            .collect();
+        VecTree::from_nodes(nodes, Some(0))
+    }
+
+    /// Adds items from another `VecTree` and returns the index of the top item. This method
+    /// can be used to copy another tree or part of another tree into the current one.
+    ///
+    /// The items are cloned from the other tree. If `top` is not `None`, it contains the index of
+    /// the top element that is copied from the other tree. If `top` is `None`, the whole tree is
+    /// added.
+    ///
+    /// If `parent_index` is provided (not `None`), the top item is added to the parent's list of children.
+    /// If that parent doesn't exist, or in other words, if the value of `parent_index` is too big for the
+    /// buffer size, the method panics. If `parent_index` is `None`, the top item must be attached to
+    /// the current tree another way.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root".to_string());
+    /// let a = tree.add(Some(root), "a".to_string());
+    /// let b = tree.add(Some(root), "b".to_string());
+    /// let _ = tree.add(Some(root), "c".to_string());
+    /// tree.add_iter(Some(a), ["a1".to_string(), "a2".to_string()]);
+    /// // => tree:  root(a(a1, a2), b, c)
+    /// let other = tree.clone();
+    /// tree.add_from_tree(Some(b), &other, Some(a));
+    /// // => tree: root(a(a1, a2), b(a(a1, a2)), c)
+    /// //                           ^^^^^^^^^^^
+    /// ```
+    pub fn add_from_tree(&mut self, parent_index: Option<usize>, tree: &VecTree<T>, top: Option<usize>) -> usize {
+        self.add_from_tree_iter(parent_index, tree.iter_depth_at(top.unwrap_or_else(|| tree.get_root().unwrap())))
+    }
+
+    /// Replaces the node `index` and all the nodes below it by a copy of the tree `other`, or of its
+    /// subtree under the node `top` if it's not `None`, and returns the index of the top copied item. The
+    /// copy takes the place of the node in the children of its parent, or becomes the root if the node was
+    /// the root.
+    ///
+    /// The replaced nodes are removed like with [`VecTree::remove_subtree()`]: they're marked as removed
+    /// but stay in the buffer until [`VecTree::compact()`] is called. The items are cloned from `other`,
+    /// and the parent is found by scanning the whole buffer.
+    ///
+    /// Panics if the node doesn't exist or has been removed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("p");
+    /// let placeholder = tree.addc(Some(root), "{{list}}", "name");
+    /// tree.add(Some(root), "footer");
+    /// let mut list = VecTree::new();
+    /// let ul = list.add_root("ul");
+    /// list.add_iter(Some(ul), ["li 1", "li 2"]);
+    /// let top = tree.replace_subtree(placeholder, &list, None);
+    /// assert_eq!(tree.children(root), &[top, 3]);
+    /// assert_eq!(*tree.get(top), "ul");
+    /// assert!(tree.is_removed(placeholder));
+    /// ```
+    pub fn replace_subtree(&mut self, index: usize, other: &VecTree<T>, top: Option<usize>) -> usize {
+        assert!(index < self.nodes.len(), "node index {index} doesn't exist");
+        assert!(!self.is_removed(index), "node {index} has been removed");
+        let parent = self.find_parent(index);
+        let new_index = self.add_from_tree(None, other, top);
+        if let Some(parent) = parent {
+            for child in self.nodes[parent].children.iter_mut().filter(|child| **child == index) {
+                *child = new_index;
+            }
+        }
+        if self.root == Some(index) {
+            self.root = Some(new_index);
+        }
+        self.remove_subtree(index);
+        new_index
+    }
+
+    /// Adds items from a `VecTree` iterator and returns the index of the top item. This method
+    /// can be used to copy another tree or part of another tree into the current one.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root".to_string());
+    /// let a = tree.add(Some(root), "a".to_string());
+    /// let b = tree.add(Some(root), "b".to_string());
+    /// let _ = tree.add(Some(root), "c".to_string());
+    /// tree.add_iter(Some(a), ["a1".to_string(), "a2".to_string()]);
+    /// // => tree:  root(a(a1, a2), b, c)
+    /// let other = tree.clone();
+    /// tree.add_from_tree_iter(Some(b), other.iter_depth_at(a));
+    /// // => tree: root(a(a1, a2), b(a(a1, a2)), c)
+    /// //                           ^^^^^^^^^^^
+    /// ```
+    pub fn add_from_tree_iter<'a, U>(&mut self, parent_index: Option<usize>, items: U) -> usize
+    where
+        U: IntoIterator<Item=NodeProxy<'a, T>>,
+        T: 'a
+    {
+        self.add_from_tree_iter_callback(parent_index, items, move |_, _, _| {})
+    }
+
+    /// Adds items from another `VecTree` and returns the index of the top item. This method
+    /// can be used to copy another tree or part of another tree into the current one.
+    ///
+    /// For further details, see [VecTree::add_from_tree].
+    ///
+    /// The callback function, `f(to, from, item)` gives the following parameters:
+    /// * `to: usize` is the index where the new item will be stored in the destination tree
+    /// * `from: usize` is the index where the original item is in the source tree
+    /// * `item: &T` is a reference to the item in the source tree
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// # fn main() {
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root".to_string());
+    /// let a = tree.add(Some(root), "a".to_string());
+    /// tree.add(Some(a), "a1".to_string());
+    /// tree.add(Some(root), "b".to_string());
+    /// let mut result = Vec::<(usize, usize, String)>::new();
+    /// let mut other = VecTree::new();
+    /// other.add_from_tree_callback(
+    ///     None,
+    ///     &tree,
+    ///     Some(a),
+    ///     |to, from, item| result.push((to, from, item.clone())) );
+    /// assert_eq!(result, vec![(0, 2, "a1".to_string()), (1, 1, "a".to_string())]);
+    /// # }
+    /// ```
+    pub fn add_from_tree_callback<'a, F>(&mut self, parent_index: Option<usize>, tree: &'a VecTree<T>, top: Option<usize>, f: F) -> usize
+    where
+        F: FnMut(usize, usize, &T),
+        T: 'a
+    {
+        self.add_from_tree_iter_callback(parent_index, tree.iter_depth_at(top.unwrap_or_else(|| tree.get_root().unwrap())), f)
+    }
+
+    /// Adds items from a `VecTree` iterator and returns the index of the top item. This method
+    /// can be used to copy another tree or part of another tree into the current one.
+    ///
+    /// The callback function, `f(to, from, item)` gives the following parameters:
+    /// * `to: usize` is the index where the new item will be stored in the destination tree
+    /// * `from: usize` is the index where the original item is in the source tree
+    /// * `item: &T` is a reference to the item in the source tree
+    ///
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// # fn main() {
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root".to_string());
+    /// let a = tree.add(Some(root), "a".to_string());
+    /// tree.add(Some(a), "a1".to_string());
+    /// tree.add(Some(root), "b".to_string());
+    /// let mut result = Vec::<(usize, usize, String)>::new();
+    /// let mut other = VecTree::new();
+    /// other.add_from_tree_iter_callback(
+    ///     None,
+    ///     tree.iter_depth_at(a),
+    ///     |to, from, item| result.push((to, from, item.clone())) );
+    /// assert_eq!(result, vec![(0, 2, "a1".to_string()), (1, 1, "a".to_string())]);
+    /// # }
+    /// ```
+    pub fn add_from_tree_iter_callback<'a, U, F>(&mut self, parent_index: Option<usize>, items: U, mut f: F) -> usize
+    where
+        U: IntoIterator<Item=NodeProxy<'a, T>>,
+        T: 'a,
+        F: FnMut(usize, usize, &T),
+    {
+        let mut stack = Vec::<usize>::new();
+        for item in items {
+            let node = item.deref().clone();
+            let num_children = item.num_children();
+            f(self.nodes.len(), item.index, item.deref());
+            let index = if num_children > 0 {
+                let children = stack.split_off(stack.len() - num_children);
+                self.addci_iter(None, node, children)
+            } else {
+                self.add(None, node)
+            };
+            stack.push(index);
+        }
+        assert_eq!(stack.len(), 1, "something is wrong with the structure of the provided items");
+        let index = stack.pop().unwrap();
+        if let Some(parent) = parent_index {
+            self.nodes[parent].children.push(index);
+        }
+        index
+    }
+}
+
+impl<T> SubTree<T> {
+    /// Creates a branch with the given value and children.
+    pub fn new(value: T, children: Vec<SubTree<T>>) -> Self {
+        SubTree { value, children }
+    }
+
+    /// Creates a branch with the given value and no children.
+    pub fn leaf(value: T) -> Self {
+        SubTree { value, children: Vec::new() }
+    }
+}
+
+impl<T> Node<T> {
+    /// Creates a node without children, which can be added to a tree with [`VecTree::push_node()`].
+    pub fn new(value: T) -> Self {
+        Node { data: UnsafeCell::new(value), children: Vec::new() }
+    }
+
+    /// Creates a node with the given children indices, which can be added to a tree with [`VecTree::push_node()`].
+    pub fn with_children(value: T, children: Vec<usize>) -> Self {
+        Node { data: UnsafeCell::new(value), children }
+    }
+
+    /// Returns a reference to the node's value.
+    pub fn value(&self) -> &T {
+        // SAFETY: A reference to a node can only be obtained from an immutable reference to the tree
+        //         (see [`VecTree::nodes()`]), so no mutable reference to its data can exist while
+        //         it's alive.
+        unsafe { &*self.data.get() }
+    }
+
+    /// Returns a mutable reference to the node's value.
+    pub fn value_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Replaces the node's value and returns the previous one.
+    pub fn set_value(&mut self, value: T) -> T {
+        std::mem::replace(self.data.get_mut(), value)
+    }
+
+    /// Returns `true` if the node has children.
+    pub fn has_children(&self) -> bool {
+        !self.children.is_empty()
+    }
+
+    /// Returns a reference slice to the children's indices.
+    pub fn children(&self) -> &[usize] {
+        &self.children
+    }
+
+    /// Returns a mutable reference to the children's indices.
+    ///
+    /// Like [`VecTree::children_mut()`], the modifications aren't verified.
+    pub fn children_mut(&mut self) -> &mut Vec<usize> {
+        &mut self.children
+    }
+}
+
+impl<T> Index<usize> for VecTree<T> {
+    type Output = Node<T>;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.nodes.get(index).unwrap()
+    }
+}
+
+impl<T> IndexMut<usize> for VecTree<T> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        // the node's children may be modified
+        self.structure_changed();
+        self.mark_dirty(index);
+        self.nodes.get_mut(index).unwrap()
+    }
+}
+
+/// An indexable view of the items of a [VecTree], created by [`VecTree::values()`].
+pub struct Values<'a, T> {
+    tree: &'a VecTree<T>
+}
+
+impl<T> Values<'_, T> {
+    /// Returns the number of items.
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Returns `true` if there is no item.
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+}
+
+impl<T> Index<usize> for Values<'_, T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.tree.get(index)
+    }
+}
+
+/// A mutable, indexable view of the items of a [VecTree], created by [`VecTree::values_mut()`].
+pub struct ValuesMut<'a, T> {
+    tree: &'a mut VecTree<T>
+}
+
+impl<T> ValuesMut<'_, T> {
+    /// Returns the number of items.
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Returns `true` if there is no item.
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+}
+
+impl<T> Index<usize> for ValuesMut<'_, T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.tree.get(index)
+    }
+}
+
+impl<T> IndexMut<usize> for ValuesMut<'_, T> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        self.tree.get_mut(index)
+    }
+}
+
+impl<T: Clone> Clone for VecTree<T> {
+    fn clone(&self) -> Self {
+        let mut tree = VecTree::from_nodes(self.nodes.clone(), self.root);
+        tree.strict = self.strict;
+        tree.dirty = self.dirty.clone();
+        tree.expanded = self.expanded.clone();
+        tree.removed = self.removed.clone();
+        tree.key_index = self.key_index.as_ref().map(|key_index| key_index.clone_box());
+        tree
+    }
+}
+
+impl<T> Default for VecTree<T> {
+    fn default() -> Self {
+        VecTree::new()
+    }
+}
+
+impl<T: Clone> Clone for Node<T> {
+    fn clone(&self) -> Self {
+        Node {
+            // SAFETY: We're cloning, so there is no reference to the newly created field.
+            data: UnsafeCell::new(unsafe { (*self.data.get()).clone() }),
+            children: self.children.clone()
+        }
+    }
+}
+
+// This trait is used as a bound for both usize and &usize. It would be otherwise impossible
+// to implement From for iterators on both types (except by using Borrow, which produces
+// the same optimized code but is conceptually contradictory with what we do).
+trait IntoUsize {
+    fn into_usize(self) -> usize;
+}
+
+impl IntoUsize for usize {
+    fn into_usize(self) -> usize { self }
+}
+
+impl IntoUsize for &usize {
+    fn into_usize(self) -> usize { *self }
+}
+
+impl<T, A, C> From<(Option<usize>, A)> for VecTree<T>
+where
+    A: IntoIterator<Item=(T, C)>,
+    C: IntoIterator<Item: IntoUsize>,
+{
+    /// Creates a [VecTree] from a tuple `(r, array)`, where
+    /// * `r` is an optional root index
+    /// * `array` is any collection that can be converted into an iterator of `(T, &[usize])`,
+    ///   `T` being the node value at that index and the slice being the enumeration of its
+    ///   children indices.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use vectree::VecTree;
+    /// let tree = VecTree::from((
+    ///     Some(0),
+    ///     vec![
+    ///         ("root", vec![1, 2]),
+    ///         ("a",    vec![3, 4]),
+    ///         ("b",    vec![]),
+    ///         ("a.1",  vec![]),
+    ///         ("a.2",  vec![]),
+    ///     ]
+    /// ));
+    /// let str = tree.iter_depth_simple()
+    ///     .map(|n| format!("{}:{}", n.depth, *n))
+    ///     .collect::<Vec<_>>()
+    ///     .join(", ");
+    /// assert_eq!(str, "2:a.1, 2:a.2, 1:a, 1:b, 0:root");
+    /// ```
+    fn from((root, nodes): (Option<usize>, A)) -> Self {
+        VecTree::from_nodes(
+            nodes.into_iter()
+                .map(|(value, children)| Node { data: UnsafeCell::new(value), children: children.into_iter().map(|c| c.into_usize()).collect() })
+                .collect(),
+            root
+        )
+    }
+}
+
+impl Display for TreeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TreeError::BadIndex(index) => write!(f, "node index {index} doesn't exist"),
+            TreeError::Cycle { parent, child } => write!(f, "attaching node {child} to node {parent} would create a cycle"),
+            TreeError::MultipleParents { child, parent } => write!(f, "node {child} already has a parent, node {parent}"),
+            TreeError::RootHasParent { root, parent } => write!(f, "root node {root} has a parent, node {parent}"),
+            TreeError::BadPermutation => write!(f, "invalid permutation of the node indices"),
+            TreeError::ForeignNode { node_tree, tree } => write!(f, "node ID of tree {node_tree} used with tree {tree}"),
+        }
+    }
+}
+
+impl std::error::Error for TreeError {}
+
+impl<T: Display> Display for VisitNode<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VisitNode::Down(v) => write!(f, "D({v})"),
+            VisitNode::Up(v) => write!(f, "U({v})"),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+// Iterators
+//
+// Since the main depth-first search loop is the same for all iterators, we use the `TData` generic parameter
+// that implements the `TreeDataIter` trait. There are four types of iterator:
+// * IterDataSimple:   each iteration returns a NodeProxySimple
+// * IterDataSimpleMut:                         NodeProxySimpleMut
+// * IterData:                                  NodeProxy
+// * IterDataMut:                               NodeProxyMut
+//
+// NodeProxySimple / NodeProxySimpleMut allow to access the data.
+// NodeProxy / NodeProxy allow to access the data and the node's children, and it also allows to
+// iterate over the children or even the subtree with another embedded depth-first search, with
+// that node as root.
+
+/// A [VecTree] post-order, depth-first search iterator.
+pub struct VecTreePoDfsIter<TData> {
+    stack: Vec<VisitNode<usize>>,
+    depth: u32,
+    next: Option<VisitNode<usize>>,
+    last_depth: u32,
+    depth_delta: i32,
+    top: Option<usize>,
+    top_depth: u32,                     // depth of the last node, 1 if the top of the iteration is excluded
+    front_count: usize,                 // number of nodes returned by `next()`
+    back: Option<BackSearch>,
+    data: TData
+}
+
+/// The search from the end of a [VecTreePoDfsIter] traversal, which is a pre-order, depth-first search
+/// visiting the children from right to left.
+#[derive(Clone, Debug)]
+struct BackSearch {
+    stack: Vec<(usize, u32)>,
+    count: usize,                       // number of nodes returned by `next_back()`
+    total: usize                        // number of nodes in the whole traversal
+}
+
+/// A saved position of a [VecTreePoDfsIter] traversal (see [`VecTreePoDfsIter::checkpoint()`]).
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    stack: Vec<VisitNode<usize>>,
+    depth: u32,
+    next: Option<VisitNode<usize>>,
+    last_depth: u32,
+    front_count: usize
+}
+
+/// Implements methods used by the depth-first search algorithm and which depends on the
+/// type of iterator: simple or full-fledged (allowing to search each node's children),
+/// immutable or mutable.
+pub trait TreeDataIter {
+    type TProxy;
+
+    /// Gets a reference slice to the node's children indices.
+    fn get_children(&self, index: usize) -> &[usize];
+
+    /// Creates the proxy returned by each iteration. The proxy is used to access the
+    /// tree node and, when a full-fledged iterator is used, the nodes below it.
+    fn create_proxy(&self, index: usize, depth: u32) -> Self::TProxy;
+}
+
+impl<TData> VecTreePoDfsIter<TData> {
+    fn with_data(top: Option<usize>, data: TData) -> Self {
+        VecTreePoDfsIter {
+            stack: Vec::new(),
+            depth: 0,
+            next: top.map(VisitNode::Down),
+            last_depth: 0,
+            depth_delta: 0,
+            top,
+            top_depth: 0,
+            front_count: 0,
+            back: None,
+            data
+        }
+    }
+
+    /// Returns the number of nodes left to return when the end of the traversal has been used.
+    fn remaining(&self) -> Option<usize> {
+        self.back.as_ref().map(|back| back.total - back.count - self.front_count)
+    }
+
+    /// Returns the change in depth between the last node returned by the iterator and the node
+    /// returned before it. For the first node, the change is relative to the top of the iteration,
+    /// so it equals the node's depth.
+    ///
+    /// A positive value is the number of levels the search went down to reach the node (the number
+    /// of scopes to open before emitting the node), and a negative value is the number of levels it
+    /// went up (the number of scopes to close).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add_iter(Some(a), ["a1", "a2"]);
+    /// tree.add(Some(root), "b");
+    /// let mut iter = tree.iter_depth_simple();
+    /// let mut deltas = vec![];
+    /// while let Some(inode) = iter.next() {
+    ///     deltas.push((inode.to_string(), iter.depth_delta()));
+    /// }
+    /// assert_eq!(deltas, [("a1".to_string(), 2), ("a2".to_string(), 0), ("a".to_string(), -1),
+    ///                     ("b".to_string(), 0), ("root".to_string(), -1)]);
+    /// ```
+    pub fn depth_delta(&self) -> i32 {
+        self.depth_delta
+    }
+
+    /// Returns the index of the parent of the last node returned by the iterator, or `None` if that
+    /// node is the top of the iteration.
+    ///
+    /// The parent is known by the search, which is waiting on the stack to come back up to it, so
+    /// it doesn't require any lookup. Only the front of the traversal is concerned (see
+    /// [`VecTreePoDfsIter::depth_delta()`]).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add(Some(a), "a1");
+    /// let mut iter = tree.iter_depth_simple();
+    /// let mut parents = vec![];
+    /// while let Some(inode) = iter.next() {
+    ///     parents.push((inode.index, iter.parent()));
+    /// }
+    /// assert_eq!(parents, [(2, Some(1)), (1, Some(0)), (0, None)]);
+    /// ```
+    pub fn parent(&self) -> Option<usize> {
+        // when the top is excluded, its children have no parent on the stack
+        self.ancestors().next().or(if self.top_depth == 1 { self.top } else { None })
+    }
+
+    /// Saves the position of the traversal (see [`VecTreePoDfsIter::checkpoint()`]).
+    fn save(&self) -> Checkpoint {
+        Checkpoint {
+            stack: self.stack.clone(),
+            depth: self.depth,
+            next: self.next,
+            last_depth: self.last_depth,
+            front_count: self.front_count
+        }
+    }
+
+    /// Restores the position of the traversal (see [`VecTreePoDfsIter::resume()`]).
+    fn restore(mut self, checkpoint: Checkpoint) -> Self {
+        self.stack = checkpoint.stack;
+        self.depth = checkpoint.depth;
+        self.next = checkpoint.next;
+        self.last_depth = checkpoint.last_depth;
+        self.depth_delta = 0;
+        self.front_count = checkpoint.front_count;
+        self.back = None;
+        self
+    }
+
+    /// Iterates over the indices of the ancestors of the last node returned by the iterator, from its
+    /// parent up to the top of the iteration.
+    ///
+    /// The ancestors are the nodes waiting on the stack for the search to come back up.
+    fn ancestors(&self) -> impl Iterator<Item = usize> + '_ {
+        self.next.iter().chain(self.stack.iter().rev())
+            .filter_map(|node_dir| if let VisitNode::Up(index) = node_dir { Some(*index) } else { None })
+    }
+}
+
+impl<TData: TreeDataIter> VecTreePoDfsIter<TData> {
+    /// Returns the index and the depth of the next node, without moving to it. Unlike the
+    /// [`Peekable`](std::iter::Peekable) adapter, it doesn't create the proxy of the node.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add(Some(a), "a1");
+    /// let mut iter = tree.iter_depth_simple();
+    /// assert_eq!(iter.peek(), Some((2, 2)));
+    /// let a1 = iter.next().unwrap();
+    /// // the next node closes a scope:
+    /// assert!(iter.peek().unwrap().1 < a1.depth);
+    /// ```
+    pub fn peek(&self) -> Option<(usize, u32)> {
+        if self.remaining() == Some(0) {
+            return None;
+        }
+        match self.next? {
+            VisitNode::Up(index) => Some((index, self.depth - 1)),
+            VisitNode::Down(mut index) => {
+                let mut depth = self.depth;
+                while let Some(&child) = self.data.get_children(index).first() {
+                    index = child;
+                    depth += 1;
+                }
+                Some((index, depth))
+            }
+        }
+    }
+
+    /// Excludes the top of the iteration, which must not have started yet, so that only its descendants
+    /// are visited. Their depth remains relative to the top.
+    fn without_top(mut self) -> Self {
+        if let Some(VisitNode::Down(top)) = self.next {
+            self.stack.extend(self.data.get_children(top).iter().rev().map(|&child| VisitNode::Down(child)));
+            self.next = self.stack.pop();
+            self.depth = 1;
+            self.top_depth = 1;
+        }
+        self
+    }
+
+    /// Moves to the next node and returns its index and depth, without creating a proxy.
+    fn advance(&mut self) -> Option<(usize, u32)> {
+        if self.remaining() == Some(0) {
+            // the search has met the search from the end
+            return None;
+        }
+        // post-order depth-first search algorithm, common to all iterators
+        while let Some(node_dir) = self.next {
+            let index_option = match node_dir {
+                VisitNode::Down(index) => {
+                    let children = self.data.get_children(index);
+                    if children.is_empty() {
+                        Some(index)
+                    } else {
+                        self.depth += 1;
+                        self.stack.push(VisitNode::Up(index));
+                        for index in children.iter().rev() {
+                            self.stack.push(VisitNode::Down(*index));
+                        }
+                        None
+                    }
+                }
+                VisitNode::Up(index) => {
+                    self.depth -= 1;
+                    Some(index)
+                }
+            };
+            self.next = self.stack.pop();
+            if let Some(index) = index_option {
+                self.depth_delta = self.depth as i32 - self.last_depth as i32;
+                self.last_depth = self.depth;
+                self.front_count += 1;
+                return Some((index, self.depth));
+            }
+        }
+        None
+    }
+
+    /// Moves to the previous node from the end of the traversal and returns its index and depth,
+    /// without creating a proxy.
+    ///
+    /// The first call counts the nodes of the traversal, so that both ends stop when they meet.
+    fn advance_back(&mut self) -> Option<(usize, u32)> {
+        if self.back.is_none() {
+            let top = self.top?;
+            let mut stack = vec![(top, 0)];
+            if self.top_depth == 1 {
+                stack.clear();
+                stack.extend(self.data.get_children(top).iter().map(|&child| (child, 1)));
+            }
+            let mut total = stack.len();
+            let mut pending = stack.iter().map(|&(index, _)| index).collect::<Vec<_>>();
+            while let Some(index) = pending.pop() {
+                let children = self.data.get_children(index);
+                total += children.len();
+                pending.extend(children);
+            }
+            self.back = Some(BackSearch { stack, count: 0, total });
+        }
+        if self.remaining() == Some(0) {
+            return None;
+        }
+        let back = self.back.as_mut().unwrap();
+        // reverse post-order: pre-order, depth-first search visiting the children from right to left
+        let (index, depth) = back.stack.pop()?;
+        back.stack.extend(self.data.get_children(index).iter().map(|&child| (child, depth + 1)));
+        back.count += 1;
+        Some((index, depth))
+    }
+}
+
+impl<TData: TreeDataIter> Iterator for VecTreePoDfsIter<TData> {
+    type Item = TData::TProxy;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.advance().map(|(index, depth)| self.data.create_proxy(index, depth))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if let Some(remaining) = self.remaining() {
+            return (remaining, Some(remaining));
+        }
+        // each node waiting on the stack will be returned, but the number of nodes below them is unknown
+        (self.stack.len() + self.next.is_some() as usize, None)
+    }
+
+    fn count(mut self) -> usize {
+        if let Some(remaining) = self.remaining() {
+            return remaining;
+        }
+        let mut count = 0;
+        while self.advance().is_some() {
+            count += 1;
+        }
+        count
+    }
+
+    fn last(mut self) -> Option<Self::Item> {
+        if self.back.is_some() {
+            // the end of the traversal has been used, so the last node is where both searches meet
+            let mut last = None;
+            while let Some(index_depth) = self.advance() {
+                last = Some(index_depth);
+            }
+            return last.map(|(index, depth)| self.data.create_proxy(index, depth));
+        }
+        // the top of the iteration is always the last node in post-order; when it's been visited
+        // down, it's waiting at the bottom of the stack
+        let top = match self.stack.first().or(self.next.as_ref())? {
+            VisitNode::Down(index) | VisitNode::Up(index) => *index
+        };
+        Some(self.data.create_proxy(top, self.top_depth))
+    }
+}
+
+impl<TData: TreeDataIter> FusedIterator for VecTreePoDfsIter<TData> {}
+
+/// The immutable iterators are double-ended: [`Iterator::rev()`] gives the nodes in reverse post-order,
+/// which is a pre-order, depth-first search visiting the children from right to left. Both ends can be used
+/// alternately, until they meet.
+///
+/// The first call to [`DoubleEndedIterator::next_back()`] counts the nodes of the traversal. [`VecTreePoDfsIter::depth_delta()`]
+/// and [`VecTreePoDfsIter::peek()`] only concern the front of the traversal.
+///
+/// # Example
+///
+/// ```
+/// use vectree::VecTree;
+/// let mut tree = VecTree::new();
+/// let root = tree.add_root("root");
+/// let a = tree.add(Some(root), "a");
+/// tree.add_iter(Some(a), ["a1", "a2"]);
+/// tree.add(Some(root), "b");
+/// let result = tree.iter_depth_simple().rev().map(|inode| *inode).collect::<Vec<_>>();
+/// assert_eq!(result, ["root", "b", "a", "a2", "a1"]);
+/// let mut iter = tree.iter_depth();
+/// assert_eq!(iter.next().map(|inode| *inode), Some("a1"));
+/// assert_eq!(iter.next_back().map(|inode| *inode), Some("root"));
+/// assert_eq!(iter.map(|inode| *inode).collect::<Vec<_>>(), ["a2", "a", "b"]);
+/// ```
+impl<'a, T> DoubleEndedIterator for VecTreePoDfsIter<IterDataSimple<'a, T>> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.advance_back().map(|(index, depth)| self.data.create_proxy(index, depth))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for VecTreePoDfsIter<IterData<'a, T>> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.advance_back().map(|(index, depth)| self.data.create_proxy(index, depth))
+    }
+}
+
+/// Only the immutable iterators can save and restore their position: a mutable iterator could return
+/// the same node twice, while the proxy returned the first time is still alive.
+impl<'a, T> VecTreePoDfsIter<IterDataSimple<'a, T>> {
+    /// Saves the position of the traversal, which can be restored later with [`VecTreePoDfsIter::resume()`],
+    /// even after the iterator and the borrow of the tree are dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// tree.add_iter(Some(root), ["a", "b", "c"]);
+    /// let mut iter = tree.iter_depth_simple();
+    /// iter.next();
+    /// let checkpoint = iter.checkpoint();
+    /// drop(iter);
+    /// *tree.get_mut(root) = "ROOT";
+    /// let rest = tree.iter_depth_simple().resume(checkpoint).map(|inode| *inode).collect::<Vec<_>>();
+    /// assert_eq!(rest, ["b", "c", "ROOT"]);
+    /// ```
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.save()
+    }
+
+    /// Restores the position of the traversal saved by [`VecTreePoDfsIter::checkpoint()`].
+    ///
+    /// The structure of the tree must not have been modified since the checkpoint was saved. The
+    /// iterator must start at the same node as the one that saved it.
+    /// The nodes taken from the end of the traversal with [`DoubleEndedIterator::next_back()`]
+    /// are not part of the checkpoint.
+    pub fn resume(self, checkpoint: Checkpoint) -> Self {
+        self.restore(checkpoint)
+    }
+}
+
+impl<'a, T> VecTreePoDfsIter<IterData<'a, T>> {
+    /// Saves the position of the traversal, which can be restored later with [`VecTreePoDfsIter::resume()`],
+    /// even after the iterator and the borrow of the tree are dropped.
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.save()
+    }
+
+    /// Restores the position of the traversal saved by [`VecTreePoDfsIter::checkpoint()`] (see the
+    /// simple iterator's `resume()`).
+    pub fn resume(self, checkpoint: Checkpoint) -> Self {
+        self.restore(checkpoint)
+    }
+}
+
+/// The immutable iterators can be cloned to fork the traversal at the current position.
+///
+/// # Example
+///
+/// ```
+/// use vectree::VecTree;
+/// let mut tree = VecTree::new();
+/// let root = tree.add_root("root");
+/// tree.add_iter(Some(root), ["a", "b"]);
+/// let mut iter = tree.iter_depth_simple();
+/// iter.next();
+/// let lookahead = iter.clone().map(|inode| *inode).collect::<Vec<_>>();
+/// assert_eq!(lookahead, ["b", "root"]);
+/// assert_eq!(*iter.next().unwrap(), "b");
+/// ```
+impl<TData: Clone> Clone for VecTreePoDfsIter<TData> {
+    fn clone(&self) -> Self {
+        VecTreePoDfsIter {
+            stack: self.stack.clone(),
+            depth: self.depth,
+            next: self.next,
+            last_depth: self.last_depth,
+            depth_delta: self.depth_delta,
+            top: self.top,
+            top_depth: self.top_depth,
+            front_count: self.front_count,
+            back: self.back.clone(),
+            data: self.data.clone()
+        }
+    }
+}
+
+impl<TData> Debug for VecTreePoDfsIter<TData> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VecTreePoDfsIter")
+            .field("stack", &self.stack)
+            .field("depth", &self.depth)
+            .field("next", &self.next)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a: 'i,'i, T> VecTree<T> {
+    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
+    /// its root node.
+    ///
+    /// The iterator returns a proxy for each node, which gives an immutable reference only to that node.
+    pub fn iter_depth_simple(&'a self) -> VecTreePoDfsIter<IterDataSimple<'i, T>> {
+        VecTreePoDfsIter::<IterDataSimple<'i, T>>::new(self, self.root)
+    }
+
+    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
+    /// the node of index `top`.
+    ///
+    /// The iterator returns a proxy for each node, which gives an immutable reference only to that node.
+    pub fn iter_depth_simple_at(&'a self, top: usize) -> VecTreePoDfsIter<IterDataSimple<'i, T>> {
+        VecTreePoDfsIter::<IterDataSimple<'i, T>>::new(self, Some(top))
+    }
+
+    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
+    /// its root node.
+    ///
+    /// The iterator returns a proxy for each node, which gives an immutable reference to that node
+    /// and its children with the following methods:
+    /// * [NodeProxy::num_children()], to get the number of children
+    /// * [NodeProxy::iter_children()], to iterate over the children with a proxy to access their children
+    /// * [NodeProxy::iter_children_simple()], to iterate over the children
+    /// * [NodeProxy::iter_depth_simple()], to iterate the subtree under the node
+    pub fn iter_depth(&'a self) -> VecTreePoDfsIter<IterData<'i, T>> {
+        VecTreePoDfsIter::<IterData<'i, T>>::new(self, self.root)
+    }
+
+    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
+    /// the node of index `top`.
+    ///
+    /// The iterator returns a proxy for each node, which gives an immutable reference to that node
+    /// and its children with the following methods:
+    /// * [NodeProxy::num_children()], to get the number of children
+    /// * [NodeProxy::iter_children()], to iterate over the children with a proxy to access their children
+    /// * [NodeProxy::iter_children_simple()], to iterate over the children
+    /// * [NodeProxy::iter_depth_simple()], to iterate the subtree under the node
+    pub fn iter_depth_at(&'a self, top: usize) -> VecTreePoDfsIter<IterData<'i, T>> {
+        VecTreePoDfsIter::<IterData<'i, T>>::new(self, Some(top))
+    }
+
+    /// Post-order, depth-first search iteration over the descendants of the node of index `top`,
+    /// excluding `top` itself.
+    ///
+    /// The iterator returns the same proxies as [`VecTree::iter_depth_at()`], and the depth of each node
+    /// is still relative to `top`, so the children of `top` are at depth 1.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add_iter(Some(a), ["a1", "a2"]);
+    /// tree.add(Some(root), "b");
+    /// let below_a = tree.iter_descendants(a).map(|inode| *inode).collect::<Vec<_>>();
+    /// assert_eq!(below_a, ["a1", "a2"]);
+    /// assert_eq!(tree.iter_descendants(root).count(), 4);
+    /// ```
+    pub fn iter_descendants(&'a self, top: usize) -> VecTreePoDfsIter<IterData<'i, T>> {
+        VecTreePoDfsIter::<IterData<'i, T>>::new(self, Some(top)).without_top()
+    }
+
+    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
+    /// its root node. Each node is given with the index of its parent, or `None` for the root.
+    ///
+    /// The iterator returns the same proxies as [`VecTree::iter_depth()`] (see also
+    /// [`VecTreePoDfsIter::parent()`]).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root(1);
+    /// let a = tree.add(Some(root), 2);
+    /// tree.add_iter(Some(a), [3, 4]);
+    /// // sums the values of the children in their parent
+    /// let mut sums = vec![0; tree.len()];
+    /// for (parent, inode) in tree.iter_depth_with_parent() {
+    ///     let value = *inode + sums[inode.index];
+    ///     if let Some(parent) = parent {
+    ///         sums[parent] += value;
+    ///     }
+    /// }
+    /// assert_eq!(sums, [9, 7, 0, 0]);
+    /// ```
+    pub fn iter_depth_with_parent(&'a self) -> impl Iterator<Item = (Option<usize>, NodeProxy<'i, T>)> {
+        Self::with_parent(self.iter_depth())
+    }
+
+    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
+    /// the node of index `top`. Each node is given with the index of its parent, or `None` for `top`
+    /// (see [`VecTree::iter_depth_with_parent()`]).
+    pub fn iter_depth_with_parent_at(&'a self, top: usize) -> impl Iterator<Item = (Option<usize>, NodeProxy<'i, T>)> {
+        Self::with_parent(self.iter_depth_at(top))
+    }
+
+    /// Adds the index of the parent to each node given by `iter`.
+    fn with_parent(mut iter: VecTreePoDfsIter<IterData<'i, T>>) -> impl Iterator<Item = (Option<usize>, NodeProxy<'i, T>)> {
+        std::iter::from_fn(move || {
+            let inode = iter.next()?;
+            Some((iter.parent(), inode))
+        })
+    }
+
+    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
+    /// its root node.
+    ///
+    /// The iterator returns a proxy for each node, which gives a mutable reference only to that node.
+    pub fn iter_depth_simple_mut(&'a mut self) -> VecTreePoDfsIter<IterDataSimpleMut<'i, T>> {
+        VecTreePoDfsIter::<IterDataSimpleMut<'i, T>>::new(self, self.root)
+    }
+
+    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
+    /// the node of index `top`.
+    ///
+    /// The iterator returns a proxy for each node, which gives a mutable reference only to that node.
+    pub fn iter_depth_simple_at_mut(&'a mut self, top: usize) -> VecTreePoDfsIter<IterDataSimpleMut<'i, T>> {
+        VecTreePoDfsIter::<IterDataSimpleMut<'i, T>>::new(self, Some(top))
+    }
+
+    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
+    /// its root node.
+    ///
+    /// The iterator returns a proxy for each node, which gives a mutable reference to that node
+    /// and an immutable reference its children with the following methods:
+    /// * [NodeProxy::num_children()], to get the number of children
+    /// * [NodeProxy::iter_children()], to iterate over the children with a proxy to access their children
+    /// * [NodeProxy::iter_children_simple()], to iterate over the children
+    /// * [NodeProxy::iter_depth_simple()], to iterate the subtree under the node
+    pub fn iter_depth_mut(&'a mut self) -> VecTreePoDfsIter<IterDataMut<'i, T>> {
+        VecTreePoDfsIter::<IterDataMut<'i, T>>::new(self, self.root)
+    }
+
+    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
+    /// the node of index `top`.
+    ///
+    /// The iterator returns a proxy for each node, which gives a mutable reference to that node
+    /// and an immutable reference its children with the following methods:
+    /// * [NodeProxy::num_children()], to get the number of children
+    /// * [NodeProxy::iter_children()], to iterate over the children with a proxy to access their children
+    /// * [NodeProxy::iter_children_simple()], to iterate over the children
+    /// * [NodeProxy::iter_depth_simple()], to iterate the subtree under the node
+    pub fn iter_depth_at_mut(&'a mut self, top: usize) -> VecTreePoDfsIter<IterDataMut<'i, T>> {
+        VecTreePoDfsIter::<IterDataMut<'i, T>>::new(self, Some(top))
+    }
+
+    /// Clears the tree content.
+    pub fn clear(&mut self) {
+        // should never happen, since the compiler wouldn't allow another mutable borrow (required by this method):
+        assert_eq!(self.borrows.get(), 0, "must drop all iterator's node references before clearing a VecTree{}", self.borrows.report());
+        self.structure_changed();
+        self.nodes.clear();
+        self.root = None;
+        self.release_all_handles();
+        self.take_dirty();
+        self.expanded.clear();
+        self.removed.clear();
+        self.rebuild_index();
+    }
+
+    /// Clears the tree content, like [`VecTree::clear()`], but keeps the allocated children lists of the
+    /// nodes in a pool. The subsequent calls to [`VecTree::add()`] and the other methods adding nodes reuse
+    /// them, so rebuilding a tree of a similar size doesn't need to allocate them again.
+    ///
+    /// The pool is only released when the tree is dropped.
+    pub fn clear_keep_allocations(&mut self) {
+        assert_eq!(self.borrows.get(), 0, "must drop all iterator's node references before clearing a VecTree{}", self.borrows.report());
+        self.structure_changed();
+        self.children_pool.extend(self.nodes.drain(..).filter_map(|node| {
+            let mut children = node.children;
+            (children.capacity() > 0).then(|| {
+                children.clear();
+                children
+            })
+        }));
+        self.root = None;
+        self.release_all_handles();
+        self.take_dirty();
+        self.expanded.clear();
+        self.removed.clear();
+        self.rebuild_index();
+    }
+
+    /// Empties the tree and iterates over its items, which are moved out of the tree in a post-order,
+    /// depth-first search starting at the root node. The items of the nodes that can't be reached from
+    /// the root are dropped.
+    ///
+    /// The tree is emptied when the method is called, even if the iterator isn't consumed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root".to_string());
+    /// let a = tree.add(Some(root), "a".to_string());
+    /// tree.add(Some(a), "a1".to_string());
+    /// tree.add(Some(root), "b".to_string());
+    /// let items: Vec<String> = tree.drain_depth().collect();
+    /// assert_eq!(items, ["a1", "a", "b", "root"]);
+    /// assert!(tree.is_empty());
+    /// ```
+    pub fn drain_depth(&mut self) -> impl Iterator<Item = T> {
+        self.take_post_order().into_iter()
+    }
+
+    /// Empties the tree and returns the items of the nodes under the root, in post-order.
+    fn take_post_order(&mut self) -> Vec<T> {
+        let mut order = Vec::new();
+        if let Some(root) = self.root {
+            self.visit_post_order_at(root, |index, _, _| order.push(index));
+        }
+        let mut items = std::mem::take(&mut self.nodes).into_iter().map(|node| Some(node.data.into_inner())).collect::<Vec<_>>();
+        self.clear();
+        order.into_iter().map(|index| items[index].take().unwrap()).collect()
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+// Node layout
+
+impl<T> VecTree<T> {
+    /// Reorders the buffer of nodes according to the permutation `perm`, where `perm[i]` is the new
+    /// index of the node currently at index `i`. The root and the children indices are updated
+    /// accordingly, so the tree keeps the same structure.
+    ///
+    /// Returns an error and leaves the tree unchanged if `perm` doesn't have one entry per node or
+    /// if it doesn't give each node a distinct index.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let a = tree.add(None, "a");
+    /// let root = tree.addci(None, "root", a);
+    /// tree.set_root(root);
+    /// tree.apply_permutation(&[1, 0]).unwrap();
+    /// assert_eq!(tree.get_root(), Some(0));
+    /// assert_eq!(tree.children(0), &[1]);
+    /// assert_eq!(*tree.get(1), "a");
+    /// ```
+    pub fn apply_permutation(&mut self, perm: &[usize]) -> Result<(), TreeError> {
+        let size = self.nodes.len();
+        if perm.len() != size {
+            return Err(TreeError::BadPermutation);
+        }
+        let mut used = vec![false; size];
+        for &new_index in perm {
+            if new_index >= size || used[new_index] {
+                return Err(TreeError::BadPermutation);
+            }
+            used[new_index] = true;
+        }
+        self.relocate(perm);
+        Ok(())
+    }
+
+    /// Renumbers the nodes in breadth-first order from the root, so that the root is at index 0 and
+    /// the children of each node occupy contiguous, increasing indices. The nodes that aren't attached
+    /// to the root follow, numbered the same way from each node without parent, in the order of their
+    /// current index.
+    ///
+    /// Returns the mapping from the old indices to the new ones, where `mapping[i]` is the new index
+    /// of the node previously at index `i`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let a1 = tree.add(None, "a1");
+    /// let a = tree.addci(None, "a", a1);
+    /// let b = tree.add(None, "b");
+    /// let root = tree.addci_iter(None, "root", [a, b]);
+    /// tree.set_root(root);
+    /// assert_eq!(tree.renumber(), vec![3, 1, 2, 0]);
+    /// assert_eq!(tree.children(0), &[1, 2]);
+    /// assert_eq!(tree.children(1), &[3]);
+    /// ```
+    pub fn renumber(&mut self) -> Vec<usize> {
+        let size = self.nodes.len();
+        let parents = self.parents();
+        let mut new_index = vec![usize::MAX; size];
+        let mut order = Vec::with_capacity(size);
+        let tops = self.root.into_iter()
+            .chain((0..size).filter(|&index| parents[index].is_none() && Some(index) != self.root))
+            .chain(0..size);
+        for top in tops {
+            if new_index[top] != usize::MAX {
+                continue;
+            }
+            let start = order.len();
+            new_index[top] = start;
+            order.push(top);
+            // the nodes added to `order` are the breadth-first queue:
+            let mut next = start;
+            while next < order.len() {
+                for &child in &self.nodes[order[next]].children {
+                    if new_index[child] == usize::MAX {
+                        new_index[child] = order.len();
+                        order.push(child);
+                    }
+                }
+                next += 1;
+            }
+        }
+        self.relocate(&new_index);
+        new_index
+    }
+
+    /// Moves each node from index `i` to index `new_index[i]` and updates the root and the children
+    /// indices. `new_index` must be a valid permutation.
+    fn relocate(&mut self, new_index: &[usize]) {
+        self.structure_changed();
+        let mut new_nodes = Vec::with_capacity(self.nodes.len());
+        new_nodes.resize_with(self.nodes.len(), || None);
+        for (index, mut node) in self.nodes.drain(..).enumerate() {
+            for child in node.children.iter_mut() {
+                *child = new_index[*child];
+            }
+            new_nodes[new_index[index]] = Some(node);
+        }
+        self.nodes.extend(new_nodes.into_iter().map(|node| node.unwrap()));
+        self.root = self.root.map(|root| new_index[root]);
+        for index in self.handles.iter_mut().flatten() {
+            *index = new_index[*index];
+        }
+        if let Some(dirty) = &mut self.dirty {
+            permute_flags(dirty, new_index);
+        }
+        permute_flags(&mut self.expanded, new_index);
+        permute_flags(&mut self.removed, new_index);
+        self.rebuild_index();
+        self.generation += 1;
+    }
+
+    /// Removes the nodes for which `removed[i]` is `true` and shifts the other ones down in the buffer,
+    /// keeping their order. The root, the children indices and the handles are updated; the references
+    /// to the removed nodes are dropped. Returns the items of the removed nodes, in the order of their
+    /// index.
+    fn remove_nodes(&mut self, removed: &[bool]) -> Vec<T> {
+        self.structure_changed();
+        let mut new_index = vec![None; self.nodes.len()];
+        let mut size = 0;
+        for (index, new) in new_index.iter_mut().enumerate() {
+            if !removed[index] {
+                *new = Some(size);
+                size += 1;
+            }
+        }
+        let mut items = Vec::new();
+        let mut nodes = Vec::with_capacity(size);
+        for (index, mut node) in self.nodes.drain(..).enumerate() {
+            if removed[index] {
+                items.push(node.data.into_inner());
+            } else {
+                node.children.retain_mut(|child| new_index[*child].map(|new| *child = new).is_some());
+                nodes.push(node);
+            }
+        }
+        self.nodes = nodes;
+        self.root = self.root.and_then(|root| new_index[root]);
+        for handle in self.handles.iter_mut() {
+            *handle = handle.and_then(|index| new_index[index]);
+        }
+        if let Some(dirty) = &mut self.dirty {
+            remove_flags(dirty, removed);
+        }
+        remove_flags(&mut self.expanded, removed);
+        remove_flags(&mut self.removed, removed);
+        self.rebuild_index();
+        self.generation += 1;
+        items
+    }
+}
+
+/// Moves the flag of each node from index `i` to index `new_index[i]` (see [`VecTree::relocate()`]).
+/// The vector of flags may be shorter than the number of nodes.
+fn permute_flags<F: Default>(flags: &mut Vec<F>, new_index: &[usize]) {
+    let mut new_flags = Vec::new();
+    new_flags.resize_with(new_index.len(), F::default);
+    for (index, flag) in flags.drain(..).enumerate() {
+        new_flags[new_index[index]] = flag;
+    }
+    *flags = new_flags;
+}
+
+/// Removes the flags of the removed nodes (see [`VecTree::remove_nodes()`]). The vector of flags may
+/// be shorter than the number of nodes.
+fn remove_flags<F>(flags: &mut Vec<F>, removed: &[bool]) {
+    let mut index = 0;
+    flags.retain(|_| {
+        index += 1;
+        !removed[index - 1]
+    });
+}
+
+// ---------------------------------------------------------------------------------------------
+// Stable handles
+
+/// A stable reference to a node of a [VecTree], which remains valid when the nodes are moved in
+/// the buffer by methods like [`VecTree::renumber()`] or [`VecTree::apply_permutation()`].
+///
+/// A handle is created with [`VecTree::handle()`] and converted to the current index of its node
+/// with [`VecTree::resolve()`]. It's only valid for the tree that created it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Handle(usize);
+
+impl<T> VecTree<T> {
+    /// Creates a handle to the node `index`, which remains valid when the nodes are moved in the
+    /// buffer (see [Handle]).
+    ///
+    /// The tree keeps a table of the handles and updates it each time the nodes are moved, so the
+    /// handles should be released with [`VecTree::release_handle()`] when they're not needed any more.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let a = tree.add(None, "a");
+    /// let root = tree.addci(None, "root", a);
+    /// tree.set_root(root);
+    /// let handle = tree.handle(a);
+    /// tree.renumber();
+    /// assert_eq!(tree.resolve(handle), Some(1));
+    /// assert_eq!(*tree.get(1), "a");
+    /// ```
+    pub fn handle(&mut self, index: usize) -> Handle {
+        assert!(index < self.nodes.len(), "node index {index} doesn't exist");
+        self.handles.push(Some(index));
+        Handle(self.handles.len() - 1)
+    }
+
+    /// Returns the current index of the node referenced by `handle`, or `None` if the handle was
+    /// released or if the node doesn't exist any more.
+    pub fn resolve(&self, handle: Handle) -> Option<usize> {
+        self.handles.get(handle.0).copied().flatten()
+    }
+
+    /// Releases a handle, which can't be resolved any more.
+    pub fn release_handle(&mut self, handle: Handle) {
+        if let Some(index) = self.handles.get_mut(handle.0) {
+            *index = None;
+        }
+    }
+
+    fn release_all_handles(&mut self) {
+        self.handles.fill(None);
+        self.generation += 1;
+    }
+
+    /// Creates a weak reference to the node `index`, which can be upgraded to its index as long as
+    /// no node has been moved or removed (see [WeakNodeRef]).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// let weak = tree.weak_ref(a);
+    /// tree.add(Some(root), "b");
+    /// assert_eq!(weak.upgrade(&tree), Some(a));
+    /// tree.clear();
+    /// assert_eq!(weak.upgrade(&tree), None);
+    /// ```
+    pub fn weak_ref(&self, index: usize) -> WeakNodeRef {
+        assert!(index < self.nodes.len(), "node index {index} doesn't exist");
+        WeakNodeRef { tree_id: self.id, index, generation: self.generation }
+    }
 
-impl IntoUsize for &usize {
-    fn into_usize(self) -> usize { *self }
+    /// Returns the generation of the tree, which changes each time nodes are moved or removed, and
+    /// so each time the indices held by the user may become invalid.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
 }
 
-impl<T, A, C> From<(Option<usize>, A)> for VecTree<T>
-where
-    A: IntoIterator<Item=(T, C)>,
-    C: IntoIterator<Item: IntoUsize>,
-{
-    /// Creates a [VecTree] from a tuple `(r, array)`, where
-    /// * `r` is an optional root index
-    /// * `array` is any collection that can be converted into an iterator of `(T, &[usize])`,
-    ///   `T` being the node value at that index and the slice being the enumeration of its
-    ///   children indices.
+/// A weak reference to a node of a [VecTree], created by [`VecTree::weak_ref()`].
+///
+/// Unlike a [Handle], it isn't updated by the tree: it can only be upgraded to the node index with
+/// the tree that created it, and if that tree is still in the same generation, that is, if no node
+/// has been moved or removed since its creation. It costs nothing to the tree, so it's suited to caches that must not hold a stale
+/// index, like the last selected node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WeakNodeRef {
+    tree_id: u64,
+    index: usize,
+    generation: u64
+}
+
+impl WeakNodeRef {
+    /// Returns the index of the referenced node if it's still valid in `tree`, or `None` if nodes have
+    /// been moved or removed since the reference was created, or if `tree` isn't the tree that created it.
+    pub fn upgrade<T>(&self, tree: &VecTree<T>) -> Option<usize> {
+        (self.tree_id == tree.id && self.generation == tree.generation && self.index < tree.nodes.len()).then_some(self.index)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+// Branded indices
+
+/// The index of a node in a [BrandedTree], which can't be used with another branded tree.
+///
+/// The brand `'id` is an invariant lifetime that is unique to each call of [`VecTree::branded()`],
+/// so mixing the node IDs of two trees is rejected at compile time.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct NodeId<'id> {
+    index: usize,
+    _brand: PhantomData<fn(&'id ()) -> &'id ()>
+}
+
+impl<'id> NodeId<'id> {
+    fn new(index: usize) -> Self {
+        NodeId { index, _brand: PhantomData }
+    }
+
+    /// Returns the index of the node in the tree.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl Clone for NodeId<'_> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl Copy for NodeId<'_> {}
+
+/// A view of a [VecTree] whose node indices are branded, created by [`VecTree::branded()`].
+///
+/// The nodes can be added and accessed with [NodeId] indices that can only come from this view,
+/// so they always exist in the tree.
+pub struct BrandedTree<'id, 'a, T> {
+    tree: &'a mut VecTree<T>,
+    _brand: PhantomData<fn(&'id ()) -> &'id ()>
+}
+
+impl<T> VecTree<T> {
+    /// Calls `f` with a view of the tree in which the node indices are branded, so that an index
+    /// can't be used by mistake with another tree, even if it has the same type. The mistake is
+    /// detected at compile time.
+    ///
+    /// # Example
     ///
-    /// ## Example
-    /// ```rust
-    /// # use vectree::VecTree;
-    /// let tree = VecTree::from((
-    ///     Some(0),
-    ///     vec![
-    ///         ("root", vec![1, 2]),
-    ///         ("a",    vec![3, 4]),
-    ///         ("b",    vec![]),
-    ///         ("a.1",  vec![]),
-    ///         ("a.2",  vec![]),
-    ///     ]
-    /// ));
-    /// let str = tree.iter_depth_simple()
-    ///     .map(|n| format!("{}:{}", n.depth, *n))
-    ///     .collect::<Vec<_>>()
-    ///     .join(", ");
-    /// assert_eq!(str, "2:a.1, 2:a.2, 1:a, 1:b, 0:root");
     /// ```
-    fn from((root, nodes): (Option<usize>, A)) -> Self {
-        VecTree {
-            nodes: nodes.into_iter()
-                .map(|(value, children)| Node { data: UnsafeCell::new(value), children: children.into_iter().map(|c| c.into_usize()).collect() })
-                .collect(),
-            borrows: Cell::new(0),
-            root,
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let count = tree.branded(|mut tree| {
+    ///     let root = tree.add_root("root");
+    ///     let a = tree.add(Some(root), "a");
+    ///     *tree.get_mut(a) = "A";
+    ///     tree.add(Some(a), "a1");
+    ///     tree.children(root).len()
+    /// });
+    /// assert_eq!(count, 1);
+    /// assert_eq!(*tree.get(1), "A");
+    /// ```
+    ///
+    /// The node IDs of one tree can't be used with another one:
+    ///
+    /// ```compile_fail
+    /// use vectree::VecTree;
+    /// let mut tree1 = VecTree::new();
+    /// let mut tree2 = VecTree::new();
+    /// tree1.branded(|mut tree1| {
+    ///     let root1 = tree1.add_root("root1");
+    ///     tree2.branded(|mut tree2| {
+    ///         tree2.add_root("root2");
+    ///         tree2.add(Some(root1), "a"); // root1 doesn't belong to tree2
+    ///     });
+    /// });
+    /// ```
+    pub fn branded<R, F>(&mut self, f: F) -> R
+    where
+        F: for<'id> FnOnce(BrandedTree<'id, '_, T>) -> R
+    {
+        f(BrandedTree { tree: self, _brand: PhantomData })
+    }
+}
+
+impl<'id, T> BrandedTree<'id, '_, T> {
+    /// Returns the ID of the tree root, if it exists.
+    pub fn root(&self) -> Option<NodeId<'id>> {
+        self.tree.root.map(NodeId::new)
+    }
+
+    /// Returns the ID of the node `index`, if it exists.
+    pub fn id(&self, index: usize) -> Option<NodeId<'id>> {
+        (index < self.tree.nodes.len()).then(|| NodeId::new(index))
+    }
+
+    /// Adds an item and defines it as root of the tree (see [`VecTree::add_root()`]).
+    pub fn add_root(&mut self, item: T) -> NodeId<'id> {
+        NodeId::new(self.tree.add_root(item))
+    }
+
+    /// Adds an item to the tree (see [`VecTree::add()`]).
+    pub fn add(&mut self, parent: Option<NodeId<'id>>, item: T) -> NodeId<'id> {
+        NodeId::new(self.tree.add(parent.map(|id| id.index), item))
+    }
+
+    /// Returns a reference to the item.
+    pub fn get(&self, id: NodeId<'id>) -> &T {
+        self.tree.get(id.index)
+    }
+
+    /// Returns a mutable reference to the item.
+    pub fn get_mut(&mut self, id: NodeId<'id>) -> &mut T {
+        self.tree.get_mut(id.index)
+    }
+
+    /// Returns the IDs of the item's children.
+    pub fn children(&self, id: NodeId<'id>) -> Vec<NodeId<'id>> {
+        self.tree.children(id.index).iter().map(|&index| NodeId::new(index)).collect()
+    }
+
+    /// Returns the underlying tree.
+    pub fn tree(&self) -> &VecTree<T> {
+        self.tree
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+// Tree identity
+
+fn new_tree_id() -> u64 {
+    static NEXT_TREE_ID: AtomicU64 = AtomicU64::new(0);
+    NEXT_TREE_ID.fetch_add(1, AtomicOrdering::Relaxed)
+}
+
+/// The index of a node, tagged with the identity of its [VecTree], so that using it with another tree
+/// is detected at runtime.
+///
+/// It's created by [`VecTree::tagged_id()`] or by the `tagged_id()` method of the iterator proxies.
+/// Each tree has a unique identity, including the clones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TaggedNodeId {
+    tree_id: u64,
+    index: usize
+}
+
+impl TaggedNodeId {
+    /// Returns the index of the node in its tree.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the identity of the node's tree.
+    pub fn tree_id(&self) -> u64 {
+        self.tree_id
+    }
+}
+
+impl<T> VecTree<T> {
+    /// Returns the unique identity of the tree.
+    pub fn tree_id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the index `index` tagged with the identity of the tree.
+    pub fn tagged_id(&self, index: usize) -> TaggedNodeId {
+        assert!(index < self.nodes.len(), "node index {index} doesn't exist");
+        TaggedNodeId { tree_id: self.id, index }
+    }
+
+    /// Returns the index of the node `id`, or an error if `id` belongs to another tree
+    /// or if the node doesn't exist.
+    pub fn resolve_tagged(&self, id: TaggedNodeId) -> Result<usize, TreeError> {
+        if id.tree_id != self.id {
+            Err(TreeError::ForeignNode { node_tree: id.tree_id, tree: self.id })
+        } else if id.index >= self.nodes.len() {
+            Err(TreeError::BadIndex(id.index))
+        } else {
+            Ok(id.index)
+        }
+    }
+
+    /// Returns a reference to the item `id`.
+    ///
+    /// Panics if `id` belongs to another tree.
+    ///
+    /// # Example
+    ///
+    /// ```should_panic
+    /// use vectree::VecTree;
+    /// let mut tree1 = VecTree::new();
+    /// let root1 = tree1.add_root("root1");
+    /// let id1 = tree1.tagged_id(root1);
+    /// let mut tree2 = VecTree::new();
+    /// tree2.add_root("root2");
+    /// assert_eq!(*tree1.get_tagged(id1), "root1");
+    /// tree2.get_tagged(id1); // panics
+    /// ```
+    pub fn get_tagged(&self, id: TaggedNodeId) -> &T {
+        match self.resolve_tagged(id) {
+            Ok(index) => self.get(index),
+            Err(e) => panic!("{e}")
+        }
+    }
+
+    /// Returns a mutable reference to the item `id`.
+    ///
+    /// Panics if `id` belongs to another tree.
+    pub fn get_tagged_mut(&mut self, id: TaggedNodeId) -> &mut T {
+        match self.resolve_tagged(id) {
+            Ok(index) => self.get_mut(index),
+            Err(e) => panic!("{e}")
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+// Cached traversal
+
+impl<T> VecTree<T> {
+    /// Returns the post-order, depth-first sequence of the nodes of the [VecTree] from its root node,
+    /// as `(index, depth)` pairs. It's the sequence given by [`VecTree::iter_depth_simple()`].
+    ///
+    /// The sequence is computed at the first call and cached until the structure of the tree is modified,
+    /// so repeated traversals of a stable tree don't need to run the depth-first search again. Modifying
+    /// the data of the nodes doesn't invalidate the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add(Some(a), "a1");
+    /// assert_eq!(tree.post_order(), &[(2, 2), (1, 1), (0, 0)]);
+    /// tree.add(Some(root), "b");
+    /// assert_eq!(tree.post_order(), &[(2, 2), (1, 1), (3, 1), (0, 0)]);
+    /// ```
+    pub fn post_order(&self) -> &[(usize, u32)] {
+        self.order.get_or_init(|| self.iter_depth_simple().map(|inode| (inode.index, inode.depth)).collect())
+    }
+
+    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
+    /// its root node, like [`VecTree::iter_depth_simple()`], but using the cached sequence given
+    /// by [`VecTree::post_order()`].
+    pub fn iter_depth_simple_cached(&self) -> impl Iterator<Item = NodeProxySimple<'_, T>> {
+        let data = IterDataSimple { tree: self };
+        self.post_order().iter().map(move |&(index, depth)| data.create_proxy(index, depth))
+    }
+
+    /// Computes the number of descendants of every node, so that [`VecTree::num_descendants()`] and
+    /// the `num_descendants()` method of the proxies return it in O(1).
+    ///
+    /// The result is cached until the structure of the tree is modified; calling this method again
+    /// on a stable tree doesn't do anything.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add_iter(Some(a), ["a1", "a2"]);
+    /// tree.compute_descendants();
+    /// let sizes = tree.iter_depth().map(|n| (*n, n.num_descendants().unwrap())).collect::<Vec<_>>();
+    /// assert_eq!(sizes, [("a1", 0), ("a2", 0), ("a", 2), ("root", 3)]);
+    /// ```
+    pub fn compute_descendants(&self) {
+        self.descendants.get_or_init(|| {
+            let mut descendants = vec![0; self.nodes.len()];
+            let parents = self.parents();
+            for top in (0..self.nodes.len()).filter(|&i| parents[i].is_none()) {
+                for inode in self.iter_depth_simple_at(top) {
+                    descendants[inode.index] = self.nodes[inode.index].children.iter().map(|&c| 1 + descendants[c]).sum();
+                }
+            }
+            descendants
+        });
+    }
+
+    /// Returns the number of descendants of the node of index `index` if they have been computed by
+    /// [`VecTree::compute_descendants()`] since the last structural modification, or `None` otherwise.
+    pub fn num_descendants(&self, index: usize) -> Option<usize> {
+        self.descendants.get().map(|descendants| descendants[index])
+    }
+
+    /// Returns a pointer to the cached numbers of descendants, or a null pointer if they haven't been
+    /// computed.
+    fn descendants_ptr(&self) -> *const usize {
+        self.descendants.get().map_or(std::ptr::null(), |descendants| descendants.as_ptr())
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+// Modification tracking
+
+impl<T> VecTree<T> {
+    /// Enables or disables the tracking of the modified items. When it's enabled, each node whose
+    /// item is mutably accessed is marked as modified, until [`VecTree::take_dirty()`] is called.
+    ///
+    /// A node is marked when a mutable reference to its item is given, through the mutable proxies
+    /// of the iterators, [`VecTree::get_mut()`], [`VecTree::values_mut()`], [`VecTree::iter_children_mut()`]
+    /// or the tree's [`IndexMut`], even if the item isn't actually modified. The structural
+    /// modifications aren't tracked.
+    ///
+    /// Disabling the tracking forgets the modified nodes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root(0);
+    /// tree.add_iter(Some(root), [1, 2, 3]);
+    /// tree.set_dirty_tracking(true);
+    /// for mut inode in tree.iter_depth_simple_mut() {
+    ///     if *inode % 2 == 1 {
+    ///         *inode *= 10;
+    ///     }
+    /// }
+    /// assert_eq!(tree.take_dirty(), [1, 3]);
+    /// assert!(tree.take_dirty().is_empty());
+    /// ```
+    pub fn set_dirty_tracking(&mut self, enabled: bool) {
+        if enabled != self.dirty.is_some() {
+            self.dirty = enabled.then(Vec::new);
+        }
+    }
+
+    /// Returns `true` if the tracking of the modified items is enabled (see [`VecTree::set_dirty_tracking()`]).
+    pub fn is_dirty_tracking(&self) -> bool {
+        self.dirty.is_some()
+    }
+
+    /// Returns the indices of the nodes whose item has been mutably accessed since the last call, in
+    /// increasing order, and clears them (see [`VecTree::set_dirty_tracking()`]). The list is empty if
+    /// the tracking is disabled.
+    pub fn take_dirty(&mut self) -> Vec<usize> {
+        match &mut self.dirty {
+            Some(dirty) => dirty.iter_mut().enumerate().filter_map(|(index, flag)| flag.take().then_some(index)).collect(),
+            None => Vec::new()
+        }
+    }
+
+    /// Marks the node `index` as modified if the modifications are tracked.
+    fn mark_dirty(&mut self, index: usize) {
+        if let Some(dirty) = &mut self.dirty {
+            if index < self.nodes.len() {
+                if dirty.len() <= index {
+                    dirty.resize_with(self.nodes.len(), Cell::default);
+                }
+                dirty[index].set(true);
+            }
+        }
+    }
+
+    /// Returns a pointer to the dirty flags, which include all the nodes, or a null pointer if the
+    /// modifications aren't tracked.
+    fn dirty_ptr(&mut self) -> *const Cell<bool> {
+        match &mut self.dirty {
+            Some(dirty) => {
+                dirty.resize_with(self.nodes.len(), Cell::default);
+                dirty.as_ptr()
+            }
+            None => std::ptr::null()
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+// Traversal helpers
+
+impl<T> VecTree<T> {
+    /// Post-order, depth-first walk over all the nodes of the [VecTree], starting at its root node.
+    /// The walk stops as soon as `f` returns [`ControlFlow::Break`], and the break value is returned.
+    ///
+    /// Each node is given as a [NodeProxy], like with [`VecTree::iter_depth()`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::ops::ControlFlow;
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// tree.add_iter(Some(root), ["a", "b", "c"]);
+    /// let found = tree.try_walk(|node| {
+    ///     if *node == "b" { ControlFlow::Break(node.index) } else { ControlFlow::Continue(()) }
+    /// });
+    /// assert_eq!(found, ControlFlow::Break(2));
+    /// ```
+    pub fn try_walk<B, F>(&self, f: F) -> ControlFlow<B>
+    where
+        F: FnMut(NodeProxy<'_, T>) -> ControlFlow<B>
+    {
+        self.iter_depth().try_for_each(f)
+    }
+
+    /// Post-order, depth-first walk over all the nodes of the [VecTree], starting at the node of
+    /// index `top`. The walk stops as soon as `f` returns [`ControlFlow::Break`], and the break
+    /// value is returned.
+    pub fn try_walk_at<B, F>(&self, top: usize, f: F) -> ControlFlow<B>
+    where
+        F: FnMut(NodeProxy<'_, T>) -> ControlFlow<B>
+    {
+        self.iter_depth_at(top).try_for_each(f)
+    }
+
+    /// Post-order, depth-first walk over all the nodes of the [VecTree], starting at its root node.
+    /// The walk stops as soon as `f` returns [`ControlFlow::Break`], and the break value is returned.
+    ///
+    /// Each node is given as a [NodeProxyMut], like with [`VecTree::iter_depth_mut()`].
+    pub fn try_walk_mut<B, F>(&mut self, f: F) -> ControlFlow<B>
+    where
+        F: FnMut(NodeProxyMut<'_, T>) -> ControlFlow<B>
+    {
+        self.iter_depth_mut().try_for_each(f)
+    }
+
+    /// Post-order, depth-first walk over all the nodes of the [VecTree], starting at the node of
+    /// index `top`. The walk stops as soon as `f` returns [`ControlFlow::Break`], and the break
+    /// value is returned.
+    pub fn try_walk_at_mut<B, F>(&mut self, top: usize, f: F) -> ControlFlow<B>
+    where
+        F: FnMut(NodeProxyMut<'_, T>) -> ControlFlow<B>
+    {
+        self.iter_depth_at_mut(top).try_for_each(f)
+    }
+
+    /// Post-order, depth-first walk over all the nodes of the [VecTree], starting at its root node.
+    /// The walk stops at the first error returned by `f`, which is then returned by the method.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root(10);
+    /// tree.add_iter(Some(root), [1, 0, 2]);
+    /// let result = tree.try_for_each(|node| {
+    ///     if *node == 0 { Err(format!("zero at index {}", node.index)) } else { Ok(()) }
+    /// });
+    /// assert_eq!(result, Err("zero at index 2".to_string()));
+    /// ```
+    pub fn try_for_each<E, F>(&self, f: F) -> Result<(), E>
+    where
+        F: FnMut(NodeProxy<'_, T>) -> Result<(), E>
+    {
+        self.iter_depth().try_for_each(f)
+    }
+
+    /// Post-order, depth-first walk over all the nodes of the [VecTree], starting at its root node.
+    /// The walk stops at the first error returned by `f`, which is then returned by the method.
+    pub fn try_for_each_mut<E, F>(&mut self, f: F) -> Result<(), E>
+    where
+        F: FnMut(NodeProxyMut<'_, T>) -> Result<(), E>
+    {
+        self.iter_depth_mut().try_for_each(f)
+    }
+
+    /// Calls `f(index, depth, item)` on each node of the [VecTree], in a post-order, depth-first
+    /// search starting at its root node.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// tree.add_iter(Some(root), ["a", "b"]);
+    /// let mut result = vec![];
+    /// tree.for_each(|index, depth, item| result.push(format!("{index}:{depth}:{item}")));
+    /// assert_eq!(result, ["1:1:a", "2:1:b", "0:0:root"]);
+    /// ```
+    pub fn for_each<F: FnMut(usize, u32, &T)>(&self, f: F) {
+        self.visit_post_order(f);
+    }
+
+    /// Calls `f(index, depth, item)` on each node of the [VecTree], in a post-order, depth-first
+    /// search starting at the node of index `top`.
+    pub fn for_each_at<F: FnMut(usize, u32, &T)>(&self, top: usize, f: F) {
+        self.visit_post_order_at(top, f);
+    }
+
+    /// Calls `f(index, depth, item)` on each node of the [VecTree], in a post-order, depth-first
+    /// search starting at its root node.
+    ///
+    /// It's the fastest traversal: it reads the nodes directly, without creating a proxy for each
+    /// node like the iterators do.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root(1);
+    /// let a = tree.add(Some(root), 2);
+    /// tree.add_iter(Some(a), [3, 4]);
+    /// let mut sum = 0;
+    /// tree.visit_post_order(|_, depth, item| sum += depth * item);
+    /// assert_eq!(sum, 16);
+    /// ```
+    pub fn visit_post_order<F: FnMut(usize, u32, &T)>(&self, f: F) {
+        if let Some(root) = self.root {
+            self.visit_post_order_at(root, f);
+        }
+    }
+
+    /// Calls `f(index, depth, item)` on each node of the [VecTree], in a post-order, depth-first
+    /// search starting at the node of index `top` (see [`VecTree::visit_post_order()`]).
+    pub fn visit_post_order_at<F: FnMut(usize, u32, &T)>(&self, top: usize, mut f: F) {
+        self.debug_validate();
+        assert!(top < self.nodes.len(), "node index {top} doesn't exist");
+        // (index, position of the next child to visit)
+        let mut stack = vec![(top, 0)];
+        while let Some((index, pos)) = stack.last_mut() {
+            let node = &self.nodes[*index];
+            if let Some(&child) = node.children.get(*pos) {
+                *pos += 1;
+                assert!(child < self.nodes.len(), "node index {child} doesn't exist");
+                stack.push((child, 0));
+            } else {
+                let index = *index;
+                stack.pop();
+                // SAFETY: The data can't be modified while the tree is borrowed immutably.
+                f(index, stack.len() as u32, unsafe { &*node.data.get() });
+            }
         }
     }
-}
 
-impl<T: Display> Display for VisitNode<T> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            VisitNode::Down(v) => write!(f, "D({v})"),
-            VisitNode::Up(v) => write!(f, "U({v})"),
+    /// Calls `f(index, depth, item)` on each node of the [VecTree], in a post-order, depth-first
+    /// search starting at its root node. `item` is a mutable reference to the node's data.
+    pub fn for_each_mut<F: FnMut(usize, u32, &mut T)>(&mut self, mut f: F) {
+        for mut inode in self.iter_depth_simple_mut() {
+            f(inode.index, inode.depth, &mut inode);
         }
     }
-}
 
-// ---------------------------------------------------------------------------------------------
-// Iterators
-//
-// Since the main depth-first search loop is the same for all iterators, we use the `TData` generic parameter
-// that implements the `TreeDataIter` trait. There are four types of iterator:
-// * IterDataSimple:   each iteration returns a NodeProxySimple
-// * IterDataSimpleMut:                         NodeProxySimpleMut
-// * IterData:                                  NodeProxy
-// * IterDataMut:                               NodeProxyMut
-//
-// NodeProxySimple / NodeProxySimpleMut allow to access the data.
-// NodeProxy / NodeProxy allow to access the data and the node's children, and it also allows to
-// iterate over the children or even the subtree with another embedded depth-first search, with
-// that node as root.
+    /// Calls `f(index, depth, item)` on each node of the [VecTree], in a post-order, depth-first
+    /// search starting at the node of index `top`. `item` is a mutable reference to the node's data.
+    pub fn for_each_at_mut<F: FnMut(usize, u32, &mut T)>(&mut self, top: usize, mut f: F) {
+        for mut inode in self.iter_depth_simple_at_mut(top) {
+            f(inode.index, inode.depth, &mut inode);
+        }
+    }
 
-/// A [VecTree] post-order, depth-first search iterator.
-pub struct VecTreePoDfsIter<TData> {
-    stack: Vec<VisitNode<usize>>,
-    depth: u32,
-    next: Option<VisitNode<usize>>,
-    data: TData
-}
+    /// Returns `true` if `pred` is `true` for any node of the subtree under the node of index `index`,
+    /// including that node. The search stops at the first match.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add_iter(Some(a), ["a1", "a2"]);
+    /// let b = tree.add(Some(root), "b");
+    /// assert!(tree.any_in_subtree(a, |s| s.ends_with('2')));
+    /// assert!(!tree.any_in_subtree(b, |s| s.ends_with('2')));
+    /// ```
+    pub fn any_in_subtree<F: FnMut(&T) -> bool>(&self, index: usize, mut pred: F) -> bool {
+        self.iter_depth_simple_at(index).any(|inode| pred(&inode))
+    }
 
-/// Implements methods used by the depth-first search algorithm and which depends on the
-/// type of iterator: simple or full-fledged (allowing to search each node's children),
-/// immutable or mutable.
-pub trait TreeDataIter {
-    type TProxy;
+    /// Returns `true` if `pred` is `true` for all the nodes of the subtree under the node of index `index`,
+    /// including that node. The search stops at the first node that doesn't match.
+    pub fn all_in_subtree<F: FnMut(&T) -> bool>(&self, index: usize, mut pred: F) -> bool {
+        self.iter_depth_simple_at(index).all(|inode| pred(&inode))
+    }
 
-    /// Gets a reference slice to the node's children indices.
-    fn get_children(&self, index: usize) -> &[usize];
+    /// Counts the nodes of the subtree under the node of index `index`, including that node,
+    /// for which `pred` is `true`.
+    pub fn count_in_subtree<F: FnMut(&T) -> bool>(&self, index: usize, mut pred: F) -> usize {
+        self.iter_depth_simple_at(index).filter(|inode| pred(inode)).count()
+    }
 
-    /// Creates the proxy returned by each iteration. The proxy is used to access the
-    /// tree node and, when a full-fledged iterator is used, the nodes below it.
-    fn create_proxy(&self, index: usize, depth: u32) -> Self::TProxy;
-}
+    /// Folds all the nodes of the subtree under the node of index `index`, including that node, into
+    /// an accumulator, starting from `init`. The nodes are visited in post-order, depth-first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root(1);
+    /// let a = tree.add(Some(root), 10);
+    /// tree.add_iter(Some(a), [100, 1000]);
+    /// assert_eq!(tree.aggregate(root, 0, |acc, x| acc + x), 1111);
+    /// assert_eq!(tree.aggregate(a, 0, |acc, x| acc + x), 1110);
+    /// ```
+    pub fn aggregate<B, F: FnMut(B, &T) -> B>(&self, index: usize, init: B, mut f: F) -> B {
+        self.iter_depth_simple_at(index).fold(init, |acc, inode| f(acc, &inode))
+    }
 
-impl<TData: TreeDataIter> Iterator for VecTreePoDfsIter<TData> {
-    type Item = TData::TProxy;
+    /// Computes a value for each node of the tree from its item and the values of its children, like
+    /// a recursive function would, and returns the value of the root, or `None` if there's no root.
+    ///
+    /// `f` is called with the item and the values of its children, in order. It's implemented without
+    /// recursion, so it can't overflow the call stack, whatever the depth of the tree.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add_iter(Some(a), ["a1", "a2"]);
+    /// tree.add(Some(root), "b");
+    /// let text = tree.walk_recursive(|item, children: Vec<String>| {
+    ///     if children.is_empty() { item.to_string() } else { format!("{item}({})", children.join(",")) }
+    /// });
+    /// assert_eq!(text, Some("root(a(a1,a2),b)".to_string()));
+    /// ```
+    pub fn walk_recursive<R, F: FnMut(&T, Vec<R>) -> R>(&self, f: F) -> Option<R> {
+        self.root.map(|root| self.walk_recursive_at(root, f))
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        // post-order depth-first search algorithm, common to all iterators
-        while let Some(node_dir) = self.next {
-            let index_option = match node_dir {
-                VisitNode::Down(index) => {
-                    let children = self.data.get_children(index);
-                    if children.is_empty() {
-                        Some(index)
-                    } else {
-                        self.depth += 1;
-                        self.stack.push(VisitNode::Up(index));
-                        for index in children.iter().rev() {
-                            self.stack.push(VisitNode::Down(*index));
-                        }
-                        None
-                    }
-                }
-                VisitNode::Up(index) => {
-                    self.depth -= 1;
-                    Some(index)
-                }
-            };
-            self.next = self.stack.pop();
-            if let Some(index) = index_option {
-                return Some(self.data.create_proxy(index, self.depth));
-            }
+    /// Computes a value for each node of the subtree under the node of index `index`, including that
+    /// node, from its item and the values of its children, and returns the value of the node `index`
+    /// (see [`VecTree::walk_recursive()`]).
+    pub fn walk_recursive_at<R, F: FnMut(&T, Vec<R>) -> R>(&self, index: usize, mut f: F) -> R {
+        // the values of the nodes waiting for their parent
+        let mut values = Vec::new();
+        for inode in self.iter_depth_simple_at(index) {
+            let children = values.split_off(values.len() - inode.num_children());
+            values.push(f(&inode, children));
         }
-        None
+        values.pop().unwrap()
     }
-}
 
-impl<'a: 'i,'i, T> VecTree<T> {
-    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
-    /// its root node.
+    /// Evaluates the tree bottom-up and returns the value of the root, or `None` if there's no root.
     ///
-    /// The iterator returns a proxy for each node, which gives an immutable reference only to that node.
-    pub fn iter_depth_simple(&'a self) -> VecTreePoDfsIter<IterDataSimple<'i, T>> {
-        VecTreePoDfsIter::<IterDataSimple<'i, T>>::new(self, self.root)
+    /// The value of a leaf is given by `leaf`, and the value of any other node by `node`, from its item
+    /// and the values of its children. It's a simpler form of [`VecTree::walk_recursive()`], typically
+    /// used to evaluate expression trees.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("*");
+    /// let plus = tree.add(Some(root), "+");
+    /// tree.add_iter(Some(plus), ["2", "3"]);
+    /// tree.add(Some(root), "4");
+    /// let value = tree.reduce(
+    ///     |item| item.parse::<i32>().unwrap(),
+    ///     |item, args| if *item == "+" { args.iter().sum() } else { args.iter().product() });
+    /// assert_eq!(value, Some(20));
+    /// ```
+    pub fn reduce<R>(&self, leaf: impl Fn(&T) -> R, node: impl Fn(&T, Vec<R>) -> R) -> Option<R> {
+        self.walk_recursive(|item, children| if children.is_empty() { leaf(item) } else { node(item, children) })
     }
 
-    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
-    /// the node of index `top`.
+    /// Creates a tree with the same structure and indices, where each item is transformed by `map`, and
+    /// aggregates the items and their transformed values with `reduce`, in a single pass.
     ///
-    /// The iterator returns a proxy for each node, which gives an immutable reference only to that node.
-    pub fn iter_depth_simple_at(&'a self, top: usize) -> VecTreePoDfsIter<IterDataSimple<'i, T>> {
-        VecTreePoDfsIter::<IterDataSimple<'i, T>>::new(self, Some(top))
+    /// All the nodes of the buffer are visited in their order of index, so the aggregate includes the
+    /// nodes which aren't attached to the root, if any, but not the removed nodes (see
+    /// [`VecTree::remove_subtree()`]), which are only transformed to keep the indices.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// tree.add_iter(Some(root), ["a", "bb", "ccc"]);
+    /// let (lengths, total) = tree.map_reduce(|item| item.len(), 0, |acc, _, len| acc + len);
+    /// assert_eq!(lengths.iter_depth_simple().map(|n| *n).collect::<Vec<_>>(), [1, 2, 3, 4]);
+    /// assert_eq!(total, 10);
+    /// ```
+    pub fn map_reduce<U, A>(&self, mut map: impl FnMut(&T) -> U, init: A, mut reduce: impl FnMut(A, &T, &U) -> A) -> (VecTree<U>, A) {
+        let mut acc = init;
+        let mut nodes = Vec::with_capacity(self.nodes.len());
+        for (index, node) in self.nodes.iter().enumerate() {
+            let value = map(node.value());
+            if !self.is_removed(index) {
+                acc = reduce(acc, node.value(), &value);
+            }
+            nodes.push(Node::with_children(value, node.children.clone()));
+        }
+        let mut tree = VecTree::from_nodes(nodes, self.root);
+        tree.strict = self.strict;
+        tree.removed = self.removed.clone();
+        (tree, acc)
     }
 
-    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
-    /// its root node.
+    /// Creates a tree with the same structure and indices, but without the items. It's cheaper than
+    /// cloning the tree when only its shape is needed, for example to compare or hash it.
     ///
-    /// The iterator returns a proxy for each node, which gives an immutable reference to that node
-    /// and its children with the following methods:
-    /// * [NodeProxy::num_children()], to get the number of children
-    /// * [NodeProxy::iter_children()], to iterate over the children with a proxy to access their children
-    /// * [NodeProxy::iter_children_simple()], to iterate over the children
-    /// * [NodeProxy::iter_depth_simple()], to iterate the subtree under the node
-    pub fn iter_depth(&'a self) -> VecTreePoDfsIter<IterData<'i, T>> {
-        VecTreePoDfsIter::<IterData<'i, T>>::new(self, self.root)
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root".to_string());
+    /// tree.add_iter(Some(root), ["a".to_string(), "b".to_string()]);
+    /// let skeleton = tree.skeleton();
+    /// assert_eq!(skeleton.len(), 3);
+    /// assert_eq!(skeleton.children(root), &[1, 2]);
+    /// ```
+    pub fn skeleton(&self) -> VecTree<()> {
+        let nodes = self.nodes.iter().map(|node| Node::with_children((), node.children.clone())).collect();
+        let mut tree = VecTree::from_nodes(nodes, self.root);
+        tree.strict = self.strict;
+        tree.removed = self.removed.clone();
+        tree
     }
 
-    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
-    /// the node of index `top`.
+    /// Returns `true` if both trees have the same shape under their root, ignoring the items and
+    /// the indices of the nodes: the roots and all their corresponding descendants must have the
+    /// same number of children.
     ///
-    /// The iterator returns a proxy for each node, which gives an immutable reference to that node
-    /// and its children with the following methods:
-    /// * [NodeProxy::num_children()], to get the number of children
-    /// * [NodeProxy::iter_children()], to iterate over the children with a proxy to access their children
-    /// * [NodeProxy::iter_children_simple()], to iterate over the children
-    /// * [NodeProxy::iter_depth_simple()], to iterate the subtree under the node
-    pub fn iter_depth_at(&'a self, top: usize) -> VecTreePoDfsIter<IterData<'i, T>> {
-        VecTreePoDfsIter::<IterData<'i, T>>::new(self, Some(top))
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree1 = VecTree::new();
+    /// let root = tree1.add_root("root");
+    /// tree1.add_iter(Some(root), ["a", "b"]);
+    /// let mut tree2 = VecTree::new();
+    /// let b = tree2.add(None, 2);
+    /// let a = tree2.add(None, 1);
+    /// tree2.addci_iter(None, 0, [a, b]);
+    /// tree2.set_root(2);
+    /// assert!(tree1.same_shape(&tree2));
+    /// tree2.add(Some(a), 3);
+    /// assert!(!tree1.same_shape(&tree2));
+    /// ```
+    pub fn same_shape<U>(&self, other: &VecTree<U>) -> bool {
+        let (root, other_root) = match (self.root, other.root) {
+            (None, None) => return true,
+            (Some(root), Some(other_root)) => (root, other_root),
+            _ => return false,
+        };
+        let mut stack = vec![(root, other_root)];
+        while let Some((index, other_index)) = stack.pop() {
+            let (children, other_children) = (&self.nodes[index].children, &other.nodes[other_index].children);
+            if children.len() != other_children.len() {
+                return false;
+            }
+            stack.extend(children.iter().copied().zip(other_children.iter().copied()));
+        }
+        true
     }
 
-    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
-    /// its root node.
+    /// Returns the index of the node with the maximum value in the subtree under the node of index
+    /// `index`, including that node, with respect to the comparison function `compare`, or `None`
+    /// if the node has been removed (see [`VecTree::remove_subtree()`]).
     ///
-    /// The iterator returns a proxy for each node, which gives a mutable reference only to that node.
-    pub fn iter_depth_simple_mut(&'a mut self) -> VecTreePoDfsIter<IterDataSimpleMut<'i, T>> {
-        VecTreePoDfsIter::<IterDataSimpleMut<'i, T>>::new(self, self.root)
+    /// If several nodes are equally maximum, the index of the last one in post-order is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add_iter(Some(a), ["a1", "a2"]);
+    /// let b = tree.add(Some(root), "b");
+    /// assert_eq!(tree.max_by_in_subtree(root, |x, y| x.cmp(y)), Some(root));
+    /// assert_eq!(tree.max_by_in_subtree(a, |x, y| x.cmp(y)).map(|i| *tree.get(i)), Some("a2"));
+    /// tree.remove_subtree(b);
+    /// assert_eq!(tree.max_by_in_subtree(b, |x, y| x.cmp(y)), None);
+    /// ```
+    pub fn max_by_in_subtree<F>(&self, index: usize, mut compare: F) -> Option<usize>
+    where
+        F: FnMut(&T, &T) -> Ordering
+    {
+        self.iter_depth_simple_at(index).max_by(|a, b| compare(a, b)).map(|inode| inode.index)
     }
 
-    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
-    /// the node of index `top`.
+    /// Returns the index of the node with the minimum value in the subtree under the node of index
+    /// `index`, including that node, with respect to the comparison function `compare`, or `None`
+    /// if the node has been removed (see [`VecTree::remove_subtree()`]).
     ///
-    /// The iterator returns a proxy for each node, which gives a mutable reference only to that node.
-    pub fn iter_depth_simple_at_mut(&'a mut self, top: usize) -> VecTreePoDfsIter<IterDataSimpleMut<'i, T>> {
-        VecTreePoDfsIter::<IterDataSimpleMut<'i, T>>::new(self, Some(top))
+    /// If several nodes are equally minimum, the index of the first one in post-order is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root(5);
+    /// let a = tree.add(Some(root), 3);
+    /// tree.add_iter(Some(a), [4, 1]);
+    /// tree.add(Some(root), 2);
+    /// assert_eq!(tree.min_by_in_subtree(root, |x, y| x.cmp(y)).map(|i| *tree.get(i)), Some(1));
+    /// assert_eq!(tree.min_by_in_subtree(a, |x, y| y.cmp(x)).map(|i| *tree.get(i)), Some(4));
+    /// ```
+    pub fn min_by_in_subtree<F>(&self, index: usize, mut compare: F) -> Option<usize>
+    where
+        F: FnMut(&T, &T) -> Ordering
+    {
+        self.iter_depth_simple_at(index).min_by(|a, b| compare(a, b)).map(|inode| inode.index)
     }
 
-    /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
-    /// its root node.
+    /// Returns the path from the root to the first node for which `pred` is `true`, or `None` if
+    /// no node matches. The path is the list of node indices, starting with the root and ending with
+    /// the matching node.
     ///
-    /// The iterator returns a proxy for each node, which gives a mutable reference to that node
-    /// and an immutable reference its children with the following methods:
-    /// * [NodeProxy::num_children()], to get the number of children
-    /// * [NodeProxy::iter_children()], to iterate over the children with a proxy to access their children
-    /// * [NodeProxy::iter_children_simple()], to iterate over the children
-    /// * [NodeProxy::iter_depth_simple()], to iterate the subtree under the node
-    pub fn iter_depth_mut(&'a mut self) -> VecTreePoDfsIter<IterDataMut<'i, T>> {
-        VecTreePoDfsIter::<IterDataMut<'i, T>>::new(self, self.root)
+    /// The nodes are tested in the same order as the other iterators: post-order, depth-first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add_iter(Some(a), ["a1", "a2"]);
+    /// assert_eq!(tree.find_path(|s| *s == "a2"), Some(vec![0, 1, 3]));
+    /// assert_eq!(tree.find_path(|s| *s == "b"), None);
+    /// ```
+    pub fn find_path<F: FnMut(&T) -> bool>(&self, mut pred: F) -> Option<Vec<usize>> {
+        let mut iter = self.iter_depth_simple();
+        while let Some(inode) = iter.next() {
+            if pred(&inode) {
+                let mut path = iter.ancestors().collect::<Vec<_>>();
+                path.reverse();
+                path.push(inode.index);
+                return Some(path);
+            }
+        }
+        None
+    }
+
+    /// Returns the path between the nodes of indices `a` and `b`, going through their lowest common
+    /// ancestor, or `None` if the nodes aren't in the same tree. The path is the list of node indices,
+    /// starting with `a` and ending with `b`.
+    ///
+    /// This method must find the parents of the nodes, so it scans the whole buffer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// let a1 = tree.add(Some(a), "a1");
+    /// let b = tree.add(Some(root), "b");
+    /// assert_eq!(tree.path_between(a1, b), Some(vec![a1, a, root, b]));
+    /// assert_eq!(tree.path_between(a1, a), Some(vec![a1, a]));
+    /// ```
+    pub fn path_between(&self, a: usize, b: usize) -> Option<Vec<usize>> {
+        assert!(a < self.len(), "node index {a} doesn't exist");
+        assert!(b < self.len(), "node index {b} doesn't exist");
+        let parents = self.parents();
+        // the number of steps is limited in case the parent links are cyclic
+        let lineage = |mut index: usize| {
+            let mut lineage = vec![index];
+            while let Some(parent) = parents[index] {
+                if lineage.len() > parents.len() {
+                    break;
+                }
+                lineage.push(parent);
+                index = parent;
+            }
+            lineage
+        };
+        let lineage_a = lineage(a);
+        let mut in_lineage_a = vec![false; self.len()];
+        for &index in &lineage_a {
+            in_lineage_a[index] = true;
+        }
+        let lineage_b = lineage(b);
+        let pos_b = lineage_b.iter().position(|&index| in_lineage_a[index])?;
+        let lca = lineage_b[pos_b];
+        let pos_a = lineage_a.iter().position(|&index| index == lca).unwrap();
+        let mut path = lineage_a[..=pos_a].to_vec();
+        path.extend(lineage_b[..pos_b].iter().rev());
+        Some(path)
+    }
+
+    /// Finds the parent of each node in the buffer by scanning all the children lists. If a node is
+    /// the child of several nodes, only the last one is kept.
+    fn parents(&self) -> Vec<Option<usize>> {
+        let mut parents = vec![None; self.nodes.len()];
+        for (index, node) in self.nodes.iter().enumerate() {
+            for &child in &node.children {
+                parents[child] = Some(index);
+            }
+        }
+        parents
     }
+}
+
+// ---------------------------------------------------------------------------------------------
+// Streaming reduction
 
+impl<T> VecTree<T> {
     /// Post-order, depth-first search iteration over all the nodes of the [VecTree], starting at
-    /// the node of index `top`.
+    /// its root node, which computes a value for each node from its item and the values of its
+    /// children, like [`VecTree::walk_recursive()`], but lazily.
     ///
-    /// The iterator returns a proxy for each node, which gives a mutable reference to that node
-    /// and an immutable reference its children with the following methods:
-    /// * [NodeProxy::num_children()], to get the number of children
-    /// * [NodeProxy::iter_children()], to iterate over the children with a proxy to access their children
-    /// * [NodeProxy::iter_children_simple()], to iterate over the children
-    /// * [NodeProxy::iter_depth_simple()], to iterate the subtree under the node
-    pub fn iter_depth_at_mut(&'a mut self, top: usize) -> VecTreePoDfsIter<IterDataMut<'i, T>> {
-        VecTreePoDfsIter::<IterDataMut<'i, T>>::new(self, Some(top))
+    /// `f` is called with the item and the values of its children. Each iteration gives a [Reduced]
+    /// item with the node and the values of its children, and the value of the root is given by
+    /// [`ReduceIter::into_result()`] once the iteration is over.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root(1);
+    /// let a = tree.add(Some(root), 2);
+    /// tree.add_iter(Some(a), [3, 4]);
+    /// tree.add(Some(root), 5);
+    /// let mut iter = tree.reduce_iter(|item, children: &[i32]| item + children.iter().sum::<i32>());
+    /// let mut sums = vec![];
+    /// for item in iter.by_ref() {
+    ///     sums.push((*item.node, item.children().to_vec()));
+    /// }
+    /// assert_eq!(sums, [(3, vec![]), (4, vec![]), (2, vec![3, 4]), (5, vec![]), (1, vec![9, 5])]);
+    /// assert_eq!(iter.into_result(), Some(15));
+    /// ```
+    pub fn reduce_iter<R, F: FnMut(&T, &[R]) -> R>(&self, f: F) -> ReduceIter<'_, T, R, F> {
+        ReduceIter { iter: self.iter_depth_simple(), values: Vec::new(), f }
+    }
+
+    /// Same as [`VecTree::reduce_iter()`], but starting at the node of index `top`.
+    pub fn reduce_iter_at<R, F: FnMut(&T, &[R]) -> R>(&self, top: usize, f: F) -> ReduceIter<'_, T, R, F> {
+        ReduceIter { iter: self.iter_depth_simple_at(top), values: Vec::new(), f }
+    }
+}
+
+/// An iterator which computes a value for each node from the values of its children, created by
+/// [`VecTree::reduce_iter()`].
+pub struct ReduceIter<'a, T, R, F> {
+    iter: VecTreePoDfsIter<IterDataSimple<'a, T>>,
+    // the values of the nodes waiting for their parent
+    values: Vec<R>,
+    f: F
+}
+
+impl<T, R, F> ReduceIter<'_, T, R, F> {
+    /// Returns the value of the top node, or `None` if the iteration isn't over.
+    pub fn into_result(mut self) -> Option<R> {
+        if self.iter.peek().is_none() && self.values.len() == 1 { self.values.pop() } else { None }
+    }
+}
+
+impl<'a, T, R, F: FnMut(&T, &[R]) -> R> Iterator for ReduceIter<'a, T, R, F> {
+    type Item = Reduced<'a, T, R>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.iter.next()?;
+        let children = self.values.split_off(self.values.len() - node.num_children());
+        self.values.push((self.f)(&node, &children));
+        Some(Reduced { node, children })
     }
+}
 
-    /// Clears the tree content.
-    pub fn clear(&mut self) {
-        // should never happen, since the compiler wouldn't allow another mutable borrow (required by this method):
-        assert_eq!(self.borrows.get(), 0, "must drop all iterator's node references before clearing a VecTree");
-        self.nodes.clear();
-        self.root = None;
+impl<T, R, F: FnMut(&T, &[R]) -> R> FusedIterator for ReduceIter<'_, T, R, F> {}
+
+/// An item given by [ReduceIter]: a node and the values computed for its children.
+pub struct Reduced<'a, T, R> {
+    pub node: NodeProxySimple<'a, T>,
+    children: Vec<R>
+}
+
+impl<T, R> Reduced<'_, T, R> {
+    /// Returns the values computed for the children of the node, in order.
+    pub fn children(&self) -> &[R] {
+        &self.children
+    }
+
+    /// Returns the values computed for the children of the node, in order.
+    pub fn into_children(self) -> Vec<R> {
+        self.children
     }
 }
 
@@ -801,12 +4434,27 @@ impl<'a: 'i,'i, T> VecTree<T> {
 
 impl<'a: 'i, 'i, T> VecTreePoDfsIter<IterDataSimple<'i, T>> {
     fn new(tree: &'a VecTree<T>, top: Option<usize>) -> Self {
-        VecTreePoDfsIter {
-            stack: Vec::new(),
-            depth: 0,
-            next: top.map(VisitNode::Down),
-            data: IterDataSimple { tree },
-        }
+        tree.debug_validate();
+        VecTreePoDfsIter::with_data(tree.live_top(top), IterDataSimple { tree })
+    }
+}
+
+impl<'a, T> VecTreePoDfsIter<IterDataSimple<'a, T>> {
+    /// Converts the iterator into an iterator of `(index, &item)` tuples.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// tree.add_iter(Some(root), ["a", "b"]);
+    /// let map = tree.iter_depth_simple().with_indices().collect::<HashMap<_, _>>();
+    /// assert_eq!(map[&2], &"b");
+    /// ```
+    pub fn with_indices(self) -> impl Iterator<Item = (usize, &'a T)> {
+        self.map(|inode| (inode.index, inode.into_ref()))
     }
 }
 
@@ -816,6 +4464,12 @@ pub struct IterDataSimple<'a, T> {
     tree: &'a VecTree<T>,
 }
 
+impl<T> Clone for IterDataSimple<'_, T> {
+    fn clone(&self) -> Self {
+        IterDataSimple { tree: self.tree }
+    }
+}
+
 impl<'a, T> TreeDataIter for IterDataSimple<'a, T> {
     type TProxy = NodeProxySimple<'a, T>;
 
@@ -833,6 +4487,7 @@ impl<'a, T> TreeDataIter for IterDataSimple<'a, T> {
         NodeProxySimple {
             index,
             depth,
+            tree_id: self.tree.id,
             num_children: unsafe { &(*self.tree.nodes.as_ptr().add(index)).children }.len(),
             data: unsafe { NonNull::new_unchecked((*self.tree.nodes.as_ptr().add(index)).data.get()) },
             _marker: PhantomData
@@ -845,12 +4500,25 @@ impl<'a, T> TreeDataIter for IterDataSimple<'a, T> {
 pub struct NodeProxySimple<'a, T> {
     pub index: usize,
     pub depth: u32,
+    tree_id: u64,
     num_children: usize,
     data: NonNull<T>,
     _marker: PhantomData<&'a T>
 }
 
-impl<T> NodeProxySimple<'_, T> {
+impl<'a, T> NodeProxySimple<'a, T> {
+    /// Converts the proxy into a reference to the node's item.
+    pub fn into_ref(self) -> &'a T {
+        // SAFETY: - The data lives as long as 'a.
+        //         - The proxy gives an immutable access for 'a, so no mutable borrow is possible.
+        unsafe { self.data.as_ref() }
+    }
+
+    /// Returns the index of the node, tagged with the identity of its tree (see [TaggedNodeId]).
+    pub fn tagged_id(&self) -> TaggedNodeId {
+        TaggedNodeId { tree_id: self.tree_id, index: self.index }
+    }
+
     /// Gets the number of children of the node.
     pub fn num_children(&self) -> usize {
         self.num_children
@@ -872,15 +4540,19 @@ impl<T> Deref for NodeProxySimple<'_, T> {
 
 impl<'a, T> VecTreePoDfsIter<IterData<'a, T>> {
     fn new(tree: &'a VecTree<T>, top: Option<usize>) -> Self {
-        VecTreePoDfsIter {
-            stack: Vec::new(),
-            depth: 0,
-            next: top.map(VisitNode::Down),
-            data: IterData {
-                tree_nodes_ptr: tree.nodes.as_ptr(),
-                tree_size: tree.nodes.len(),
-                _marker: PhantomData
-            },
+        VecTreePoDfsIter::with_data(tree.live_top(top), IterData::new(tree))
+    }
+}
+
+impl<'a, T> IterData<'a, T> {
+    fn new(tree: &'a VecTree<T>) -> Self {
+        tree.debug_validate();
+        IterData {
+            tree_nodes_ptr: tree.nodes.as_ptr(),
+            tree_size: tree.nodes.len(),
+            tree_id: tree.id,
+            descendants_ptr: tree.descendants_ptr(),
+            _marker: PhantomData
         }
     }
 }
@@ -890,9 +4562,23 @@ impl<'a, T> VecTreePoDfsIter<IterData<'a, T>> {
 pub struct IterData<'a, T> {
     tree_nodes_ptr: *const Node<T>,
     tree_size: usize,
+    tree_id: u64,
+    descendants_ptr: *const usize,
     _marker: PhantomData<&'a T>
 }
 
+impl<T> Clone for IterData<'_, T> {
+    fn clone(&self) -> Self {
+        IterData {
+            tree_nodes_ptr: self.tree_nodes_ptr,
+            tree_size: self.tree_size,
+            tree_id: self.tree_id,
+            descendants_ptr: self.descendants_ptr,
+            _marker: PhantomData
+        }
+    }
+}
+
 impl<'a, T> TreeDataIter for IterData<'a, T> {
     type TProxy = NodeProxy<'a, T>;
 
@@ -915,6 +4601,8 @@ impl<'a, T> TreeDataIter for IterData<'a, T> {
             data: unsafe { NonNull::new_unchecked((*self.tree_nodes_ptr.add(index)).data.get()) },
             tree_node_ptr: self.tree_nodes_ptr,
             tree_size: self.tree_size,
+            tree_id: self.tree_id,
+            descendants_ptr: self.descendants_ptr,
             _marker: PhantomData
         }
     }
@@ -928,10 +4616,17 @@ pub struct NodeProxy<'a, T> {
     data: NonNull<T>,
     tree_node_ptr: *const Node<T>,
     tree_size: usize,
+    tree_id: u64,
+    descendants_ptr: *const usize,
     _marker: PhantomData<&'a T>
 }
 
 impl<'a: 'i, 'i, T> NodeProxy<'a, T> {
+    /// Returns the index of the node, tagged with the identity of its tree (see [TaggedNodeId]).
+    pub fn tagged_id(&self) -> TaggedNodeId {
+        TaggedNodeId { tree_id: self.tree_id, index: self.index }
+    }
+
     /// Gets the number of children of the node.
     pub fn num_children(&self) -> usize {
         // SAFETY: `self.index` has been verified when the proxy was created.
@@ -939,6 +4634,25 @@ impl<'a: 'i, 'i, T> NodeProxy<'a, T> {
         children.len()
     }
 
+    /// Returns the number of descendants of the node, in O(1), if they have been computed by
+    /// [`VecTree::compute_descendants()`] before creating the iterator, or `None` otherwise.
+    pub fn num_descendants(&self) -> Option<usize> {
+        if self.descendants_ptr.is_null() {
+            None
+        } else {
+            // SAFETY: The cache has one entry per node and can't be invalidated while the tree is borrowed
+            //         by the iterator, and `self.index` has been verified when the proxy was created.
+            Some(unsafe { *self.descendants_ptr.add(self.index) })
+        }
+    }
+
+    /// Returns the indices of the node's children.
+    pub fn children_indices(&self) -> &[usize] {
+        // SAFETY: - `self.index` has been verified when the proxy was created.
+        //         - The children of the nodes can't be modified while the tree is borrowed by the iterator.
+        unsafe { &(*self.tree_node_ptr.add(self.index)).children }
+    }
+
     /// Iterates over the node's children with a proxy to access their children.
     pub fn iter_children(&self) -> impl DoubleEndedIterator<Item=NodeProxy<'_, T>> {
         // SAFETY: - `self.index` has been verified when the proxy was created.
@@ -953,6 +4667,8 @@ impl<'a: 'i, 'i, T> NodeProxy<'a, T> {
                 data: unsafe { NonNull::new_unchecked((*self.tree_node_ptr.add(index)).data.get()) },
                 tree_node_ptr: self.tree_node_ptr,
                 tree_size: self.tree_size,
+                tree_id: self.tree_id,
+                descendants_ptr: self.descendants_ptr,
                 _marker: PhantomData,
             }
         })
@@ -968,16 +4684,22 @@ impl<'a: 'i, 'i, T> NodeProxy<'a, T> {
 
     /// Iterates the subtree under the node.
     pub fn iter_depth_simple(&'a self) -> VecTreePoDfsIter<IterData<'i, T>> {
-        VecTreePoDfsIter {
-            stack: Vec::new(),
-            depth: 0,
-            next: Some(VisitNode::Down(self.index)),
-            data: IterData {
-                tree_nodes_ptr: self.tree_node_ptr,
-                tree_size: self.tree_size,
-                _marker: PhantomData
-            },
-        }
+        VecTreePoDfsIter::with_data(Some(self.index), IterData {
+            tree_nodes_ptr: self.tree_node_ptr,
+            tree_size: self.tree_size,
+            tree_id: self.tree_id,
+            descendants_ptr: self.descendants_ptr,
+            _marker: PhantomData
+        })
+    }
+
+    /// Walks the subtree under the node in post-order, depth-first, until `f` returns
+    /// [`ControlFlow::Break`]. The break value is returned.
+    pub fn try_walk<B, F>(&'a self, f: F) -> ControlFlow<B>
+    where
+        F: FnMut(NodeProxy<'_, T>) -> ControlFlow<B>
+    {
+        self.iter_depth_simple().try_for_each(f)
     }
 }
 
@@ -995,21 +4717,65 @@ impl<T> Deref for NodeProxy<'_, T> {
 // ---------------------------------------------------------------------------------------------
 // Mutable iterator
 
+/// Marks the node `index` as modified if the tree tracks the modifications (see [`VecTree::set_dirty_tracking()`]).
+///
+/// # Safety
+///
+/// `dirty_ptr` must be null or point to the dirty flags of the tree, which must include `index`.
+unsafe fn mark_dirty(dirty_ptr: *const Cell<bool>, index: usize) {
+    if !dirty_ptr.is_null() {
+        (*dirty_ptr.add(index)).set(true);
+    }
+}
+
 impl<'a, T> VecTreePoDfsIter<IterDataSimpleMut<'a, T>> {
     fn new(tree: &'a mut VecTree<T>, top: Option<usize>) -> Self {
-        VecTreePoDfsIter {
-            stack: Vec::new(),
-            depth: 0,
-            next: top.map(VisitNode::Down),
-            data: IterDataSimpleMut { tree },
+        let top = tree.live_top(top);
+        VecTreePoDfsIter::with_data(top, IterDataSimpleMut::new(tree))
+    }
+}
+
+impl<'a, T> IterDataSimpleMut<'a, T> {
+    fn new(tree: &'a mut VecTree<T>) -> Self {
+        tree.debug_validate();
+        IterDataSimpleMut {
+            tree_nodes_ptr: tree.nodes.as_mut_ptr(),
+            tree_size: tree.nodes.len(),
+            tree_id: tree.id,
+            dirty_ptr: tree.dirty_ptr(),
+            _marker: PhantomData
         }
     }
 }
 
+impl<'a, T> VecTreePoDfsIter<IterDataSimpleMut<'a, T>> {
+    /// Converts the iterator into an iterator of `(index, &mut item)` tuples.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root(1);
+    /// tree.add_iter(Some(root), [2, 3]);
+    /// for (index, value) in tree.iter_depth_simple_mut().with_indices() {
+    ///     *value *= 10 + index as i32;
+    /// }
+    /// assert_eq!(tree.values()[2], 36);
+    /// ```
+    pub fn with_indices(self) -> impl Iterator<Item = (usize, &'a mut T)> {
+        self.map(|inode| (inode.index, inode.into_mut()))
+    }
+}
+
 /// A structure used by simple [VecTree] iterators that give mutable access to each node
 /// but no access to its children.
 pub struct IterDataSimpleMut<'a, T> {
-    tree: &'a mut VecTree<T>,
+    tree_nodes_ptr: *mut Node<T>,
+    tree_size: usize,
+    tree_id: u64,
+    dirty_ptr: *const Cell<bool>,
+    _marker: PhantomData<&'a mut T>     // must be invariant for T
 }
 
 impl<'a, T> TreeDataIter for IterDataSimpleMut<'a, T> {
@@ -1017,19 +4783,21 @@ impl<'a, T> TreeDataIter for IterDataSimpleMut<'a, T> {
 
     fn get_children(&self, index: usize) -> &[usize] {
         // SAFETY: We manually check `index`.
-        assert!(index < self.tree.len(), "node index {index} doesn't exist");
-        unsafe { &(*self.tree.nodes.as_ptr().add(index)).children }
+        assert!(index < self.tree_size, "node index {index} doesn't exist");
+        unsafe { &(*self.tree_nodes_ptr.add(index)).children }
     }
 
     fn create_proxy(&self, index: usize, depth: u32) -> Self::TProxy {
         // SAFETY: - We manually check `index`, so the data reference can't be null.
         //         - The borrow returned by this method has the same lifetime as self, so no
         //           mutable borrow is possible while it's alive.
-        assert!(index < self.tree.len(), "node index {index} doesn't exist");
+        assert!(index < self.tree_size, "node index {index} doesn't exist");
         NodeProxySimpleMut {
             index,
             depth,
-            data: unsafe { NonNull::new_unchecked((*self.tree.nodes.as_ptr().add(index)).data.get()) },
+            tree_id: self.tree_id,
+            dirty_ptr: self.dirty_ptr,
+            data: unsafe { NonNull::new_unchecked((*self.tree_nodes_ptr.add(index)).data.get()) },
             _marker: PhantomData
         }
     }
@@ -1040,10 +4808,31 @@ impl<'a, T> TreeDataIter for IterDataSimpleMut<'a, T> {
 pub struct NodeProxySimpleMut<'a, T> {
     pub index: usize,
     pub depth: u32,
+    tree_id: u64,
+    dirty_ptr: *const Cell<bool>,
     data: NonNull<T>,
     _marker: PhantomData<&'a mut T>     // must be invariant for T
 }
 
+impl<'a, T> NodeProxySimpleMut<'a, T> {
+    /// Converts the proxy into a mutable reference to the node's item.
+    pub fn into_mut(self) -> &'a mut T {
+        // SAFETY: - The data lives as long as 'a.
+        //         - The proxy is the only access to the node's item for 'a, and it's consumed.
+        //         - The dirty flags, if any, have been sized to the tree when the iterator was created.
+        let mut data = self.data;
+        unsafe {
+            mark_dirty(self.dirty_ptr, self.index);
+            data.as_mut()
+        }
+    }
+
+    /// Returns the index of the node, tagged with the identity of its tree (see [TaggedNodeId]).
+    pub fn tagged_id(&self) -> TaggedNodeId {
+        TaggedNodeId { tree_id: self.tree_id, index: self.index }
+    }
+}
+
 impl<T> Deref for NodeProxySimpleMut<'_, T> {
     type Target = T;
 
@@ -1060,24 +4849,117 @@ impl<T> DerefMut for NodeProxySimpleMut<'_, T> {
         // SAFETY: - The data lives as long as the proxy.
         //         - The borrow returned by this method has the same lifetime as self, so no
         //           immutable borrow is possible while it's alive.
-        unsafe { self.data.as_mut() }
+        //         - The dirty flags, if any, have been sized to the tree when the iterator was created.
+        unsafe {
+            mark_dirty(self.dirty_ptr, self.index);
+            self.data.as_mut()
+        }
     }
 }
 
 // -- with children
 
+/// Keeps track of the mutable proxies alive, which must be dropped before immutable references
+/// on their nodes can be given.
+///
+/// With the `debug-borrows` feature, the index of the node and the backtrace of the creation of each
+/// proxy are recorded, and they're added to the panic messages about pending mutable references.
+#[derive(Debug, Default)]
+struct BorrowTracker {
+    count: Cell<u32>,
+    #[cfg(feature = "debug-borrows")]
+    live: Mutex<Vec<(usize, Backtrace)>>     // read through `&VecTree`, so it must be thread-safe
+}
+
+impl BorrowTracker {
+    /// Returns the number of mutable proxies alive.
+    fn get(&self) -> u32 {
+        self.count.get()
+    }
+
+    /// Records the creation of a mutable proxy on the node `index`.
+    fn acquire(&self, index: usize) {
+        self.count.set(self.count.get() + 1);
+        #[cfg(feature = "debug-borrows")]
+        self.live.lock().unwrap().push((index, Backtrace::force_capture()));
+        #[cfg(not(feature = "debug-borrows"))]
+        let _ = index;
+    }
+
+    /// Records the drop of a mutable proxy on the node `index`.
+    fn release(&self, index: usize) {
+        self.count.set(self.count.get() - 1);
+        #[cfg(feature = "debug-borrows")]
+        {
+            let mut live = self.live.lock().unwrap();
+            if let Some(pos) = live.iter().rposition(|(i, _)| *i == index) {
+                let _ = live.remove(pos);
+            }
+        }
+        #[cfg(not(feature = "debug-borrows"))]
+        let _ = index;
+    }
+
+    /// Describes the mutable proxies alive, to complete a panic message. The description is empty
+    /// without the `debug-borrows` feature.
+    fn report(&self) -> String {
+        #[cfg(feature = "debug-borrows")]
+        {
+            self.live.lock().unwrap().iter()
+                .map(|(index, backtrace)| format!("\n- mutable proxy on node {index}, created at:\n{backtrace}"))
+                .collect()
+        }
+        #[cfg(not(feature = "debug-borrows"))]
+        String::new()
+    }
+
+    /// Returns the index of the node and the creation backtrace of each mutable proxy alive.
+    #[cfg(feature = "debug-borrows")]
+    fn live(&self) -> Vec<(usize, String)> {
+        self.live.lock().unwrap().iter().map(|(index, backtrace)| (*index, backtrace.to_string())).collect()
+    }
+}
+
+impl<T> VecTree<T> {
+    /// Returns the number of [NodeProxyMut] proxies created by the full-fledged mutable iterators that are
+    /// still alive.
+    ///
+    /// The proxies borrow the tree, so this number is normally 0 when the tree is accessible; it isn't 0
+    /// if a proxy has been leaked, for example with [`std::mem::forget()`]. [`NodeProxyMut::active_mut_proxies()`]
+    /// gives the same number during the iteration.
+    pub fn active_mut_proxies(&self) -> u32 {
+        self.borrows.get()
+    }
+
+    /// Returns the index of the node and the creation backtrace of each [NodeProxyMut] proxy still alive,
+    /// in their order of creation.
+    ///
+    /// This method is only available with the `debug-borrows` feature, which records the proxies and adds
+    /// this information to the "pending mutable reference(s)" panic messages.
+    #[cfg(feature = "debug-borrows")]
+    pub fn live_mut_proxies(&self) -> Vec<(usize, String)> {
+        self.borrows.live()
+    }
+}
+
 impl<'a, T> VecTreePoDfsIter<IterDataMut<'a, T>> {
     fn new(tree: &'a mut VecTree<T>, top: Option<usize>) -> Self {
-        VecTreePoDfsIter {
-            stack: Vec::new(),
-            depth: 0,
-            next: top.map(VisitNode::Down),
-            data: IterDataMut {
-                tree_nodes_ptr: tree.nodes.as_mut_ptr(),
-                tree_size: tree.nodes.len(),
-                borrows: &tree.borrows,
-                _marker: PhantomData
-            },
+        let top = tree.live_top(top);
+        VecTreePoDfsIter::with_data(top, IterDataMut::new(tree))
+    }
+}
+
+impl<'a, T> IterDataMut<'a, T> {
+    fn new(tree: &'a mut VecTree<T>) -> Self {
+        tree.debug_validate();
+        IterDataMut {
+            tree_nodes_ptr: tree.nodes.as_mut_ptr(),
+            tree_size: tree.nodes.len(),
+            tree_id: tree.id,
+            dirty_ptr: tree.dirty_ptr(),
+            descendants_ptr: tree.descendants_ptr(),
+            borrows: &tree.borrows,
+            _marker: PhantomData
         }
     }
 }
@@ -1087,7 +4969,10 @@ impl<'a, T> VecTreePoDfsIter<IterDataMut<'a, T>> {
 pub struct IterDataMut<'a, T> {
     tree_nodes_ptr: *mut Node<T>,
     tree_size: usize,
-    borrows: &'a Cell<u32>,
+    tree_id: u64,
+    dirty_ptr: *const Cell<bool>,
+    descendants_ptr: *const usize,
+    borrows: &'a BorrowTracker,
     _marker: PhantomData<&'a mut T>     // must be invariant for T
 }
 
@@ -1106,8 +4991,7 @@ impl<'a, T> TreeDataIter for IterDataMut<'a, T> {
         // IterDataMut can spawn immutable iterators, so we keep track of how many mutable proxies (which
         // work as smart pointers) are alive. If more than one is alive, it is forbidden to spawn an
         // immutable iterator, since it would violate the aliasing rule.
-        let c = self.borrows.get() + 1;
-        self.borrows.set(c);
+        self.borrows.acquire(index);
         // SAFETY: - We manually check `index`, so the data reference can't be null.
         //         - The borrow returned by this method has the same lifetime as self, so no
         //           mutable borrow is possible while it's alive.
@@ -1118,7 +5002,11 @@ impl<'a, T> TreeDataIter for IterDataMut<'a, T> {
             data: unsafe { NonNull::new_unchecked((*self.tree_nodes_ptr.add(index)).data.get()) },
             tree_node_ptr: self.tree_nodes_ptr,
             tree_size: self.tree_size,
+            tree_id: self.tree_id,
+            dirty_ptr: self.dirty_ptr,
+            descendants_ptr: self.descendants_ptr,
             borrows: self.borrows,
+            nested: 0,
             _marker: PhantomData
         }
     }
@@ -1132,11 +5020,34 @@ pub struct NodeProxyMut<'a, T> {
     data: NonNull<T>,
     tree_node_ptr: *const Node<T>,
     tree_size: usize,
-    borrows: &'a Cell<u32>,
+    tree_id: u64,
+    dirty_ptr: *const Cell<bool>,
+    descendants_ptr: *const usize,
+    borrows: &'a BorrowTracker,
+    nested: u32,                        // number of parent proxies mutably borrowed by this one
     _marker: PhantomData<&'a mut T>     // must be invariant for T
 }
 
 impl<'a: 'i, 'i, T> NodeProxyMut<'a, T> {
+    /// Returns the number of [NodeProxyMut] proxies of the iteration that are alive, including this one.
+    ///
+    /// [`NodeProxyMut::iter_children()`] panics if any other proxy is alive.
+    pub fn active_mut_proxies(&self) -> u32 {
+        self.borrows.get()
+    }
+
+    /// Returns the index of the node and the creation backtrace of each [NodeProxyMut] proxy of the
+    /// iteration that is alive, including this one (see `VecTree::live_mut_proxies()`).
+    #[cfg(feature = "debug-borrows")]
+    pub fn live_mut_proxies(&self) -> Vec<(usize, String)> {
+        self.borrows.live()
+    }
+
+    /// Returns the index of the node, tagged with the identity of its tree (see [TaggedNodeId]).
+    pub fn tagged_id(&self) -> TaggedNodeId {
+        TaggedNodeId { tree_id: self.tree_id, index: self.index }
+    }
+
     /// Gets the number of children of the node.
     pub fn num_children(&self) -> usize {
         // SAFETY: `self.index` has been verified when the proxy was created.
@@ -1144,6 +5055,25 @@ impl<'a: 'i, 'i, T> NodeProxyMut<'a, T> {
         children.len()
     }
 
+    /// Returns the number of descendants of the node, in O(1), if they have been computed by
+    /// [`VecTree::compute_descendants()`] before creating the iterator, or `None` otherwise.
+    pub fn num_descendants(&self) -> Option<usize> {
+        if self.descendants_ptr.is_null() {
+            None
+        } else {
+            // SAFETY: The cache has one entry per node and can't be invalidated while the tree is borrowed
+            //         by the iterator, and `self.index` has been verified when the proxy was created.
+            Some(unsafe { *self.descendants_ptr.add(self.index) })
+        }
+    }
+
+    /// Returns the indices of the node's children.
+    pub fn children_indices(&self) -> &[usize] {
+        // SAFETY: - `self.index` has been verified when the proxy was created.
+        //         - The children of the nodes can't be modified while the tree is borrowed by the iterator.
+        unsafe { &(*self.tree_node_ptr.add(self.index)).children }
+    }
+
     /// Iterates over the node's children with a proxy to access their children (immutably).
     pub fn iter_children(&self) -> impl DoubleEndedIterator<Item = NodeProxy<'_, T>> {
         // SAFETY: - We manually check that no mutable borrow is alive before handing a reference to the content of `UnsafeCell<T> data`.
@@ -1151,7 +5081,8 @@ impl<'a: 'i, 'i, T> NodeProxyMut<'a, T> {
         //         - `self.index` has been verified when the proxy was created.
         //         - The children indices have been verified when they were added.
         let c = self.borrows.get();
-        assert!(c <= 1, "{} extra pending mutable reference(s) on children when requesting immutable references on them", c - 1);
+        assert!(c <= 1 + self.nested, "{} extra pending mutable reference(s) on children when requesting immutable references on them{}",
+                c - 1 - self.nested, self.borrows.report());
         let children = unsafe { &(*self.tree_node_ptr.add(self.index)).children };
         children.iter().map(|&index| {
             assert!(index < self.tree_size, "node index {index} doesn't exist");
@@ -1161,11 +5092,114 @@ impl<'a: 'i, 'i, T> NodeProxyMut<'a, T> {
                 data: unsafe { NonNull::new_unchecked((*self.tree_node_ptr.add(index)).data.get()) },
                 tree_node_ptr: self.tree_node_ptr,
                 tree_size: self.tree_size,
+                tree_id: self.tree_id,
+                descendants_ptr: self.descendants_ptr,
                 _marker: PhantomData,
             }
         })
     }
 
+    /// Returns a mutable proxy on the `n`-th child of the node, or `None` if the node has no such child.
+    ///
+    /// The child proxy borrows this proxy mutably, so the node can't be accessed until the child proxy
+    /// is dropped, and it gives the same access to the child and its children as the proxy of the iterator.
+    ///
+    /// The method panics if other proxies of the iteration are alive (see [`NodeProxyMut::active_mut_proxies()`]),
+    /// since they could be on the child node; for example, if the proxies of the children were collected in
+    /// a vector before reaching the node.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root(1);
+    /// let a = tree.add(Some(root), 10);
+    /// tree.add_iter(Some(a), [100, 200]);
+    /// for mut inode in tree.iter_depth_mut() {
+    ///     // the second child's value is moved into the node
+    ///     let value = inode.child_mut(1).map(|mut child| std::mem::take(&mut *child));
+    ///     *inode += value.unwrap_or(0);
+    /// }
+    /// assert_eq!(tree.iter_depth_simple().map(|inode| *inode).collect::<Vec<_>>(), [100, 0, 210, 1]);
+    /// ```
+    pub fn child_mut(&mut self, n: usize) -> Option<NodeProxyMut<'_, T>> {
+        self.check_exclusive_children();
+        let index = *self.children_indices().get(n)?;
+        // SAFETY: The other proxies of the iteration, except the parents of this one, have been dropped,
+        //         and this proxy is mutably borrowed while the child proxy is alive.
+        Some(unsafe { self.child_proxy(index) })
+    }
+
+    /// Iterates over the node's children with a mutable proxy on each of them, which gives the same access
+    /// to the child and its children as the proxy of the iterator.
+    ///
+    /// The child proxies borrow this proxy mutably, so the node can't be accessed until they're dropped.
+    /// Since the child proxies can be alive at the same time, [`NodeProxyMut::iter_children()`] panics if it's
+    /// called on one of them while the proxy of another child is still alive.
+    ///
+    /// The method panics if other proxies of the iteration are alive (see [`NodeProxyMut::child_mut()`]), or if
+    /// the same child appears several times in the children of the node (see [`VecTree::dedup_children()`]).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root(1);
+    /// let a = tree.add(Some(root), 10);
+    /// tree.add_iter(Some(a), [100, 200]);
+    /// tree.add(Some(root), 20);
+    /// // moves the values up to the root
+    /// for mut inode in tree.iter_depth_mut() {
+    ///     let total = inode.iter_children_mut().map(|mut child| std::mem::take(&mut *child)).sum::<i32>();
+    ///     *inode += total;
+    /// }
+    /// assert_eq!(tree.iter_depth_simple().map(|inode| *inode).collect::<Vec<_>>(), [0, 0, 0, 0, 331]);
+    /// ```
+    pub fn iter_children_mut(&mut self) -> impl DoubleEndedIterator<Item = NodeProxyMut<'_, T>> {
+        self.check_exclusive_children();
+        let this = &*self;
+        let children = this.children_indices();
+        let mut unique = HashSet::new();
+        assert!(children.iter().all(|&index| unique.insert(index)), "node {} has duplicate children", this.index);
+        // SAFETY: The other proxies of the iteration, except the parents of this one, have been dropped,
+        //         this proxy is mutably borrowed while the child proxies are alive, and each child is distinct.
+        children.iter().map(move |&index| unsafe { this.child_proxy(index) })
+    }
+
+    /// Checks that the only mutable proxies alive are this one and its parents, before creating proxies
+    /// on its children.
+    fn check_exclusive_children(&self) {
+        let c = self.borrows.get();
+        assert!(c <= 1 + self.nested, "{} extra pending mutable reference(s) when requesting mutable references on children{}",
+                c - 1 - self.nested, self.borrows.report());
+    }
+
+    /// Creates a mutable proxy on the child node `index`.
+    ///
+    /// # Safety
+    ///
+    /// No other reference on the child node may be alive while the proxy is alive, and this proxy must be
+    /// borrowed mutably for the lifetime of the child proxy.
+    unsafe fn child_proxy(&self, index: usize) -> NodeProxyMut<'_, T> {
+        assert!(index < self.tree_size, "node index {index} doesn't exist");
+        self.borrows.acquire(index);
+        NodeProxyMut {
+            index,
+            depth: self.depth + 1,
+            data: NonNull::new_unchecked((*self.tree_node_ptr.add(index)).data.get()),
+            tree_node_ptr: self.tree_node_ptr,
+            tree_size: self.tree_size,
+            tree_id: self.tree_id,
+            dirty_ptr: self.dirty_ptr,
+            descendants_ptr: self.descendants_ptr,
+            borrows: self.borrows,
+            nested: self.nested + 1,
+            _marker: PhantomData
+        }
+    }
+
     /// Iterates over the node's children (immutably).
     pub fn iter_children_simple(&self) -> impl DoubleEndedIterator<Item=&T> {
         // SAFETY: - `self.index` has been verified when the proxy was created.
@@ -1176,16 +5210,22 @@ impl<'a: 'i, 'i, T> NodeProxyMut<'a, T> {
 
     /// Iterates the subtree under the node (immutably).
     pub fn iter_depth_simple(&'a self) -> VecTreePoDfsIter<IterData<'i, T>> {
-        VecTreePoDfsIter {
-            stack: Vec::new(),
-            depth: 0,
-            next: Some(VisitNode::Down(self.index)),
-            data: IterData {
-                tree_nodes_ptr: self.tree_node_ptr,
-                tree_size: self.tree_size,
-                _marker: PhantomData
-            },
-        }
+        VecTreePoDfsIter::with_data(Some(self.index), IterData {
+            tree_nodes_ptr: self.tree_node_ptr,
+            tree_size: self.tree_size,
+            tree_id: self.tree_id,
+            descendants_ptr: self.descendants_ptr,
+            _marker: PhantomData
+        })
+    }
+
+    /// Walks the subtree under the node (immutably) in post-order, depth-first, until `f` returns
+    /// [`ControlFlow::Break`]. The break value is returned.
+    pub fn try_walk<B, F>(&'a self, f: F) -> ControlFlow<B>
+    where
+        F: FnMut(NodeProxy<'_, T>) -> ControlFlow<B>
+    {
+        self.iter_depth_simple().try_for_each(f)
     }
 }
 
@@ -1205,14 +5245,126 @@ impl<T> DerefMut for NodeProxyMut<'_, T> {
         // SAFETY: - The data lives as long as the proxy.
         //         - The borrow returned by this method has the same lifetime as self, so no
         //           immutable borrow is possible while it's alive.
-        unsafe { self.data.as_mut() }
+        //         - The dirty flags, if any, have been sized to the tree when the iterator was created.
+        unsafe {
+            mark_dirty(self.dirty_ptr, self.index);
+            self.data.as_mut()
+        }
     }
 }
 
 impl<T> Drop for NodeProxyMut<'_, T> {
     fn drop(&mut self) {
-        let c = self.borrows.get() - 1;
-        self.borrows.set(c);
+        self.borrows.release(self.index);
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+// Mutable subtrees
+
+impl<T> VecTree<T> {
+    /// Splits the tree into two mutable handles on the disjoint subtrees under the nodes of
+    /// indices `a` and `b`. Each handle provides the same mutable iterators as the tree, limited
+    /// to its subtree, and both handles can be used at the same time.
+    ///
+    /// The method panics if one of the nodes doesn't exist, or if the subtrees share any node
+    /// (for example, if one node is under the other).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root(0);
+    /// let a = tree.addc_iter(Some(root), 10, [1, 2]);
+    /// let b = tree.addc_iter(Some(root), 20, [3, 4]);
+    /// let (mut sub_a, mut sub_b) = tree.split_subtrees_mut(a, b);
+    /// for (mut node_a, node_b) in sub_a.iter_depth_simple_mut().zip(sub_b.iter_depth_simple_mut()) {
+    ///     *node_a += *node_b;
+    /// }
+    /// assert_eq!(tree.iter_depth_simple().map(|n| *n).collect::<Vec<_>>(), [4, 6, 30, 3, 4, 20, 0]);
+    /// ```
+    pub fn split_subtrees_mut(&mut self, a: usize, b: usize) -> (SubtreeMut<'_, T>, SubtreeMut<'_, T>) {
+        assert!(a < self.len(), "node index {a} doesn't exist");
+        assert!(b < self.len(), "node index {b} doesn't exist");
+        let mut in_a = vec![false; self.len()];
+        for inode in self.iter_depth_simple_at(a) {
+            in_a[inode.index] = true;
+        }
+        assert!(!self.iter_depth_simple_at(b).any(|inode| in_a[inode.index]), "subtrees {a} and {b} are not disjoint");
+        let descendants_ptr = self.descendants_ptr();
+        let dirty_ptr = self.dirty_ptr();
+        let tree_nodes_ptr = self.nodes.as_mut_ptr();
+        let tree_size = self.nodes.len();
+        (
+            SubtreeMut { top: a, tree_nodes_ptr, tree_size, tree_id: self.id, descendants_ptr, dirty_ptr, borrows: BorrowTracker::default(), _marker: PhantomData },
+            SubtreeMut { top: b, tree_nodes_ptr, tree_size, tree_id: self.id, descendants_ptr, dirty_ptr, borrows: BorrowTracker::default(), _marker: PhantomData },
+        )
+    }
+}
+
+/// A handle giving mutable access to a subtree of a [VecTree], created by [`VecTree::split_subtrees_mut()`].
+///
+/// The handle has its own count of mutable proxies, since the nodes of its subtree can't be reached
+/// from the other handle.
+pub struct SubtreeMut<'a, T> {
+    top: usize,
+    tree_nodes_ptr: *mut Node<T>,
+    tree_size: usize,
+    tree_id: u64,
+    dirty_ptr: *const Cell<bool>,
+    descendants_ptr: *const usize,
+    borrows: BorrowTracker,
+    _marker: PhantomData<&'a mut T>     // must be invariant for T
+}
+
+impl<T> SubtreeMut<'_, T> {
+    /// Returns the index of the top node of the subtree.
+    pub fn top(&self) -> usize {
+        self.top
+    }
+
+    /// Post-order, depth-first search iteration over all the nodes of the subtree.
+    ///
+    /// The iterator returns a proxy for each node, which gives an immutable reference to that node
+    /// and its children (see [`VecTree::iter_depth()`]).
+    pub fn iter_depth(&self) -> VecTreePoDfsIter<IterData<'_, T>> {
+        VecTreePoDfsIter::with_data(Some(self.top), IterData {
+            tree_nodes_ptr: self.tree_nodes_ptr,
+            tree_size: self.tree_size,
+            tree_id: self.tree_id,
+            descendants_ptr: self.descendants_ptr,
+            _marker: PhantomData
+        })
+    }
+
+    /// Post-order, depth-first search iteration over all the nodes of the subtree.
+    ///
+    /// The iterator returns a proxy for each node, which gives a mutable reference only to that node.
+    pub fn iter_depth_simple_mut(&mut self) -> VecTreePoDfsIter<IterDataSimpleMut<'_, T>> {
+        VecTreePoDfsIter::with_data(Some(self.top), IterDataSimpleMut {
+            tree_nodes_ptr: self.tree_nodes_ptr,
+            tree_size: self.tree_size,
+            tree_id: self.tree_id,
+            dirty_ptr: self.dirty_ptr,
+            _marker: PhantomData
+        })
+    }
+
+    /// Post-order, depth-first search iteration over all the nodes of the subtree.
+    ///
+    /// The iterator returns a proxy for each node, which gives a mutable reference to that node
+    /// and an immutable reference its children (see [`VecTree::iter_depth_mut()`]).
+    pub fn iter_depth_mut(&mut self) -> VecTreePoDfsIter<IterDataMut<'_, T>> {
+        VecTreePoDfsIter::with_data(Some(self.top), IterDataMut {
+            tree_nodes_ptr: self.tree_nodes_ptr,
+            tree_size: self.tree_size,
+            tree_id: self.tree_id,
+            dirty_ptr: self.dirty_ptr,
+            descendants_ptr: self.descendants_ptr,
+            borrows: &self.borrows,
+            _marker: PhantomData
+        })
     }
 }
 
@@ -1237,4 +5389,30 @@ impl<'a, T> IntoIterator for &'a mut VecTree<T> {
     }
 }
 
+/// Consumes the tree and iterates over its items in a post-order, depth-first search starting at the
+/// root node, like [`VecTree::drain_depth()`]. The items of the nodes that can't be reached from the
+/// root are dropped.
+///
+/// # Example
+///
+/// ```
+/// use vectree::VecTree;
+/// let mut tree = VecTree::new();
+/// let root = tree.add_root("root".to_string());
+/// tree.add_iter(Some(root), ["a".to_string(), "b".to_string()]);
+/// let mut items = vec![];
+/// for item in tree {
+///     items.push(item);
+/// }
+/// assert_eq!(items, ["a", "b", "root"]);
+/// ```
+impl<T> IntoIterator for VecTree<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        self.take_post_order().into_iter()
+    }
+}
+
 // ---------------------------------------------------------------------------------------------
@@ -0,0 +1,226 @@
+// Copyright 2025 Redglyph
+//
+
+//! Flattened rows, to display a tree in a list view.
+
+use std::iter::FusedIterator;
+use crate::VecTree;
+
+/// A visible row of a tree displayed as a list, given by [`VecTree::flatten_visible()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RowInfo {
+    /// Index of the node.
+    pub index: usize,
+    /// Depth of the node, 0 for the root.
+    pub depth: u32,
+    /// `true` if the node has children, whether it's expanded or not.
+    pub has_children: bool,
+    /// `true` if the node is the last child of its parent, or if it's the root.
+    pub is_last_child: bool,
+}
+
+impl<T> VecTree<T> {
+    /// Returns the visible rows of the tree displayed as a list, in pre-order, from the root: each
+    /// node is followed by its children if `expanded` returns `true` for its index.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add_iter(Some(a), ["a1", "a2"]);
+    /// let b = tree.add(Some(root), "b");
+    /// tree.add(Some(b), "b1");
+    /// let rows = tree.flatten_visible(|index| index != b);
+    /// let text = rows.iter()
+    ///     .map(|row| format!("{}{}{}", "  ".repeat(row.depth as usize), tree.get(row.index), if row.has_children { ":" } else { "" }))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(text, ["root:", "  a:", "    a1", "    a2", "  b:"]);
+    /// assert!(rows[3].is_last_child);
+    /// ```
+    pub fn flatten_visible<F: Fn(usize) -> bool>(&self, expanded: F) -> Vec<RowInfo> {
+        self.iter_rows_range(expanded, 0, usize::MAX).collect()
+    }
+
+    /// Iterates over the visible rows `start..start + len` of the tree displayed as a list, like the
+    /// corresponding rows given by [`VecTree::flatten_visible()`], without creating the whole list.
+    ///
+    /// The rows before `start` must still be visited, but the collapsed subtrees are skipped, and
+    /// the rows after the window aren't visited. The memory is proportional to the depth of the tree.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root(0);
+    /// for i in 1..=1000 {
+    ///     tree.add(Some(root), i);
+    /// }
+    /// let window = tree.iter_rows_range(|_| true, 500, 3).map(|row| *tree.get(row.index)).collect::<Vec<_>>();
+    /// assert_eq!(window, [500, 501, 502]);
+    /// ```
+    pub fn iter_rows_range<F: Fn(usize) -> bool>(&self, expanded: F, start: usize, len: usize) -> RowIter<'_, T, F> {
+        let mut iter = RowIter {
+            tree: self,
+            stack: self.get_root().map(|root| (root, 0, true)).into_iter().collect(),
+            expanded,
+            remaining: start.saturating_add(len),
+        };
+        if start > 0 {
+            iter.nth(start - 1);
+        }
+        iter
+    }
+}
+
+/// An iterator over the visible rows of a tree displayed as a list, created by [`VecTree::iter_rows_range()`].
+pub struct RowIter<'a, T, F> {
+    tree: &'a VecTree<T>,
+    // (index, depth, is_last_child)
+    stack: Vec<(usize, u32, bool)>,
+    expanded: F,
+    remaining: usize,
+}
+
+impl<T, F: Fn(usize) -> bool> Iterator for RowIter<'_, T, F> {
+    type Item = RowInfo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let (index, depth, is_last_child) = self.stack.pop()?;
+        self.remaining -= 1;
+        let children = self.tree.children(index);
+        if (self.expanded)(index) {
+            let last = children.len().wrapping_sub(1);
+            self.stack.extend(children.iter().enumerate().rev().map(|(i, &child)| (child, depth + 1, i == last)));
+        }
+        Some(RowInfo { index, depth, has_children: !children.is_empty(), is_last_child })
+    }
+}
+
+impl<T, F: Fn(usize) -> bool> FusedIterator for RowIter<'_, T, F> {}
+
+// ---------------------------------------------------------------------------------------------
+// Expansion state
+
+impl<T> VecTree<T> {
+    /// Expands or collapses the node `index`. The expansion state of the nodes is kept by the tree
+    /// and used by [`VecTree::visible_rows()`] and [`VecTree::iter_visible_rows()`]. The nodes are
+    /// initially collapsed.
+    ///
+    /// Panics if the index is out of the buffer bounds.
+    pub fn set_expanded(&mut self, index: usize, expanded: bool) {
+        assert!(index < self.len(), "node index {index} doesn't exist");
+        if self.is_expanded(index) != expanded {
+            if self.expanded.len() <= index {
+                self.expanded.resize(self.len(), false);
+            }
+            self.expanded[index] = expanded;
+            self.visible_counts.take();
+        }
+    }
+
+    /// Returns `true` if the node `index` is expanded (see [`VecTree::set_expanded()`]).
+    pub fn is_expanded(&self, index: usize) -> bool {
+        self.expanded.get(index).copied().unwrap_or(false)
+    }
+
+    /// Expands the node `index` if it's collapsed, or collapses it if it's expanded, and returns
+    /// `true` if the node is now expanded (see [`VecTree::set_expanded()`]).
+    pub fn toggle(&mut self, index: usize) -> bool {
+        let expanded = !self.is_expanded(index);
+        self.set_expanded(index, expanded);
+        expanded
+    }
+
+    /// Collapses the node `index` and all the nodes below it (see [`VecTree::set_expanded()`]).
+    pub fn collapse_all_below(&mut self, index: usize) {
+        let subtree = self.iter_depth_simple_at(index).map(|inode| inode.index).collect::<Vec<_>>();
+        for index in subtree {
+            if let Some(expanded) = self.expanded.get_mut(index) {
+                *expanded = false;
+            }
+        }
+        self.visible_counts.take();
+    }
+
+    /// Returns the number of visible rows, with the expansion state of the tree (see [`VecTree::set_expanded()`]).
+    pub fn num_visible_rows(&self) -> usize {
+        self.get_root().map_or(0, |root| self.visible_counts()[root])
+    }
+
+    /// Returns the visible rows of the tree displayed as a list, with the expansion state of the
+    /// tree (see [`VecTree::set_expanded()`] and [`VecTree::flatten_visible()`]).
+    pub fn visible_rows(&self) -> Vec<RowInfo> {
+        self.flatten_visible(|index| self.is_expanded(index))
+    }
+
+    /// Iterates over the visible rows `start..start + len` of the tree displayed as a list, with the
+    /// expansion state of the tree (see [`VecTree::set_expanded()`] and [`VecTree::iter_rows_range()`]).
+    ///
+    /// The number of visible rows under each node is cached until the structure or the expansion
+    /// state of the tree is modified, so the rows before `start` don't need to be visited. Once the
+    /// cache is computed, the iteration is proportional to the size of the window, plus the depth
+    /// and the number of children of the nodes leading to `start`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root".to_string());
+    /// for i in 0..1000 {
+    ///     let child = tree.add(Some(root), format!("{i}"));
+    ///     tree.add_iter(Some(child), [format!("{i}.a"), format!("{i}.b")]);
+    /// }
+    /// tree.set_expanded(root, true);
+    /// tree.toggle(4); // "1"
+    /// assert_eq!(tree.num_visible_rows(), 1003);
+    /// let window = tree.iter_visible_rows(1, 4).map(|row| tree.get(row.index).as_str()).collect::<Vec<_>>();
+    /// assert_eq!(window, ["0", "1", "1.a", "1.b"]);
+    /// ```
+    pub fn iter_visible_rows(&self, start: usize, len: usize) -> impl Iterator<Item = RowInfo> + '_ {
+        let mut stack = Vec::new();
+        if let Some(root) = self.get_root() {
+            let counts = self.visible_counts();
+            if start < counts[root] {
+                // finds the row `start`, keeping the next siblings of its ancestors in the stack
+                let (mut index, mut depth, mut is_last_child, mut offset) = (root, 0, true, start);
+                while offset > 0 {
+                    offset -= 1;
+                    let children = self.children(index);
+                    let last = children.len() - 1;
+                    let mut position = 0;
+                    while offset >= counts[children[position]] {
+                        offset -= counts[children[position]];
+                        position += 1;
+                    }
+                    stack.extend(children.iter().enumerate().skip(position + 1).rev().map(|(i, &child)| (child, depth + 1, i == last)));
+                    (index, depth, is_last_child) = (children[position], depth + 1, position == last);
+                }
+                stack.push((index, depth, is_last_child));
+            }
+        }
+        RowIter { tree: self, stack, expanded: |index| self.is_expanded(index), remaining: len }
+    }
+
+    /// Returns the number of visible rows under each node, including the node, for the nodes under
+    /// the root.
+    fn visible_counts(&self) -> &[usize] {
+        self.visible_counts.get_or_init(|| {
+            let mut counts = vec![0; self.len()];
+            for inode in self.iter_depth_simple() {
+                counts[inode.index] = 1;
+                if self.is_expanded(inode.index) {
+                    counts[inode.index] += self.children(inode.index).iter().map(|&child| counts[child]).sum::<usize>();
+                }
+            }
+            counts
+        })
+    }
+}
@@ -0,0 +1,269 @@
+// Copyright 2025 Redglyph
+//
+
+//! Tree collection without unsafe code.
+
+#![forbid(unsafe_code)]
+
+use std::iter::FusedIterator;
+use std::ops::{Index, IndexMut};
+use crate::{TreeError, VecTree};
+
+/// A vector-based tree collection similar to [`VecTree`], implemented without unsafe code.
+///
+/// The values and the children indices are stored in separate vectors, so the borrow checker can
+/// verify every access. The price is a less flexible traversal: instead of proxies that give access
+/// to the children of the visited nodes, the nodes are visited by index with a [`PostOrder`] cursor,
+/// which doesn't borrow the tree, and [`SafeVecTree::split_mut()`] gives a mutable reference to a
+/// node's value together with immutable references to its children's values.
+///
+/// A `SafeVecTree` can be converted from a [`VecTree`], and to a [`VecTree`] with [`TryFrom`]
+/// after verifying the tree.
+///
+/// # Example
+///
+/// ```
+/// use vectree::SafeVecTree;
+///
+/// let mut tree = SafeVecTree::new();
+/// let root = tree.add_root(0);
+/// let a = tree.add(Some(root), 0);
+/// tree.add_iter(Some(a), [1, 2]);
+/// tree.add(Some(root), 3);
+/// // replaces the value of each parent by the sum of its children's values
+/// let mut cursor = tree.post_order();
+/// while let Some((index, _depth)) = cursor.next(&tree) {
+///     let (value, children) = tree.split_mut(index);
+///     if children.len() > 0 {
+///         *value = children.sum();
+///     }
+/// }
+/// assert_eq!(tree[root], 6);
+/// assert_eq!(tree[a], 3);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SafeVecTree<T> {
+    values: Vec<T>,
+    children: Vec<Vec<usize>>,
+    root: Option<usize>,
+}
+
+impl<T> SafeVecTree<T> {
+    /// Creates a new and empty tree.
+    pub fn new() -> Self {
+        SafeVecTree { values: Vec::new(), children: Vec::new(), root: None }
+    }
+
+    /// Creates a new and empty tree with pre-allocated buffers of the specified initial capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        SafeVecTree { values: Vec::with_capacity(capacity), children: Vec::with_capacity(capacity), root: None }
+    }
+
+    /// Returns the number of nodes in the tree.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if the tree has no node.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the index of the tree root, if it exists.
+    pub fn get_root(&self) -> Option<usize> {
+        self.root
+    }
+
+    /// Adds a node without parent and makes it the root of the tree. Returns its index.
+    pub fn add_root(&mut self, item: T) -> usize {
+        let index = self.add(None, item);
+        self.root = Some(index);
+        index
+    }
+
+    /// Adds a node as last child of `parent_index`, or without parent if `parent_index` is `None`.
+    /// Returns the index of the new node.
+    ///
+    /// The method panics if `parent_index` isn't the index of an existing node.
+    pub fn add(&mut self, parent_index: Option<usize>, item: T) -> usize {
+        let index = self.values.len();
+        if let Some(parent) = parent_index {
+            self.children[parent].push(index);
+        }
+        self.values.push(item);
+        self.children.push(Vec::new());
+        index
+    }
+
+    /// Adds the items as children of `parent_index`, or without parent if `parent_index` is `None`.
+    /// Returns the index of the last node that was added.
+    ///
+    /// The method panics if `parent_index` isn't the index of an existing node or if `items` is empty.
+    pub fn add_iter<I: IntoIterator<Item = T>>(&mut self, parent_index: Option<usize>, items: I) -> usize {
+        items.into_iter().map(|item| self.add(parent_index, item)).last().expect("no item to add")
+    }
+
+    /// Returns a reference to the value of the node `index`.
+    pub fn get(&self, index: usize) -> &T {
+        &self.values[index]
+    }
+
+    /// Returns a mutable reference to the value of the node `index`.
+    pub fn get_mut(&mut self, index: usize) -> &mut T {
+        &mut self.values[index]
+    }
+
+    /// Returns the indices of the children of the node `index`.
+    pub fn children(&self, index: usize) -> &[usize] {
+        &self.children[index]
+    }
+
+    /// Returns a mutable reference to the value of the node `index` and an iterator over the values
+    /// of its children.
+    ///
+    /// The method panics if `index` is one of its own children.
+    pub fn split_mut(&mut self, index: usize) -> (&mut T, Children<'_, T>) {
+        let children = &self.children[index];
+        let (before, rest) = self.values.split_at_mut(index);
+        let (value, after) = rest.split_first_mut().expect("index out of bounds");
+        assert!(!children.contains(&index), "node {index} is its own child");
+        (value, Children { before, after, split: index, indices: children.iter() })
+    }
+
+    /// Returns a cursor that visits the nodes under the root in a post-order, depth-first search.
+    /// The cursor doesn't borrow the tree, which can be modified between two visits.
+    pub fn post_order(&self) -> PostOrder {
+        self.post_order_at_opt(self.root)
+    }
+
+    /// Returns a cursor that visits the nodes under `top` in a post-order, depth-first search
+    /// (see [`SafeVecTree::post_order()`]).
+    pub fn post_order_at(&self, top: usize) -> PostOrder {
+        self.post_order_at_opt(Some(top))
+    }
+
+    fn post_order_at_opt(&self, top: Option<usize>) -> PostOrder {
+        PostOrder { stack: top.map(|top| vec![(top, false)]).unwrap_or_default(), depth: 0 }
+    }
+
+    /// Iterates over the nodes under the root in a post-order, depth-first search, giving the index,
+    /// the depth and a reference to the value of each node.
+    pub fn iter_depth(&self) -> impl FusedIterator<Item = (usize, u32, &T)> {
+        let mut cursor = self.post_order();
+        std::iter::from_fn(move || cursor.next(self).map(|(index, depth)| (index, depth, &self.values[index]))).fuse()
+    }
+
+    /// Calls `f` with the index, the depth, a mutable reference to the value and the values of the
+    /// children of each node under the root, in a post-order, depth-first search.
+    pub fn for_each_mut<F: FnMut(usize, u32, &mut T, Children<'_, T>)>(&mut self, mut f: F) {
+        let mut cursor = self.post_order();
+        while let Some((index, depth)) = cursor.next(self) {
+            let (value, children) = self.split_mut(index);
+            f(index, depth, value, children);
+        }
+    }
+}
+
+impl<T> Default for SafeVecTree<T> {
+    fn default() -> Self {
+        SafeVecTree::new()
+    }
+}
+
+impl<T> Index<usize> for SafeVecTree<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.get(index)
+    }
+}
+
+impl<T> IndexMut<usize> for SafeVecTree<T> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        self.get_mut(index)
+    }
+}
+
+impl<T> From<VecTree<T>> for SafeVecTree<T> {
+    fn from(tree: VecTree<T>) -> Self {
+        let (nodes, root) = tree.into_raw_parts();
+        let (values, children) = nodes.into_iter().unzip();
+        SafeVecTree { values, children, root }
+    }
+}
+
+impl<T> TryFrom<SafeVecTree<T>> for VecTree<T> {
+    type Error = TreeError;
+
+    /// Converts the tree after verifying it (see [`VecTree::try_from_parts()`]).
+    fn try_from(tree: SafeVecTree<T>) -> Result<Self, Self::Error> {
+        VecTree::try_from_parts(tree.values.into_iter().zip(tree.children).collect(), tree.root)
+    }
+}
+
+/// A cursor visiting the nodes of a [`SafeVecTree`] in a post-order, depth-first search, created by
+/// [`SafeVecTree::post_order()`].
+///
+/// If the structure of the tree is modified during the traversal, the cursor visits the children
+/// that a node has when the node is first reached.
+#[derive(Clone, Debug)]
+pub struct PostOrder {
+    /// (index, children pushed)
+    stack: Vec<(usize, bool)>,
+    depth: u32,
+}
+
+impl PostOrder {
+    /// Returns the index and the depth of the next node, or `None` at the end of the traversal.
+    pub fn next<T>(&mut self, tree: &SafeVecTree<T>) -> Option<(usize, u32)> {
+        loop {
+            let (index, visited) = self.stack.pop()?;
+            if visited {
+                self.depth -= 1;
+                return Some((index, self.depth));
+            }
+            self.stack.push((index, true));
+            self.stack.extend(tree.children(index).iter().rev().map(|&child| (child, false)));
+            self.depth += 1;
+        }
+    }
+}
+
+/// An iterator over the values of the children of a node, created by [`SafeVecTree::split_mut()`].
+#[derive(Debug)]
+pub struct Children<'a, T> {
+    before: &'a [T],
+    after: &'a [T],
+    split: usize,
+    indices: std::slice::Iter<'a, usize>,
+}
+
+impl<'a, T> Children<'a, T> {
+    fn value(&self, index: usize) -> &'a T {
+        if index < self.split { &self.before[index] } else { &self.after[index - self.split - 1] }
+    }
+}
+
+impl<'a, T> Iterator for Children<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let &index = self.indices.next()?;
+        Some(self.value(index))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for Children<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let &index = self.indices.next_back()?;
+        Some(self.value(index))
+    }
+}
+
+impl<T> ExactSizeIterator for Children<'_, T> {}
+
+impl<T> FusedIterator for Children<'_, T> {}
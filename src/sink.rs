@@ -0,0 +1,165 @@
+// Copyright 2025 Redglyph
+//
+
+//! Event-driven construction of trees.
+
+use crate::VecTree;
+
+/// A receiver of tree events, in pre-order: a node with children is given by [`TreeSink::start_node()`],
+/// followed by the events of its children and by [`TreeSink::end_node()`]; a node without children
+/// can be given by [`TreeSink::leaf()`].
+pub trait TreeSink<T> {
+    /// Starts a node, which becomes the parent of the next nodes until the matching [`TreeSink::end_node()`].
+    fn start_node(&mut self, value: T);
+
+    /// Ends the last started node.
+    fn end_node(&mut self);
+
+    /// Adds a node without children. The default implementation starts and ends a node.
+    fn leaf(&mut self, value: T) {
+        self.start_node(value);
+        self.end_node();
+    }
+}
+
+impl<T> VecTree<T> {
+    /// Replays the tree under the root as events sent to `sink`, without recursion. Nothing is
+    /// sent if the tree has no root.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::{TreeSink, VecTree};
+    ///
+    /// // converts the tree to an S-expression
+    /// struct SExpr(String);
+    ///
+    /// impl TreeSink<&&str> for SExpr {
+    ///     fn start_node(&mut self, value: &&str) {
+    ///         self.0.push_str(&format!("({value}"));
+    ///     }
+    ///     fn end_node(&mut self) {
+    ///         self.0.push(')');
+    ///     }
+    ///     fn leaf(&mut self, value: &&str) {
+    ///         self.0.push_str(&format!(" {value}"));
+    ///     }
+    /// }
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("*");
+    /// let plus = tree.add(Some(root), "+");
+    /// tree.add_iter(Some(plus), ["2", "3"]);
+    /// tree.add(Some(root), "4");
+    /// let mut sexpr = SExpr(String::new());
+    /// tree.emit(&mut sexpr);
+    /// assert_eq!(sexpr.0, "(*(+ 2 3) 4)");
+    /// ```
+    pub fn emit<'a, S: TreeSink<&'a T>>(&'a self, sink: &mut S) {
+        if let Some(root) = self.get_root() {
+            self.emit_at(root, sink);
+        }
+    }
+
+    /// Replays the subtree under the node `top` as events sent to `sink` (see [`VecTree::emit()`]).
+    pub fn emit_at<'a, S: TreeSink<&'a T>>(&'a self, top: usize, sink: &mut S) {
+        // (index, children sent)
+        let mut stack = vec![(top, false)];
+        while let Some((index, visited)) = stack.pop() {
+            let children = self.children(index);
+            if visited {
+                sink.end_node();
+            } else if children.is_empty() {
+                sink.leaf(self.get(index));
+            } else {
+                sink.start_node(self.get(index));
+                stack.push((index, true));
+                stack.extend(children.iter().rev().map(|&child| (child, false)));
+            }
+        }
+    }
+}
+
+/// A [TreeSink] which assembles a [VecTree] from the events, without recursion. It allows
+/// SAX-style parsers to build a tree without an intermediate representation.
+///
+/// # Example
+///
+/// ```
+/// use vectree::{TreeBuilder, TreeSink};
+/// let mut builder = TreeBuilder::new();
+/// builder.start_node("root");
+/// builder.start_node("a");
+/// builder.leaf("a1");
+/// builder.end_node();
+/// builder.leaf("b");
+/// builder.end_node();
+/// let tree = builder.finish();
+/// assert_eq!(tree.iter_depth_simple().map(|n| *n).collect::<Vec<_>>(), ["a1", "a", "b", "root"]);
+/// ```
+#[derive(Debug)]
+pub struct TreeBuilder<T> {
+    tree: VecTree<T>,
+    // the nodes started and not ended yet
+    open: Vec<usize>
+}
+
+impl<T> TreeBuilder<T> {
+    /// Creates a builder for an empty tree.
+    pub fn new() -> Self {
+        TreeBuilder { tree: VecTree::new(), open: Vec::new() }
+    }
+
+    /// Returns the depth of the next node, which is the number of nodes started and not ended yet.
+    pub fn depth(&self) -> usize {
+        self.open.len()
+    }
+
+    /// Returns the tree once all the started nodes have been ended.
+    ///
+    /// Panics if some nodes haven't been ended.
+    pub fn finish(self) -> VecTree<T> {
+        assert!(self.open.is_empty(), "{} node(s) haven't been ended", self.open.len());
+        self.tree
+    }
+
+    fn add(&mut self, value: T) -> usize {
+        match self.open.last() {
+            Some(&parent) => self.tree.add(Some(parent), value),
+            None => {
+                assert!(self.tree.get_root().is_none(), "the tree already has a root");
+                self.tree.add_root(value)
+            }
+        }
+    }
+}
+
+impl<T> Default for TreeBuilder<T> {
+    fn default() -> Self {
+        TreeBuilder::new()
+    }
+}
+
+impl<T> TreeSink<T> for TreeBuilder<T> {
+    /// Adds a node, which becomes the parent of the next nodes until the matching [`TreeSink::end_node()`].
+    ///
+    /// Panics if the node is at the top level and the tree already has a root.
+    fn start_node(&mut self, value: T) {
+        let index = self.add(value);
+        self.open.push(index);
+    }
+
+    /// Ends the last started node.
+    ///
+    /// Panics if there's no started node.
+    fn end_node(&mut self) {
+        self.open.pop().expect("no started node to end");
+    }
+
+    /// Adds a node without children.
+    ///
+    /// Panics if the node is at the top level and the tree already has a root.
+    fn leaf(&mut self, value: T) {
+        self.add(value);
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright 2025 Redglyph
+//
+
+//! Asynchronous traversal, enabled by the `futures` feature.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use futures_core::Stream;
+use crate::{IterDataSimple, NodeProxySimple, VecTree, VecTreePoDfsIter};
+
+/// A [Stream] of the nodes of a [VecTree] in post-order, depth-first search, created by
+/// [`VecTree::iter_depth_stream()`].
+///
+/// The stream yields to the executor every `yield_every` nodes, so that the traversal of a large
+/// tree doesn't block the other tasks.
+pub struct VecTreeStream<'a, T> {
+    iter: VecTreePoDfsIter<IterDataSimple<'a, T>>,
+    yield_every: usize,
+    count: usize
+}
+
+impl<'a, T> Stream for VecTreeStream<'a, T> {
+    type Item = NodeProxySimple<'a, T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.count == this.yield_every {
+            this.count = 0;
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        this.count += 1;
+        Poll::Ready(this.iter.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T> VecTree<T> {
+    /// Post-order, depth-first search over all the nodes of the [VecTree], starting at its root node,
+    /// as a [Stream] which yields to the executor every `yield_every` nodes.
+    ///
+    /// Like [`VecTree::iter_depth_simple()`], the stream returns a proxy for each node, which gives an
+    /// immutable reference only to that node.
+    ///
+    /// Panics if `yield_every` is 0.
+    pub fn iter_depth_stream(&self, yield_every: usize) -> VecTreeStream<'_, T> {
+        assert!(yield_every > 0, "yield_every must be greater than 0");
+        VecTreeStream { iter: self.iter_depth_simple(), yield_every, count: 0 }
+    }
+}
@@ -2,9 +2,12 @@
 //
 
 #![cfg(test)]
+#![allow(clippy::bool_assert_comparison, clippy::single_char_add_str, clippy::needless_borrow,
+         clippy::into_iter_on_ref, clippy::type_complexity, clippy::iter_nth_zero)]
 
 use std::fmt::Display;
-use crate::VecTree;
+use std::ops::ControlFlow;
+use crate::{LazyVecTree, Node, OrphanPolicy, Parenthesized, SExpression, SafeVecTree, SubTree, SubtreeAggregate, TreeBuilder, TreeError, TreeSink, Traversal, TreeWalker, VecTree, VisitEvent};
 
 // ---------------------------------------------------------------------------------------------
 // Supporting functions
@@ -64,6 +67,199 @@ fn build_tree() -> VecTree<String> {
 mod general {
     use super::*;
 
+    #[test]
+    fn values() {
+        let mut tree = build_tree();
+        assert_eq!(tree.values()[2], "b");
+        assert_eq!(tree.values().len(), tree.len());
+        let mut values = tree.values_mut();
+        values[2].push('!');
+        values[3] = "C".to_string();
+        assert_eq!(values[3], "C");
+        tree[4].value_mut().push('?');
+        assert_eq!(tree[4].value(), "a1?");
+        assert_eq!(tree_to_string(&tree), "root(a(a1?,a2),b!,C(c1,c2))");
+    }
+
+    #[test]
+    fn push_node() {
+        let mut tree = build_tree();
+        let d = tree.push_node(Node::with_children("d".to_string(), vec![9]));
+        let e = tree.push_node(Node::new("e".to_string()));
+        assert_eq!((d, e), (8, 9));
+        tree.attach_child(2, d);
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b(d(e)),c(c1,c2))");
+    }
+
+    #[test]
+    fn node_api() {
+        let mut tree = build_tree();
+        assert_eq!(tree.post_order().len(), 8);
+        let node = &mut tree[2];
+        assert_eq!(node.set_value("B".to_string()), "b");
+        assert!(node.children_mut().is_empty());
+        tree[3].children_mut().pop();
+        assert_eq!(tree.post_order().len(), 7);
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),B,c(c1))");
+    }
+
+    #[test]
+    fn renumber() {
+        let mut tree = build_tree2_loose();
+        let expected = tree_to_string(&tree);
+        let mapping = tree.renumber();
+        assert_eq!(mapping, vec![1, 2, 3, 0, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(tree.get_root(), Some(0));
+        assert_eq!(tree_to_string(&tree), expected);
+        assert_eq!(tree.children(0), &[1, 2, 3]);
+        assert_eq!(tree.children(1), &[4, 5]);
+        assert_eq!(tree.children(3), &[6, 7]);
+        assert_eq!(tree.children(8), &[9]);
+        assert_eq!(tree.get(8), "x");
+    }
+
+    #[test]
+    fn handles() {
+        let mut tree = build_tree2_loose();
+        let root = tree.handle(3);
+        let c2 = tree.handle(7);
+        let x = tree.handle(8);
+        tree.renumber();
+        assert_eq!(tree.resolve(root), Some(0));
+        assert_eq!(tree.resolve(c2), Some(7));
+        assert_eq!(tree.get(tree.resolve(c2).unwrap()), "c2");
+        tree.apply_permutation(&[9, 8, 7, 6, 5, 4, 3, 2, 1, 0]).unwrap();
+        assert_eq!(tree.resolve(root), Some(9));
+        assert_eq!(tree.get(tree.resolve(c2).unwrap()), "c2");
+        assert_eq!(tree.get(tree.resolve(x).unwrap()), "x");
+        tree.release_handle(x);
+        assert_eq!(tree.resolve(x), None);
+        tree.clear();
+        assert_eq!(tree.resolve(root), None);
+    }
+
+    #[test]
+    fn weak_refs() {
+        let mut tree = build_tree2_loose();
+        let c2 = tree.weak_ref(7);
+        let generation = tree.generation();
+        tree.add(Some(7), "c21".to_string());
+        *tree.get_mut(7) = "C2".to_string();
+        assert_eq!(c2.upgrade(&tree), Some(7));
+        tree.renumber();
+        assert_ne!(tree.generation(), generation);
+        assert_eq!(c2.upgrade(&tree), None);
+        let c2 = tree.weak_ref(7);
+        assert_eq!(c2.upgrade(&tree), Some(7));
+        tree.clear_keep_allocations();
+        assert_eq!(c2.upgrade(&tree), None);
+        // a reference can't be upgraded with another tree, even in the same generation
+        let tree = build_tree();
+        let other = build_tree();
+        assert_eq!(tree.generation(), other.generation());
+        let c = tree.weak_ref(3);
+        assert_eq!(c.upgrade(&tree), Some(3));
+        assert_eq!(c.upgrade(&other), None);
+    }
+
+    #[test]
+    fn branded() {
+        let mut tree = build_tree();
+        let names = tree.branded(|mut tree| {
+            let root = tree.root().unwrap();
+            let b = tree.children(root)[1];
+            tree.add(Some(b), "b1".to_string());
+            assert!(tree.id(20).is_none());
+            let c = tree.id(3).unwrap();
+            tree.get_mut(c).push('!');
+            tree.children(root).into_iter().map(|id| tree.get(id).clone()).collect::<Vec<_>>()
+        });
+        assert_eq!(names, ["a", "b", "c!"]);
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b(b1),c!(c1,c2))");
+    }
+
+    #[test]
+    fn tagged_ids() {
+        let mut tree = build_tree();
+        let other = tree.clone();
+        assert_ne!(tree.tree_id(), other.tree_id());
+        let a = tree.tagged_id(1);
+        let a1 = tree.iter_depth_simple().next().unwrap().tagged_id();
+        let c = tree.iter_depth().find(|inode| inode.index == 3).unwrap().tagged_id();
+        assert_eq!(a1.index(), 4);
+        assert_eq!(tree.get_tagged(a1), "a1");
+        assert_eq!(tree.get_tagged(c), "c");
+        tree.get_tagged_mut(a).push('!');
+        assert_eq!(tree.get(1), "a!");
+        for inode in tree.iter_depth_mut() {
+            assert_eq!(inode.tagged_id().tree_id(), a.tree_id());
+        }
+        assert_eq!(other.resolve_tagged(a), Err(TreeError::ForeignNode { node_tree: tree.tree_id(), tree: other.tree_id() }));
+        assert_eq!(other.resolve_tagged(other.tagged_id(1)), Ok(1));
+    }
+
+    fn build_tree2_loose() -> VecTree<String> {
+        let mut tree = VecTree::new();
+        let a = tree.add(None, "a".to_string());
+        let b = tree.add(None, "b".to_string());
+        let c = tree.add(None, "c".to_string());
+        let root = tree.addci_iter(None, "root".to_string(), [a, b, c]);
+        tree.add_iter(Some(a), ["a1".to_string(), "a2".to_string()]);
+        tree.add_iter(Some(c), ["c1", "c2"].map(|s| s.to_string()));
+        let x = tree.add(None, "x".to_string());
+        tree.add(Some(x), "x1".to_string());
+        tree.set_root(root);
+        tree
+    }
+
+    #[test]
+    fn apply_permutation() {
+        let mut tree = build_tree();
+        let expected = tree_to_string(&tree);
+        let size = tree.len();
+        // reverses the order of the nodes:
+        let perm = (0..size).rev().collect::<Vec<_>>();
+        tree.apply_permutation(&perm).unwrap();
+        assert_eq!(tree.get_root(), Some(size - 1));
+        assert_eq!(tree.get(size - 1), "root");
+        assert_eq!(tree_to_string(&tree), expected);
+        assert_eq!(tree.apply_permutation(&perm[1..]), Err(TreeError::BadPermutation));
+        let mut bad_perm = perm.clone();
+        bad_perm[0] = bad_perm[1];
+        assert_eq!(tree.apply_permutation(&bad_perm), Err(TreeError::BadPermutation));
+        bad_perm[0] = size;
+        assert_eq!(tree.apply_permutation(&bad_perm), Err(TreeError::BadPermutation));
+        assert_eq!(tree.get_root(), Some(size - 1));
+        tree.apply_permutation(&perm).unwrap();
+        assert_eq!(tree.get_root(), Some(0));
+        assert_eq!(tree_to_string(&tree), expected);
+    }
+
+    #[test]
+    fn nodes() {
+        let tree = build_tree();
+        let nodes = tree.nodes();
+        assert_eq!(nodes.len(), tree.len());
+        assert_eq!(nodes[0].value(), "root");
+        assert_eq!(nodes[0].children(), &[1, 2, 3]);
+        let leaves = nodes.iter().filter(|node| !node.has_children()).map(|node| node.value().as_str()).collect::<Vec<_>>();
+        assert_eq!(leaves, ["b", "a1", "a2", "c1", "c2"]);
+    }
+
+    #[test]
+    fn clear_keep_allocations() {
+        let mut tree = build_tree();
+        tree.clear_keep_allocations();
+        assert!(tree.is_empty());
+        assert_eq!(tree.get_root(), None);
+        let root = tree.add_root("root".to_string());
+        assert!(tree.children(root).is_empty());
+        assert!(tree.children_mut(root).capacity() > 0);
+        tree.add(Some(root), "a".to_string());
+        assert_eq!(tree_to_string(&tree), "root(a)");
+    }
+
     #[test]
     fn test_build_tree() {
         let tree = build_tree();
@@ -532,6 +728,83 @@ mod general {
         assert_eq!(result, "ROOT(A(A1,A2),B,C(C1,C2))");
     }
 
+    #[test]
+    fn add_subtree() {
+        let mut tree = build_tree();
+        let branch = SubTree::new("d".to_string(), vec![
+            SubTree::new("d1".to_string(), vec![SubTree::leaf("d11".to_string())]),
+            SubTree::leaf("d2".to_string()),
+        ]);
+        let d = tree.add_subtree(Some(2), branch);
+        assert_eq!(d, 8);
+        assert_eq!(tree_to_string_index(&tree), "0:root(1:a(4:a1,5:a2),2:b(8:d(9:d1(10:d11),11:d2)),3:c(6:c1,7:c2))");
+        let mut other = VecTree::new();
+        let root = other.add_subtree(None, SubTree::leaf(1));
+        other.set_root(root);
+        assert_eq!(tree_to_string(&other), "1");
+    }
+
+    #[test]
+    fn append_tree() {
+        struct NoClone(&'static str);
+        impl Display for NoClone {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "{}", self.0) }
+        }
+        let mut tree = VecTree::new();
+        let root = tree.add_root(NoClone("root"));
+        tree.add(Some(root), NoClone("a"));
+        let mut other = VecTree::new();
+        let x1 = other.add(None, NoClone("x1"));
+        let x = other.addci(None, NoClone("x"), x1);
+        other.add(Some(x), NoClone("x2"));
+        other.set_root(x);
+        let x = tree.append_tree(Some(root), other);
+        assert_eq!(x, 3);
+        assert_eq!(tree_to_string_index(&tree), "0:root(1:a,3:x(2:x1,4:x2))");
+
+        let mut other = VecTree::new();
+        let y = other.add_root(NoClone("y"));
+        let y1 = other.add(Some(y), NoClone("y1"));
+        other.add(Some(y1), NoClone("y11"));
+        other.add(Some(y), NoClone("y2"));
+        other.remove_subtree(y1);
+        let y = tree.append_tree(Some(root), other);
+        assert_eq!(y, 5);
+        assert_eq!((5..9).map(|i| tree.is_removed(i)).collect::<Vec<_>>(), [false, true, true, false]);
+        assert_eq!(tree_to_string_index(&tree), "0:root(1:a,3:x(2:x1,4:x2),5:y(8:y2))");
+    }
+
+    #[test]
+    fn concat() {
+        let trees = (0..3).map(|i| {
+            let mut tree = VecTree::new();
+            if i != 1 {
+                let root = tree.add_root(format!("t{i}"));
+                tree.add_iter(Some(root), [format!("t{i}.1"), format!("t{i}.2")]);
+            }
+            tree
+        });
+        let tree = VecTree::concat("root".to_string(), trees);
+        assert_eq!(tree_to_string_index(&tree), "6:root(0:t0(1:t0.1,2:t0.2),3:t2(4:t2.1,5:t2.2))");
+
+        let (forest, roots) = VecTree::concat_forest([build_tree(), VecTree::new(), build_tree()]);
+        assert_eq!(forest.get_root(), None);
+        assert_eq!(forest.len(), 16);
+        assert_eq!(roots, [Some(0), None, Some(8)]);
+        assert_eq!(forest.children(8), &[9, 10, 11]);
+
+        let mut removed = build_tree();
+        removed.remove_subtree(3);
+        let (forest, roots) = VecTree::concat_forest([removed, build_tree()]);
+        assert_eq!(roots, [Some(0), Some(8)]);
+        assert_eq!((0..forest.len()).filter(|&i| forest.is_removed(i)).collect::<Vec<_>>(), [3, 6, 7]);
+        let mut removed = build_tree();
+        removed.remove_subtree(1);
+        let tree = VecTree::concat("root".to_string(), [build_tree(), removed]);
+        assert_eq!((0..tree.len()).filter(|&i| tree.is_removed(i)).collect::<Vec<_>>(), [9, 12, 13]);
+        assert_eq!(tree_to_string(&tree), "root(root(a(a1,a2),b,c(c1,c2)),root(b,c(c1,c2)))");
+    }
+
     #[test]
     #[should_panic(expected="node index 3 doesn't exist")]
     fn bad_index_set_root() {
@@ -576,11 +849,208 @@ mod general {
         }
         assert_eq!(result, ["a1", "a2"]);
     }
+
+    #[test]
+    fn dirty_tracking() {
+        let mut tree = build_tree();
+        *tree.get_mut(1) = "A".to_string();
+        assert!(!tree.is_dirty_tracking());
+        assert!(tree.take_dirty().is_empty());
+        tree.set_dirty_tracking(true);
+        for mut inode in tree.iter_depth_mut() {
+            if inode.num_children() == 2 {
+                inode.push('!');
+            }
+        }
+        tree.add(Some(2), "b1".to_string());
+        tree.values_mut()[8].push('!');
+        assert_eq!(tree.take_dirty(), [1, 3, 8]);
+        tree.get_mut(0).push('!');
+        tree.iter_children_mut(1).for_each(|_| {});
+        let (mut a, mut c) = tree.split_subtrees_mut(1, 3);
+        a.iter_depth_simple_mut().for_each(|inode| assert!(!inode.is_empty()));
+        c.iter_depth_simple_mut().with_indices().for_each(|_| {});
+        assert_eq!(tree.take_dirty(), [0, 3, 4, 5, 6, 7]);
+        tree.apply_permutation(&[8, 7, 6, 5, 4, 3, 2, 1, 0]).unwrap();
+        tree[7].value_mut().push('!');
+        assert_eq!(tree.take_dirty(), [7]);
+        tree.set_root(3);
+        tree.add_parent("top".to_string());
+        tree.get_mut(1).push('!');
+        tree.take_root();
+        assert_eq!(tree.take_dirty(), [1]);
+        tree.set_dirty_tracking(false);
+        tree.get_mut(1).push('!');
+        tree.set_dirty_tracking(true);
+        assert!(tree.take_dirty().is_empty());
+    }
+
+    #[test]
+    fn key_index() {
+        let mut tree = build_tree();
+        tree.build_index(|item: &String| item.chars().next().unwrap());
+        assert_eq!(tree.lookup(&'a'), &[1, 4, 5]);
+        assert_eq!(tree.lookup(&'c'), &[3, 6, 7]);
+        let b1 = tree.add(Some(2), "b1".to_string());
+        let other = VecTree::from((Some(0), [("c3".to_string(), Vec::<usize>::new())]));
+        tree.append_tree(Some(3), other);
+        assert_eq!(tree.lookup(&'b'), &[2, b1]);
+        assert_eq!(tree.lookup(&'c'), &[3, 6, 7, 9]);
+        let copy = tree.clone();
+        tree.apply_permutation(&[9, 8, 7, 6, 5, 4, 3, 2, 1, 0]).unwrap();
+        assert_eq!(tree.lookup(&'c'), &[0, 2, 3, 6]);
+        assert_eq!(copy.lookup(&'c'), &[3, 6, 7, 9]);
+        tree.get_mut(0).replace_range(0..1, "d");
+        assert_eq!(tree.lookup(&'d'), &[] as &[usize]);
+        tree.rebuild_index();
+        assert_eq!(tree.lookup(&'d'), &[0]);
+        tree.clear();
+        assert!(tree.lookup(&'a').is_empty());
+        tree.drop_index();
+    }
+
+    #[test]
+    #[should_panic(expected = "the index doesn't have keys of that type")]
+    fn key_index_bad_type() {
+        let mut tree = build_tree();
+        tree.build_index(|item: &String| item.len());
+        tree.lookup(&'a');
+    }
+
+    #[test]
+    fn tree_builder() {
+        let mut builder = TreeBuilder::new();
+        builder.start_node("root".to_string());
+        for name in ["a", "b", "c"] {
+            if name == "b" {
+                builder.leaf(name.to_string());
+            } else {
+                builder.start_node(name.to_string());
+                assert_eq!(builder.depth(), 2);
+                builder.leaf(format!("{name}1"));
+                builder.leaf(format!("{name}2"));
+                builder.end_node();
+            }
+        }
+        builder.end_node();
+        let tree = builder.finish();
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b,c(c1,c2))");
+        // deep tree, without recursion
+        let mut builder = TreeBuilder::new();
+        (0..100_000).for_each(|i| builder.start_node(i));
+        (0..100_000).for_each(|_| builder.end_node());
+        assert_eq!(builder.finish().depth(), Some(99_999));
+    }
+
+    #[test]
+    fn emit() {
+        let tree = build_tree();
+        let mut builder = TreeBuilder::new();
+        tree.emit(&mut builder);
+        let copy = builder.finish();
+        assert_eq!(tree_to_string(&copy), tree_to_string(&tree));
+        let mut builder = TreeBuilder::new();
+        tree.emit_at(3, &mut builder);
+        assert_eq!(tree_to_string(&builder.finish()), "c(c1,c2)");
+        let empty = VecTree::<String>::new();
+        let mut builder = TreeBuilder::new();
+        empty.emit(&mut builder);
+        assert!(builder.finish().is_empty());
+    }
+
+    #[test]
+    fn format_with() {
+        let tree = build_tree();
+        let mut text = String::new();
+        tree.format_with(&Parenthesized, &mut text).unwrap();
+        assert_eq!(text, tree_to_string(&tree));
+        text.clear();
+        tree.format_with(&SExpression, &mut text).unwrap();
+        assert_eq!(text, "(root (a a1 a2) b (c c1 c2))");
+        text.clear();
+        tree.format_at_with(3, &SExpression, &mut text).unwrap();
+        assert_eq!(text, "(c c1 c2)");
+        let empty = VecTree::<String>::new();
+        text.clear();
+        empty.format_with(&Parenthesized, &mut text).unwrap();
+        assert!(text.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "1 node(s) haven't been ended")]
+    fn tree_builder_unfinished() {
+        let mut builder = TreeBuilder::new();
+        builder.start_node(0);
+        builder.leaf(1);
+        builder.finish();
+    }
+}
+
+mod safe {
+    use super::*;
+
+    #[test]
+    fn safe_tree() {
+        let tree = build_tree();
+        let mut safe = SafeVecTree::from(tree.clone());
+        let visited = safe.iter_depth().map(|(index, depth, value)| format!("{index}:{depth}:{value}")).collect::<Vec<_>>();
+        assert_eq!(visited, ["4:2:a1", "5:2:a2", "1:1:a", "2:1:b", "6:2:c1", "7:2:c2", "3:1:c", "0:0:root"]);
+        safe.for_each_mut(|_, _, value, children| {
+            if children.len() > 0 {
+                *value = format!("{value}[{}]", children.map(|c| c.as_str()).collect::<Vec<_>>().join("+"));
+            }
+        });
+        assert_eq!(safe[3], "c[c1+c2]");
+        assert_eq!(safe[0], "root[a[a1+a2]+b+c[c1+c2]]");
+        let mut cursor = safe.post_order_at(1);
+        let mut indices = vec![];
+        while let Some((index, depth)) = cursor.next(&safe) {
+            if index == 4 {
+                safe.add(Some(5), "a2x".to_string());
+            }
+            indices.push((index, depth));
+        }
+        assert_eq!(indices, [(4, 1), (8, 2), (5, 1), (1, 0)]);
+        let back = VecTree::try_from(safe).unwrap();
+        assert_eq!(tree_to_string(&back), "root[a[a1+a2]+b+c[c1+c2]](a[a1+a2](a1,a2(a2x)),b,c[c1+c2](c1,c2))");
+    }
+
+    #[test]
+    fn safe_tree_conversion() {
+        let mut safe = SafeVecTree::new();
+        let a = safe.add(None, "a");
+        safe.add_root("root");
+        safe.add(Some(a), "a1");
+        assert_eq!(VecTree::try_from(safe).map(|_| ()), Ok(()));
+        let mut safe = SafeVecTree::new();
+        let root = safe.add_root("root");
+        let a = safe.add(Some(root), "a");
+        let tree = VecTree::try_from_parts(vec![("root", vec![1]), ("a", vec![])], Some(root)).unwrap();
+        let mut from_tree = SafeVecTree::from(tree);
+        from_tree.add(Some(a), "a1");
+        assert_eq!(from_tree.children(a), &[2]);
+        assert_eq!(safe.split_mut(root).1.collect::<Vec<_>>(), [&"a"]);
+    }
 }
 
 mod borrow {
     use super::*;
 
+    #[test]
+    fn shared_between_threads() {
+        fn is_send_sync<T: Send + Sync>(_: &T) {}
+        let tree = build_tree();
+        is_send_sync(&tree);
+        let results = std::thread::scope(|scope| {
+            let handles = (0..4).map(|i| {
+                let tree = &tree;
+                scope.spawn(move || tree.iter_depth_simple_at(i).map(|inode| inode.to_string()).collect::<Vec<_>>().join(","))
+            }).collect::<Vec<_>>();
+            handles.into_iter().map(|h| h.join().unwrap()).collect::<Vec<_>>()
+        });
+        assert_eq!(results, ["a1,a2,a,b,c1,c2,c,root", "a1,a2,a", "b", "c1,c2,c"]);
+    }
+
     #[test]
     #[should_panic(expected="pending mutable reference(s) on children")]
     fn iter_depth_mut_children_bad() {
@@ -632,6 +1102,326 @@ mod borrow {
         let result = tree_to_string(&tree);
         assert_eq!(result, "root(a(A1,a2),b,c(c1,c2))");
     }
+
+    #[test]
+    fn active_mut_proxies() {
+        let mut tree = build_tree();
+        {
+            let mut inodes = tree.iter_depth_mut();
+            let a1 = inodes.next().unwrap();
+            assert_eq!(a1.active_mut_proxies(), 1);
+            let a2 = inodes.next().unwrap();
+            assert_eq!(a2.active_mut_proxies(), 2);
+            drop(a1);
+            assert_eq!(a2.active_mut_proxies(), 1);
+            #[cfg(feature = "debug-borrows")]
+            assert_eq!(a2.live_mut_proxies().into_iter().map(|(index, _)| index).collect::<Vec<_>>(), [5]);
+        }
+        assert_eq!(tree.active_mut_proxies(), 0);
+        let leaked = tree.iter_depth_mut().nth(2).unwrap();
+        std::mem::forget(leaked);
+        assert_eq!(tree.active_mut_proxies(), 1);
+        #[cfg(feature = "debug-borrows")]
+        assert_eq!(tree.live_mut_proxies().into_iter().map(|(index, _)| index).collect::<Vec<_>>(), [1]);
+    }
+
+    #[test]
+    fn iter_children_mut() {
+        let mut tree = build_tree();
+        for mut inode in tree.iter_depth_mut() {
+            let mut names = Vec::new();
+            for mut child in inode.iter_children_mut() {
+                assert_eq!(child.active_mut_proxies(), 2);
+                names.push(child.to_string());
+                child.make_ascii_uppercase();
+            }
+            let depth = inode.depth;
+            if let Some(mut first) = inode.child_mut(0) {
+                assert_eq!((first.depth, first.active_mut_proxies()), (depth + 1, 2));
+                // the child proxy can look at the grandchildren
+                let grandchildren = first.iter_children().map(|k| k.to_string()).collect::<Vec<_>>().join("+");
+                first.push_str(&grandchildren);
+            }
+            if !names.is_empty() {
+                inode.push_str(&format!("[{}]", names.join("+")));
+            }
+        }
+        assert_eq!(tree_to_string(&tree), "root[a[a1+a2]+b+c[c1+c2]](A[A1+A2]A1+A2(A1,A2),B,C[C1+C2](C1,C2))");
+        assert_eq!(tree.active_mut_proxies(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "2 extra pending mutable reference(s) when requesting mutable references on children")]
+    fn iter_children_mut_borrow() {
+        let mut tree = build_tree();
+        let mut inodes = tree.iter_depth_mut().take(3).collect::<Vec<_>>();
+        // inodes[0] and inodes[1] are the children of inodes[2]
+        let _ = inodes[2].child_mut(0);
+    }
+
+    #[cfg(feature = "debug-borrows")]
+    #[test]
+    fn live_mut_proxies_between_threads() {
+        let mut tree = build_tree();
+        std::mem::forget(tree.iter_depth_mut().next());
+        let tree = &tree;
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(move || assert_eq!(tree.live_mut_proxies().len(), 1));
+            }
+        });
+    }
+
+    #[cfg(feature = "debug-borrows")]
+    #[test]
+    #[should_panic(expected = "mutable proxy on node 4, created at:")]
+    fn debug_borrows_report() {
+        let mut tree = build_tree();
+        let inodes = tree.iter_depth_mut().collect::<Vec<_>>();
+        let _ = inodes[2].iter_children().count();
+    }
+}
+
+mod subtrees {
+    use super::*;
+
+    #[test]
+    fn split_subtrees_mut() {
+        let mut tree = build_tree();
+        let (mut sub_a, mut sub_c) = tree.split_subtrees_mut(1, 3);
+        assert_eq!((sub_a.top(), sub_c.top()), (1, 3));
+        let mut iter_a = sub_a.iter_depth_mut();
+        let mut iter_c = sub_c.iter_depth_mut();
+        while let (Some(mut a), Some(mut c)) = (iter_a.next(), iter_c.next()) {
+            // both proxies are alive, but each handle counts its own borrows
+            let a_children = a.iter_children_simple().cloned().collect::<Vec<_>>().join("+");
+            let c_children = c.iter_children_simple().cloned().collect::<Vec<_>>().join("+");
+            std::mem::swap(&mut *a, &mut *c);
+            a.push_str(&c_children);
+            c.push_str(&a_children);
+        }
+        drop((iter_a, iter_c));
+        assert_eq!(sub_a.iter_depth().map(|n| n.to_string()).collect::<Vec<_>>(), ["c1", "c2", "ca1+a2"]);
+        assert_eq!(tree_to_string(&tree), "root(ca1+a2(c1,c2),b,ac1+c2(a1,a2))");
+    }
+
+    #[test]
+    fn split_subtrees_mut_simple() {
+        let mut tree = build_tree();
+        let (mut sub_b, mut sub_c) = tree.split_subtrees_mut(2, 3);
+        let b = sub_b.iter_depth_simple_mut().next().unwrap();
+        for mut inode in sub_c.iter_depth_simple_mut() {
+            inode.push_str(&b);
+        }
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b,cb(c1b,c2b))");
+    }
+
+    #[test]
+    #[should_panic(expected="subtrees 0 and 3 are not disjoint")]
+    fn split_subtrees_mut_overlap() {
+        let mut tree = build_tree();
+        tree.split_subtrees_mut(0, 3);
+    }
+
+    // the shared node is caught earlier by the integrity verification
+    #[cfg(not(feature = "debug-validate"))]
+    #[test]
+    #[should_panic(expected="subtrees 1 and 3 are not disjoint")]
+    fn split_subtrees_mut_shared() {
+        let mut tree = build_tree();
+        tree.attach_child(3, 4);
+        tree.split_subtrees_mut(1, 3);
+    }
+}
+
+mod integrity {
+    use super::*;
+
+    #[test]
+    fn try_attach_child() {
+        let mut tree = build_tree();
+        let d = tree.add(None, "d".to_string());
+        assert_eq!(tree.try_attach_child(4, 1), Err(TreeError::MultipleParents { child: 1, parent: 0 }));
+        assert_eq!(tree.try_attach_child(4, 0), Err(TreeError::Cycle { parent: 4, child: 0 }));
+        assert_eq!(tree.try_attach_child(d, d), Err(TreeError::Cycle { parent: d, child: d }));
+        assert_eq!(tree.try_attach_child(9, d), Err(TreeError::BadIndex(9)));
+        assert_eq!(tree.try_attach_child(2, 10), Err(TreeError::BadIndex(10)));
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b,c(c1,c2))");
+        assert_eq!(tree.try_attach_child(2, d), Ok(()));
+        assert_eq!(tree.try_attach_child(3, d), Err(TreeError::MultipleParents { child: d, parent: 2 }));
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b(d),c(c1,c2))");
+        assert_eq!(TreeError::Cycle { parent: 4, child: 0 }.to_string(), "attaching node 0 to node 4 would create a cycle");
+    }
+
+    #[test]
+    fn dedup_children() {
+        let mut tree = build_tree();
+        tree.attach_children(3, [7, 6, 7]);
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b,c(c1,c2,c2,c1,c2))");
+        tree.dedup_children(3);
+        assert_eq!(tree.children(3), &[6, 7]);
+        assert_eq!(tree.validate(), Ok(()));
+        tree.set_strict(true);
+        assert_eq!(tree.try_attach_child(3, 6), Err(TreeError::MultipleParents { child: 6, parent: 3 }));
+    }
+
+    #[test]
+    #[should_panic(expected = "strict mode: node 4 already has a parent, node 1")]
+    fn strict_mode_duplicate_child() {
+        let mut tree = build_tree();
+        tree.set_strict(true);
+        tree.attach_children(1, [4]);
+    }
+
+    #[test]
+    fn validate() {
+        let mut tree = build_tree();
+        assert_eq!(tree.validate(), Ok(()));
+        tree.children_mut(4).push(9);
+        assert_eq!(tree.validate(), Err(TreeError::BadIndex(9)));
+        tree.children_mut(4).pop();
+        tree.children_mut(4).push(2);
+        assert_eq!(tree.validate(), Err(TreeError::MultipleParents { child: 2, parent: 0 }));
+        tree.children_mut(4).pop();
+        tree.children_mut(4).push(0);
+        assert_eq!(tree.validate(), Err(TreeError::RootHasParent { root: 0, parent: 4 }));
+        tree.children_mut(4).pop();
+        let d = tree.add(None, "d".to_string());
+        let e = tree.add(Some(d), "e".to_string());
+        assert_eq!(tree.validate(), Ok(()));
+        tree.attach_child(e, d);
+        assert!(matches!(tree.validate(), Err(TreeError::Cycle { .. })));
+    }
+
+    #[test]
+    fn raw_parts() {
+        let tree = build_tree();
+        let expected = tree_to_string(&tree);
+        let (nodes, root) = tree.into_raw_parts();
+        assert_eq!(nodes[0], ("root".to_string(), vec![1, 2, 3]));
+        let tree = VecTree::try_from_parts(nodes.clone(), root).unwrap();
+        assert_eq!(tree_to_string(&tree), expected);
+        let tree = unsafe { VecTree::from_raw_parts(nodes.clone(), root) };
+        assert_eq!(tree_to_string(&tree), expected);
+        let mut bad_nodes = nodes;
+        bad_nodes[2].1.push(8);
+        assert_eq!(VecTree::try_from_parts(bad_nodes, root).err(), Some(TreeError::BadIndex(8)));
+    }
+
+    #[test]
+    fn strict_mode() {
+        let mut tree = build_tree();
+        assert!(!tree.is_strict());
+        tree.set_strict(true);
+        assert!(tree.clone().is_strict());
+        let d = tree.add(None, "d".to_string());
+        let e = tree.addci(None, "e".to_string(), d);
+        tree.attach_children(2, [e]);
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b(e(d)),c(c1,c2))");
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "strict mode: attaching node 0 to node 4 would create a cycle")]
+    fn strict_mode_cycle() {
+        let mut tree = build_tree();
+        tree.set_strict(true);
+        tree.attach_child(4, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "strict mode: node 4 already has a parent, node 1")]
+    fn strict_mode_addci() {
+        let mut tree = build_tree();
+        tree.set_strict(true);
+        tree.addci_iter(Some(2), "d".to_string(), [4]);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "node index 9 doesn't exist")]
+    fn debug_attach_child() {
+        let mut tree = build_tree();
+        tree.attach_children(2, [9]);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "node index 9 doesn't exist")]
+    fn debug_attach_child_bad_index() {
+        let mut tree = build_tree();
+        tree.attach_child(2, 9);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "node 2 can't be attached to itself")]
+    fn debug_attach_child_itself() {
+        let mut tree = build_tree();
+        tree.attach_child(2, 2);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "node 3 has been removed")]
+    fn debug_attach_child_removed() {
+        let mut tree = build_tree();
+        tree.remove_subtree(3);
+        tree.attach_child(2, 3);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "node 1 can't be attached to itself")]
+    fn debug_attach_child_at() {
+        let mut tree = build_tree();
+        tree.attach_child_at(1, 0, 1);
+    }
+
+    #[cfg(feature = "debug-validate")]
+    #[test]
+    #[should_panic(expected = "tree integrity: node 2 already has a parent, node 0")]
+    fn debug_validate() {
+        let mut tree = build_tree();
+        tree.children_mut(4).push(2);
+        let _ = tree.iter_depth_simple().count();
+    }
+
+    #[cfg(feature = "debug-validate")]
+    #[test]
+    #[should_panic(expected = "tree integrity: node index 9 doesn't exist")]
+    fn debug_validate_bad_index() {
+        let mut tree = build_tree();
+        tree.children_mut(4).push(9);
+        tree.visit_post_order(|_, _, _| {});
+    }
+
+    #[cfg(feature = "debug-validate")]
+    #[test]
+    #[should_panic(expected = "tree integrity: attaching node 1 to node 4 would create a cycle")]
+    fn debug_validate_cycle() {
+        let mut tree = build_tree();
+        tree.detach_child(0, 1);
+        tree.children_mut(4).push(1);
+        let _ = tree.iter_depth().count();
+    }
+
+    #[cfg(feature = "debug-validate")]
+    #[test]
+    #[should_panic(expected = "tree integrity: root node 3 has a parent, node 0")]
+    fn debug_validate_root() {
+        let mut tree = build_tree();
+        tree.set_root(3);
+        let _ = tree.iter_depth_simple_mut().count();
+    }
+
+    #[test]
+    #[should_panic(expected = "strict mode: root node 1 has a parent, node 0")]
+    fn strict_mode_set_root() {
+        let mut tree = build_tree();
+        tree.set_strict(true);
+        tree.set_root(1);
+    }
 }
 
 mod alternate_root {
@@ -699,4 +1489,1381 @@ mod alternate_root {
         drop(tree);
         assert_eq!(tree_to_string(&other_tree), "root(a(a1,a2),b,c(c1,c2))");
     }
+
+    #[test]
+    fn level_of() {
+        let mut tree = build_tree2();
+        let levels = tree.iter_depth_simple().map(|inode| (inode.depth, tree.level_of(inode.index))).collect::<Vec<_>>();
+        assert!(levels.iter().all(|&(depth, level)| Some(depth) == level));
+        tree.detach_child(3, 2);
+        tree.set_root(2);
+        assert_eq!(tree.level_of(6), Some(1));
+        assert_eq!(tree.level_of(3), None);
+        assert_eq!(tree.level_of(4), None);
+        // the table of levels is rebuilt after a modification
+        let new = tree.add(Some(6), "new".to_string());
+        assert_eq!(tree.level_of(new), Some(2));
+    }
+
+    #[test]
+    fn add_parent_take_root() {
+        let mut tree = build_tree2();
+        let c = tree.handle(2);
+        let weak = tree.weak_ref(2);
+        let top = tree.add_parent("top".to_string());
+        assert_eq!(top, 8);
+        assert_eq!(tree_to_string(&tree), "top(root(a(a1,a2),b,c(c1,c2)))");
+        assert_eq!(tree.take_root(), Some("top".to_string()));
+        assert_eq!(tree.get_root(), Some(3));
+        assert_eq!(tree.take_root(), None);
+        // removes a root in the middle of the buffer
+        let b = tree.add(None, "b".to_string());
+        let a = tree.addci(None, "a".to_string(), b);
+        tree.set_root(a);
+        assert_eq!(tree.take_root(), Some("a".to_string()));
+        assert_eq!(tree.take_root(), None);
+        tree.set_root(3);
+        assert_eq!(tree.len(), 9);
+        tree.add_parent("root".to_string());
+        assert_eq!(tree_to_string(&tree), "root(root(a(a1,a2),b,c(c1,c2)))");
+        assert_eq!(tree.take_root(), Some("root".to_string()));
+        assert_eq!(tree.get_root(), Some(3));
+        assert_eq!(tree.resolve(c), Some(2));
+        assert_eq!(weak.upgrade(&tree), None);
+        assert_eq!(VecTree::<String>::new().take_root(), None);
+    }
+
+    #[test]
+    fn remove_subtree() {
+        let mut tree = build_tree();
+        tree.build_index(|item: &String| item.len());
+        let c1 = tree.handle(6);
+        let b = tree.handle(2);
+        let weak = tree.weak_ref(2);
+        tree.remove_subtree(3);
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b)");
+        assert_eq!((3..8).map(|i| tree.is_removed(i)).collect::<Vec<_>>(), [true, false, false, true, true]);
+        assert_eq!(tree.len(), 8);
+        assert_eq!(tree.iter_depth_simple_at(6).count(), 0);
+        assert_eq!(tree.iter_depth_at_mut(3).count(), 0);
+        assert_eq!(tree.iter_traverse_at(3, Traversal::BreadthFirst).count(), 0);
+        assert_eq!((tree.resolve(c1), tree.resolve(b)), (None, Some(2)));
+        assert_eq!(weak.upgrade(&tree), None);
+        assert_eq!(tree.lookup(&2_usize), &[4, 5]);
+        assert_eq!(tree.try_attach_child(2, 6), Err(TreeError::BadIndex(6)));
+        tree.remove_subtree(0);
+        assert_eq!(tree.get_root(), None);
+        assert!((0..8).all(|i| tree.is_removed(i)));
+        let root = tree.add_root("new".to_string());
+        assert_eq!(tree_to_string(&tree), "new");
+        assert!(!tree.is_removed(root));
+    }
+
+    #[test]
+    fn compact() {
+        let mut tree = build_tree();
+        tree.build_index(|item: &String| item.len());
+        tree.set_dirty_tracking(true);
+        let c2 = tree.handle(7);
+        let a = tree.handle(1);
+        tree.remove_subtree(1);
+        tree.set_expanded(3, true);
+        tree.get_mut(7).push('!');
+        let orphan = tree.add(None, "orphan".to_string());
+        let mut remap = vec![];
+        tree.compact(|old, new| remap.push((old, new)));
+        assert_eq!(orphan, 8);
+        assert_eq!(remap, [(0, 0), (2, 1), (3, 2), (6, 3), (7, 4)]);
+        assert_eq!(tree.len(), 5);
+        assert_eq!(tree_to_string(&tree), "root(b,c(c1,c2!))");
+        assert!((0..5).all(|i| !tree.is_removed(i)));
+        assert_eq!((tree.resolve(c2), tree.resolve(a)), (Some(4), None));
+        assert!(tree.is_expanded(2));
+        assert_eq!(tree.take_dirty(), [4]);
+        assert_eq!(tree.lookup(&2_usize), &[3]);
+        assert_eq!(tree.validate(), Ok(()));
+        tree.remove_subtree(0);
+        tree.compact(|_, _| panic!("no node should be kept"));
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn retain() {
+        let keep = |item: &String| !item.starts_with('c') || item.ends_with('2');
+        for (policy, expected, kept) in [
+            (OrphanPolicy::Drop, "root(a(a1,a2),b)", vec![0, 1, 2, 4, 5]),
+            (OrphanPolicy::Hoist, "root(a(a1,a2),b,c2)", vec![0, 1, 2, 4, 5, 7]),
+        ] {
+            let mut tree = build_tree();
+            let orphan = tree.add(None, "c_orphan".to_string());
+            let c1 = tree.handle(6);
+            let mut visited = vec![];
+            tree.retain(|item| { visited.push(item.clone()); keep(item) }, policy);
+            assert_eq!(tree_to_string(&tree), expected);
+            assert_eq!((0..tree.len()).filter(|&i| !tree.is_removed(i)).collect::<Vec<_>>(), [kept, vec![orphan]].concat());
+            assert_eq!(visited.len(), if policy == OrphanPolicy::Drop { 6 } else { 8 });
+            assert_eq!(tree.resolve(c1), None);
+            assert_eq!(tree.validate(), Ok(()));
+            tree.compact(|_, _| ());
+            assert_eq!(tree_to_string(&tree), expected);
+        }
+        let mut tree = build_tree();
+        tree.retain(|item| item.len() == 2, OrphanPolicy::Hoist);
+        assert_eq!(tree.get_root(), None);
+        assert_eq!((0..8).filter(|&i| !tree.is_removed(i)).collect::<Vec<_>>(), [4, 5, 6, 7]);
+        assert_eq!(tree.find_parent(4), None);
+    }
+
+    #[test]
+    fn detach_child() {
+        let mut tree = build_tree();
+        assert!(tree.detach_child(0, 3));
+        assert!(!tree.detach_child(0, 3));
+        assert!(!tree.detach_child(1, 6));
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b)");
+        tree.attach_child(2, 3);
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b(c(c1,c2)))");
+        assert_eq!(tree.detach_child_at(1, 0), 4);
+        assert_eq!(tree_to_string(&tree), "root(a(a2),b(c(c1,c2)))");
+        assert_eq!(tree.try_attach_child(3, 4), Ok(()));
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn reparent() {
+        let mut tree = build_tree();
+        assert_eq!(tree.reparent(3, 2, None), Ok(()));
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b(c(c1,c2)))");
+        assert_eq!(tree.reparent(5, 1, Some(0)), Ok(()));
+        assert_eq!(tree_to_string(&tree), "root(a(a2,a1),b(c(c1,c2)))");
+        assert_eq!(tree.reparent(1, 0, Some(1)), Ok(()));
+        assert_eq!(tree_to_string(&tree), "root(b(c(c1,c2)),a(a2,a1))");
+        assert_eq!(tree.reparent(2, 6, None), Err(TreeError::Cycle { parent: 6, child: 2 }));
+        assert_eq!(tree.reparent(2, 2, None), Err(TreeError::Cycle { parent: 2, child: 2 }));
+        assert_eq!(tree.reparent(0, 1, None), Err(TreeError::RootHasParent { root: 0, parent: 1 }));
+        assert_eq!(tree.reparent(9, 1, None), Err(TreeError::BadIndex(9)));
+        let orphan = tree.add(None, "d".to_string());
+        assert_eq!(tree.reparent(orphan, 0, Some(0)), Ok(()));
+        assert_eq!(tree_to_string(&tree), "root(d,b(c(c1,c2)),a(a2,a1))");
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn swap_subtrees() {
+        let mut tree = build_tree();
+        assert_eq!(tree.swap_subtrees(1, 3), Ok(()));
+        assert_eq!(tree_to_string(&tree), "root(c(c1,c2),b,a(a1,a2))");
+        assert_eq!(tree.swap_subtrees(4, 7), Ok(()));
+        assert_eq!(tree_to_string(&tree), "root(c(c1,a1),b,a(c2,a2))");
+        assert_eq!(tree.swap_subtrees(3, 3), Ok(()));
+        assert_eq!(tree.swap_subtrees(3, 6), Err(TreeError::Cycle { parent: 3, child: 6 }));
+        assert_eq!(tree.swap_subtrees(6, 0), Err(TreeError::Cycle { parent: 0, child: 6 }));
+        assert_eq!(tree.swap_subtrees(2, 9), Err(TreeError::BadIndex(9)));
+        let orphan = tree.add(None, "d".to_string());
+        assert_eq!(tree.swap_subtrees(orphan, 1), Ok(()));
+        assert_eq!(tree_to_string(&tree), "root(c(c1,a1),b,d)");
+        assert_eq!(tree.find_parent(1), None);
+        assert_eq!(tree.swap_subtrees(1, 0), Ok(()));
+        assert_eq!(tree.get_root(), Some(1));
+        assert_eq!(tree_to_string(&tree), "a(c2,a2)");
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn swap() {
+        let mut tree = build_tree();
+        tree.build_index(|item: &String| item.len());
+        tree.set_dirty_tracking(true);
+        tree.swap(0, 7);
+        tree.swap(2, 2);
+        tree.swap(5, 1);
+        assert_eq!(tree_to_string(&tree), "c2(a2(a1,a),b,c(c1,root))");
+        assert_eq!(tree.lookup(&4_usize), &[7]);
+        assert_eq!(tree.lookup(&1_usize), &[2, 3, 5]);
+        assert_eq!(tree.lookup(&2_usize), &[0, 1, 4, 6]);
+        assert_eq!(tree.take_dirty(), [0, 1, 5, 7]);
+        // the items of the removed nodes aren't indexed
+        tree.remove_subtree(3);
+        tree.swap(6, 2);
+        assert_eq!(tree.lookup(&1_usize), &[5]);
+        assert_eq!(tree.lookup(&2_usize), &[0, 1, 2, 4]);
+        tree.swap(7, 6);
+        assert_eq!(tree.lookup(&4_usize), &[] as &[usize]);
+        let lookups = (1..5_usize).map(|len| tree.lookup(&len).to_vec()).collect::<Vec<_>>();
+        tree.rebuild_index();
+        assert_eq!((1..5_usize).map(|len| tree.lookup(&len).to_vec()).collect::<Vec<_>>(), lookups);
+    }
+
+    #[test]
+    fn insert_child_at() {
+        let mut tree = build_tree();
+        let a0 = tree.insert_child_at(1, 0, "a0".to_string());
+        let a3 = tree.insert_child_at(1, 3, "a3".to_string());
+        assert_eq!((a0, a3), (8, 9));
+        assert_eq!(tree_to_string(&tree), "root(a(a0,a1,a2,a3),b,c(c1,c2))");
+        let d = tree.add(None, "d".to_string());
+        tree.attach_child_at(0, 1, d);
+        assert_eq!(tree_to_string(&tree), "root(a(a0,a1,a2,a3),d,b,c(c1,c2))");
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn insert_above() {
+        let mut tree = build_tree();
+        let b2 = tree.insert_above(2, "b2".to_string());
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b2(b),c(c1,c2))");
+        let top = tree.insert_above(0, "top".to_string());
+        assert_eq!(tree.get_root(), Some(top));
+        assert_eq!(tree_to_string(&tree), "top(root(a(a1,a2),b2(b),c(c1,c2)))");
+        let orphan = tree.add(None, "d".to_string());
+        let d2 = tree.insert_above(orphan, "d2".to_string());
+        assert_eq!(tree.children(d2), &[orphan]);
+        assert_eq!(tree.find_parent(d2), None);
+        assert_eq!(b2, 8);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn remove_splice() {
+        let mut tree = build_tree();
+        let c2 = tree.handle(7);
+        assert_eq!(tree.remove_splice(3), "c");
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b,c1,c2)");
+        assert_eq!(tree.resolve(c2), Some(6));
+        assert_eq!(tree.remove_splice(4), "a2");
+        assert_eq!(tree_to_string(&tree), "root(a(a1),b,c1,c2)");
+        let top = tree.add_parent("top".to_string());
+        assert_eq!(tree.remove_splice(top), "top");
+        assert_eq!(tree.get_root(), Some(0));
+        assert_eq!(tree.len(), 6);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "the root node 0 can't be removed unless it has exactly one child")]
+    fn remove_splice_root() {
+        let mut tree = build_tree();
+        tree.remove_splice(0);
+    }
+
+    #[test]
+    fn split_off() {
+        let mut tree = build_tree();
+        let c2 = tree.handle(7);
+        let a1 = tree.handle(4);
+        let a = tree.split_off(1);
+        assert_eq!(tree_to_string(&tree), "root(b,c(c1,c2))");
+        assert_eq!(tree_to_string(&a), "a(a1,a2)");
+        assert_eq!(a.get_root(), Some(0));
+        assert_eq!((tree.resolve(c2), tree.resolve(a1)), (Some(4), None));
+        assert_eq!(tree.len(), 5);
+        let whole = tree.split_off(0);
+        assert!(tree.is_empty());
+        assert_eq!(tree.get_root(), None);
+        assert_eq!(tree_to_string(&whole), "root(b,c(c1,c2))");
+        assert_eq!(whole.validate(), Ok(()));
+    }
+
+    #[test]
+    fn subtree() {
+        let mut tree = build_tree();
+        tree.children_mut(1).retain(|&c| c != 4);
+        tree.attach_child(6, 4);
+        let c = tree.subtree(3);
+        assert_eq!(tree_to_string(&c), "c(c1(a1),c2)");
+        assert_eq!(c.nodes().iter().map(|node| node.children().to_vec()).collect::<Vec<_>>(), [vec![1, 2], vec![3], vec![], vec![]]);
+        assert_eq!(c.validate(), Ok(()));
+        assert_eq!(tree_to_string(&tree.subtree(5)), "a2");
+        assert_eq!(tree_to_string(&tree), "root(a(a2),b,c(c1(a1),c2))");
+    }
+
+    #[test]
+    fn replace_subtree() {
+        let mut tree = build_tree();
+        let other = build_tree();
+        let top = tree.replace_subtree(1, &other, Some(3));
+        assert_eq!(top, 10);
+        assert_eq!(tree_to_string(&tree), "root(c(c1,c2),b,c(c1,c2))");
+        assert!(tree.is_removed(1) && tree.is_removed(4) && tree.is_removed(5));
+        let top = tree.replace_subtree(0, &other, None);
+        assert_eq!(tree.get_root(), Some(top));
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b,c(c1,c2))");
+        tree.compact(|_, _| ());
+        assert_eq!(tree.len(), 8);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn drain_depth() {
+        let mut tree = build_tree();
+        let handle = tree.handle(1);
+        tree.add(None, "orphan".to_string());
+        tree.remove_subtree(3);
+        let mut drain = tree.drain_depth();
+        assert_eq!(drain.next().as_deref(), Some("a1"));
+        assert_eq!(drain.collect::<Vec<_>>(), ["a2", "a", "b", "root"]);
+        assert!(tree.is_empty());
+        assert_eq!((tree.get_root(), tree.resolve(handle)), (None, None));
+        assert_eq!(tree.drain_depth().count(), 0);
+        let root = tree.add_root("root".to_string());
+        assert_eq!(tree_to_string(&tree), "root");
+        assert!(!tree.is_removed(root));
+    }
+
+    #[test]
+    fn into_iter_owned() {
+        let tree = build_tree();
+        let items = Vec::from_iter(tree.clone());
+        assert_eq!(items, ["a1", "a2", "a", "b", "c1", "c2", "c", "root"]);
+        let lengths = tree.into_iter().map(|item| item.len()).collect::<Vec<_>>();
+        assert_eq!(lengths, [2, 2, 1, 1, 2, 2, 1, 4]);
+        assert_eq!(VecTree::<String>::new().into_iter().count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "position 3 is out of bounds (node 3 has 2 children)")]
+    fn insert_child_at_bad_position() {
+        let mut tree = build_tree();
+        tree.insert_child_at(3, 3, "c3".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "position 3 is out of bounds (node 0 has 2 other children)")]
+    fn reparent_bad_position() {
+        let mut tree = build_tree();
+        let _ = tree.reparent(1, 0, Some(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "position 2 is out of bounds (node 1 has 2 children)")]
+    fn detach_child_at_bad_position() {
+        let mut tree = build_tree();
+        tree.detach_child_at(1, 2);
+    }
+
+    #[test]
+    fn truncate_depth() {
+        let mut tree = build_tree();
+        tree.add(Some(4), "a11".to_string());
+        tree.truncate_depth(2);
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b,c(c1,c2))");
+        assert!(tree.is_removed(8));
+        tree.truncate_depth(1);
+        assert_eq!(tree_to_string(&tree), "root(a,b,c)");
+        assert_eq!((0..9).filter(|&i| tree.is_removed(i)).collect::<Vec<_>>(), [4, 5, 6, 7, 8]);
+        tree.truncate_depth(0);
+        assert_eq!(tree_to_string(&tree), "root");
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn prune() {
+        let mut tree = build_tree();
+        let mut visited = vec![];
+        let count = tree.prune(|item| { visited.push(item.clone()); item.ends_with('2') || item == "c" });
+        assert_eq!(count, 4);
+        assert_eq!(visited, ["root", "a", "a1", "a2", "b", "c"]);
+        assert_eq!(tree_to_string(&tree), "root(a(a1),b)");
+        assert_eq!((0..8).filter(|&i| tree.is_removed(i)).collect::<Vec<_>>(), [3, 5, 6, 7]);
+        assert_eq!(tree.prune(|_| false), 0);
+        assert_eq!(tree.prune(|item| item == "root"), 4);
+        assert_eq!(tree.get_root(), None);
+        assert_eq!(tree.prune(|_| true), 0);
+    }
+
+    #[test]
+    fn sort_children_by() {
+        let mut tree = build_tree();
+        tree.sort_children_by(0, |a, b| b.cmp(a));
+        assert_eq!(tree_to_string(&tree), "root(c(c1,c2),b,a(a1,a2))");
+        tree.sort_all_children_by(|a, b| b.len().cmp(&a.len()).then(b.cmp(a)));
+        assert_eq!(tree_to_string(&tree), "root(c(c2,c1),b,a(a2,a1))");
+        tree.sort_all_children_by(|a, b| a.len().cmp(&b.len()));
+        assert_eq!(tree_to_string(&tree), "root(c(c2,c1),b,a(a2,a1))");
+    }
+
+    #[test]
+    fn reverse_children() {
+        let mut tree = build_tree();
+        tree.reverse_children(0);
+        assert_eq!(tree_to_string(&tree), "root(c(c1,c2),b,a(a1,a2))");
+        tree.reverse_subtree(3);
+        assert_eq!(tree_to_string(&tree), "root(c(c2,c1),b,a(a1,a2))");
+        tree.reverse_all_children();
+        assert_eq!(tree_to_string(&tree), "root(a(a2,a1),b,c(c1,c2))");
+        tree.reverse_subtree(0);
+        assert_eq!(tree_to_string(&tree), "root(c(c2,c1),b,a(a1,a2))");
+    }
+
+    #[test]
+    #[should_panic(expected = "node 3 has already been removed")]
+    fn remove_subtree_twice() {
+        let mut tree = build_tree();
+        tree.remove_subtree(3);
+        tree.remove_subtree(3);
+    }
+}
+
+mod traversal {
+    use super::*;
+
+    #[test]
+    fn children_indices() {
+        let mut tree = build_tree();
+        let weights = [0, 0, 0, 0, 1, 2, 3, 4];
+        let sums = tree.iter_depth().map(|inode| inode.children_indices().iter().map(|&c| weights[c]).sum::<i32>()).collect::<Vec<_>>();
+        assert_eq!(sums, [0, 0, 3, 0, 0, 0, 7, 0]);
+        for mut inode in tree.iter_depth_mut() {
+            let n = inode.children_indices().len();
+            inode.push_str(&n.to_string());
+        }
+        assert_eq!(tree_to_string(&tree), "root3(a2(a10,a20),b0,c2(c10,c20))");
+    }
+
+    #[test]
+    fn num_descendants() {
+        let mut tree = build_tree();
+        assert!(tree.iter_depth().all(|inode| inode.num_descendants().is_none()));
+        tree.compute_descendants();
+        let counts = tree.iter_depth().map(|inode| inode.num_descendants().unwrap()).collect::<Vec<_>>();
+        assert_eq!(counts, [0, 0, 2, 0, 0, 0, 2, 7]);
+        for mut inode in tree.iter_depth_mut() {
+            let n = inode.num_descendants().unwrap();
+            inode.push_str(&n.to_string());
+        }
+        assert_eq!(tree_to_string(&tree), "root7(a2(a10,a20),b0,c2(c10,c20))");
+        assert_eq!(tree.num_descendants(1), Some(2));
+        tree.add(Some(2), "b1".to_string());
+        assert_eq!(tree.num_descendants(1), None);
+        tree.compute_descendants();
+        assert_eq!(tree.num_descendants(0), Some(8));
+    }
+
+    #[test]
+    fn visit_post_order() {
+        let tree = build_tree();
+        let mut result = vec![];
+        tree.visit_post_order(|index, depth, item| result.push((index, depth, item.clone())));
+        let expected = tree.iter_depth_simple().map(|inode| (inode.index, inode.depth, inode.to_string())).collect::<Vec<_>>();
+        assert_eq!(result, expected);
+        result.clear();
+        tree.visit_post_order_at(3, |index, depth, item| result.push((index, depth, item.clone())));
+        let expected = tree.iter_depth_simple_at(3).map(|inode| (inode.index, inode.depth, inode.to_string())).collect::<Vec<_>>();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn reduce_iter() {
+        let tree = build_tree();
+        let mut iter = tree.reduce_iter(|item, children: &[String]| {
+            if children.is_empty() { item.clone() } else { format!("{item}({})", children.join(",")) }
+        });
+        let first = iter.next().unwrap();
+        assert_eq!((first.node.index, first.children().len()), (4, 0));
+        let a = iter.nth(1).unwrap();
+        assert_eq!((a.node.index, a.into_children()), (1, vec!["a1".to_string(), "a2".to_string()]));
+        let result = iter.by_ref().last().unwrap();
+        assert_eq!(result.node.index, 0);
+        assert_eq!(result.children(), ["a(a1,a2)", "b", "c(c1,c2)"]);
+        assert_eq!(iter.into_result(), Some(tree_to_string(&tree)));
+
+        let mut iter = tree.reduce_iter_at(3, |_, children: &[usize]| 1 + children.iter().sum::<usize>());
+        assert_eq!(iter.by_ref().count(), 3);
+        assert_eq!(iter.into_result(), Some(3));
+        let mut iter = tree.reduce_iter_at(3, |_, children: &[usize]| 1 + children.iter().sum::<usize>());
+        iter.next();
+        assert_eq!(iter.into_result(), None);
+    }
+
+    #[test]
+    fn reduce() {
+        let tree = build_tree();
+        let count = tree.reduce(|_| 1, |_, children| 1 + children.iter().sum::<usize>());
+        assert_eq!(count, Some(8));
+        let leaves = tree.reduce(|item| vec![item.clone()], |_, children| children.concat());
+        assert_eq!(leaves.unwrap(), ["a1", "a2", "b", "c1", "c2"]);
+        assert_eq!(VecTree::<String>::new().reduce(|_| 1, |_, _| 0), None);
+    }
+
+    #[test]
+    fn map_reduce() {
+        let tree = build_tree();
+        let (upper, longest) = tree.map_reduce(|item| item.to_uppercase(), 0, |acc, item, _| acc.max(item.len()));
+        assert_eq!(tree_to_string(&upper), "ROOT(A(A1,A2),B,C(C1,C2))");
+        assert_eq!(upper.get_root(), tree.get_root());
+        assert_eq!(longest, 4);
+        let (_, count) = VecTree::<String>::new().map_reduce(|_| (), 0, |acc, _, _| acc + 1);
+        assert_eq!(count, 0);
+
+        let mut tree = VecTree::new();
+        let root = tree.add_root(1);
+        let a = tree.add(Some(root), 10);
+        tree.add(Some(a), 100);
+        tree.remove_subtree(a);
+        let (doubled, total) = tree.map_reduce(|item| item * 2, 0, |acc, item, _| acc + item);
+        assert_eq!(total, 1);
+        assert_eq!((0..3).map(|i| doubled.is_removed(i)).collect::<Vec<_>>(), [false, true, true]);
+        assert_eq!(doubled.iter_depth_simple().map(|n| *n).collect::<Vec<_>>(), [2]);
+    }
+
+    #[test]
+    fn filtered() {
+        let tree = build_tree();
+        let mut tested = vec![];
+        let filtered = tree.filtered(|item| { tested.push(item.clone()); !item.starts_with('a') && *item != "c2" }, OrphanPolicy::Drop);
+        assert_eq!(tree_to_string(&filtered), "root(b,c(c1))");
+        assert_eq!(filtered.len(), 4);
+        assert_eq!(tested, ["root", "c", "c2", "c1", "b", "a"]);
+        let filtered = tree.filtered(|item| item.len() > 1, OrphanPolicy::Hoist);
+        assert_eq!(tree_to_string(&filtered), "root(a1,a2,c1,c2)");
+        let filtered = tree.filtered(|item| *item != "root", OrphanPolicy::Hoist);
+        assert_eq!(filtered.get_root(), None);
+        assert_eq!(filtered.len(), 7);
+        assert_eq!(filtered.iter_depth_simple_at(filtered.len() - 1).map(|n| n.to_string()).collect::<Vec<_>>(), ["c1", "c2", "c"]);
+        assert!(tree.filtered(|item| *item != "root", OrphanPolicy::Drop).is_empty());
+    }
+
+    #[test]
+    fn skeleton() {
+        let tree = build_tree();
+        let skeleton = tree.skeleton();
+        assert_eq!(skeleton.get_root(), tree.get_root());
+        let shape = skeleton.iter_depth().map(|n| (n.index, n.depth, n.num_children())).collect::<Vec<_>>();
+        assert_eq!(shape, tree.iter_depth().map(|n| (n.index, n.depth, n.num_children())).collect::<Vec<_>>());
+        assert!(VecTree::<String>::new().skeleton().is_empty());
+
+        let mut tree = build_tree();
+        tree.remove_subtree(1);
+        let skeleton = tree.skeleton();
+        assert_eq!((0..skeleton.len()).filter(|&i| skeleton.is_removed(i)).collect::<Vec<_>>(), [1, 4, 5]);
+    }
+
+    #[test]
+    fn same_shape() {
+        let tree = build_tree();
+        let mut other = tree.skeleton();
+        assert!(tree.same_shape(&other));
+        other.children_mut(3).swap(0, 1);
+        assert!(tree.same_shape(&other));
+        other.children_mut(0).swap(0, 2);
+        assert!(tree.same_shape(&other));
+        other.children_mut(0).swap(0, 1);
+        assert!(!tree.same_shape(&other));
+        assert!(!tree.same_shape(&VecTree::<()>::new()));
+        assert!(VecTree::<i32>::new().same_shape(&VecTree::<()>::new()));
+    }
+
+    #[test]
+    fn iter_children() {
+        let tree = build_tree();
+        let children = tree.iter_children(1).map(|(index, item)| (index, item.clone(), tree.children(index).len())).collect::<Vec<_>>();
+        assert_eq!(children, [(4, "a1".to_string(), 0), (5, "a2".to_string(), 0)]);
+        assert_eq!(tree.iter_children(0).rev().map(|(index, _)| index).collect::<Vec<_>>(), [3, 2, 1]);
+    }
+
+    #[test]
+    fn siblings_of() {
+        let tree = build_tree();
+        assert_eq!(tree.siblings_of(2).collect::<Vec<_>>(), [1, 3]);
+        assert_eq!(tree.siblings_of(7).rev().collect::<Vec<_>>(), [6]);
+        assert_eq!(tree.siblings_of(0).count(), 0);
+    }
+
+    #[test]
+    fn iter_children_mut() {
+        let mut tree = build_tree();
+        for item in tree.iter_children_mut(3).rev() {
+            item.push('!');
+        }
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b,c(c1!,c2!))");
+        assert_eq!(tree.iter_children_mut(2).count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "node 0 has duplicate children")]
+    fn iter_children_mut_duplicate() {
+        let mut tree = build_tree();
+        tree.children_mut(0).push(1);
+        tree.iter_children_mut(0).for_each(|item| item.push('!'));
+    }
+
+    #[test]
+    fn subtree_aggregate() {
+        let mut tree = build_tree();
+        let mut lengths = SubtreeAggregate::new(&mut tree, |item: &String, children: &[usize]| item.len() + children.iter().sum::<usize>());
+        assert_eq!(lengths.get(0), Some(&15));
+        assert_eq!(lengths.get(3), Some(&5));
+        tree.get_mut(6).push_str("..");
+        tree.get_mut(2).push('.');
+        lengths.update(&mut tree);
+        assert_eq!((lengths.get(6), lengths.get(3), lengths.get(2), lengths.get(1)), (Some(&4), Some(&7), Some(&2), Some(&5)));
+        assert_eq!(lengths.get(0), Some(&18));
+        tree.add(Some(1), "a3".to_string());
+        let orphan = tree.add(None, "orphan".to_string());
+        lengths.update(&mut tree);
+        assert_eq!(lengths.get(1), Some(&7));
+        assert_eq!(lengths.get(0), Some(&20));
+        assert_eq!(lengths.get(orphan), None);
+        tree.get_mut(orphan).push('!');
+        lengths.update(&mut tree);
+        assert_eq!(lengths.get(0), Some(&20));
+    }
+
+    #[test]
+    fn subtree_aggregate_structure() {
+        let mut tree = build_tree();
+        let calls = std::cell::Cell::new(0);
+        let mut lengths = SubtreeAggregate::new(&mut tree, |item: &String, children: &[usize]| {
+            calls.set(calls.get() + 1);
+            item.len() + children.iter().sum::<usize>()
+        });
+        assert_eq!((lengths.get(0), calls.replace(0)), (Some(&15), 8));
+        let a3 = tree.add(Some(1), "a3".to_string());
+        lengths.update(&mut tree);
+        assert_eq!((lengths.get(a3), lengths.get(1), lengths.get(0)), (Some(&2), Some(&7), Some(&17)));
+        assert_eq!(calls.replace(0), 3);
+        tree.detach_child(0, 3);
+        lengths.update(&mut tree);
+        assert_eq!((lengths.get(0), lengths.get(3), lengths.get(6)), (Some(&12), None, None));
+        assert_eq!(calls.replace(0), 1);
+        tree.attach_child(2, 3);
+        lengths.update(&mut tree);
+        assert_eq!((lengths.get(0), lengths.get(2), lengths.get(3)), (Some(&17), Some(&6), Some(&5)));
+        assert_eq!(calls.replace(0), 5);
+        tree.reverse_children(0);
+        tree.get_mut(4).push('!');
+        lengths.update(&mut tree);
+        assert_eq!((lengths.get(0), lengths.get(1)), (Some(&18), Some(&8)));
+        assert_eq!(calls.replace(0), 3);
+        // the nodes are moved, so all the values are computed again
+        tree.renumber();
+        lengths.update(&mut tree);
+        assert_eq!((lengths.get(0), calls.replace(0)), (Some(&18), 9));
+    }
+
+    #[test]
+    fn evaluate_synthesized() {
+        let mut tree = build_tree();
+        let orphan = tree.add(None, "orphan".to_string());
+        // (height, leaves)
+        let attrs = tree.evaluate_synthesized(|_: &String, children: &[&(u32, usize)]| {
+            let height = children.iter().map(|c| c.0 + 1).max().unwrap_or(0);
+            (height, children.iter().map(|c| c.1).sum::<usize>().max(1))
+        });
+        assert_eq!(attrs[0], (2, 5));
+        assert_eq!(attrs.get(3), Some(&(1, 2)));
+        assert_eq!(attrs.get(orphan), None);
+        assert_eq!(attrs.iter().map(|(index, _)| index).collect::<Vec<_>>(), [0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(attrs.into_vec().len(), 9);
+    }
+
+    #[test]
+    fn evaluate_inherited() {
+        let mut tree = build_tree();
+        let orphan = tree.add(None, "orphan".to_string());
+        let paths = tree.evaluate_inherited(|item: &String, parent: Option<&String>| {
+            parent.map_or_else(|| item.clone(), |path| format!("{path}/{item}"))
+        });
+        assert_eq!(paths[7], "root/c/c2");
+        assert_eq!(paths.get(orphan), None);
+        assert!(VecTree::<String>::new().evaluate_inherited(|_: &String, _: Option<&()>| ()).iter().next().is_none());
+        // longest path below each node
+        let (_, longest) = tree.evaluate_attributes(
+            |item: &String, parent: Option<&String>| parent.map_or_else(|| item.clone(), |path| format!("{path}/{item}")),
+            |_, path, children: &[&String]| children.iter().map(|c| c.to_string()).max_by_key(|c| c.len()).unwrap_or(path.clone()));
+        assert_eq!(longest[0], "root/c/c2");
+        assert_eq!(longest[1], "root/a/a2");
+        assert_eq!(longest[2], "root/b");
+    }
+
+    #[test]
+    fn walk_recursive() {
+        let tree = build_tree();
+        let text = tree.walk_recursive(|item, children: Vec<String>| {
+            if children.is_empty() { item.clone() } else { format!("{item}({})", children.join(",")) }
+        });
+        assert_eq!(text.unwrap(), tree_to_string(&tree));
+        assert_eq!(tree.walk_recursive_at(1, |_, children: Vec<usize>| 1 + children.iter().sum::<usize>()), 3);
+        assert_eq!(VecTree::<i32>::new().walk_recursive(|_, _: Vec<()>| ()), None);
+    }
+
+    #[test]
+    fn from_recursive() {
+        struct Dir { name: &'static str, entries: Vec<Dir> }
+        let dir = Dir { name: "root", entries: vec![
+            Dir { name: "a", entries: vec![Dir { name: "a1", entries: vec![] }, Dir { name: "a2", entries: vec![] }] },
+            Dir { name: "b", entries: vec![] },
+        ]};
+        let tree = VecTree::from_recursive(dir, |dir| (dir.name.to_string(), dir.entries));
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),b)");
+        // 1M-deep linked list
+        const DEPTH: u32 = 1_000_000;
+        let tree = VecTree::from_recursive(0, |i| (i, if i + 1 < DEPTH { vec![i + 1] } else { vec![] }));
+        assert_eq!(tree.len(), DEPTH as usize);
+        assert_eq!(tree.iter_depth_simple().next().map(|inode| inode.depth), Some(DEPTH - 1));
+    }
+
+    #[test]
+    fn deep_tree() {
+        const DEPTH: usize = 100_000;
+        let mut tree = VecTree::new();
+        let mut parent = tree.add_root(0);
+        for i in 1..DEPTH {
+            parent = tree.add(Some(parent), i);
+        }
+        let height = tree.walk_recursive(|_, children: Vec<usize>| 1 + children.into_iter().max().unwrap_or(0));
+        assert_eq!(height, Some(DEPTH));
+        let clone = tree.clone();
+        assert_eq!(clone.iter_depth_simple().count(), DEPTH);
+        drop(tree);
+        let subtree = (1..DEPTH).fold(SubTree::leaf(0), |child, i| SubTree::new(i, vec![child]));
+        let mut tree = VecTree::new();
+        tree.add_subtree(None, subtree);
+        assert_eq!(tree.len(), DEPTH);
+    }
+
+    #[test]
+    fn lazy_tree() {
+        let mut calls = 0;
+        let mut tree = LazyVecTree::new("r".to_string(), |name: &String| {
+            calls += 1;
+            if name.len() < 3 { vec![format!("{name}a"), format!("{name}b")] } else { vec![] }
+        });
+        assert!(!tree.is_expanded(0));
+        assert_eq!(tree.expand(0), &[1, 2]);
+        assert_eq!(tree.expand(0), &[1, 2]);
+        let mut names = vec![];
+        tree.walk(1, |_, _, name| names.push(name.clone()));
+        assert_eq!(names, ["ra", "rb", "r"]);
+        names.clear();
+        tree.walk(5, |_, _, name| names.push(name.clone()));
+        assert_eq!(names, ["raa", "rab", "ra", "rba", "rbb", "rb", "r"]);
+        let tree = tree.into_tree();
+        assert_eq!(tree_to_string(&tree), "r(ra(raa,rab),rb(rba,rbb))");
+        assert_eq!(calls, 7);
+    }
+
+    #[test]
+    fn peek() {
+        let mut tree = build_tree();
+        let mut iter = tree.iter_depth_mut();
+        loop {
+            let peeked = iter.peek();
+            let next = iter.next();
+            assert_eq!(peeked, next.as_ref().map(|inode| (inode.index, inode.depth)));
+            if next.is_none() {
+                break;
+            }
+        }
+        assert!(iter.peek().is_none());
+    }
+
+    #[test]
+    fn checkpoint() {
+        let mut tree = build_tree();
+        let mut iter = tree.iter_depth_at(0);
+        iter.nth(3);
+        let checkpoint = iter.checkpoint();
+        let expected = iter.map(|inode| format!("{}:{}", inode.depth, *inode)).collect::<Vec<_>>();
+        tree.get_mut(3).push('!');
+        let rest = tree.iter_depth_at(0).resume(checkpoint.clone()).map(|inode| format!("{}:{}", inode.depth, *inode)).collect::<Vec<_>>();
+        assert_eq!(expected, ["2:c1", "2:c2", "1:c", "0:root"]);
+        assert_eq!(rest, ["2:c1", "2:c2", "1:c!", "0:root"]);
+        let rest = tree.iter_depth_simple().resume(checkpoint).map(|inode| inode.index).collect::<Vec<_>>();
+        assert_eq!(rest, [6, 7, 3, 0]);
+    }
+
+    #[test]
+    fn clone_iterator() {
+        let tree = build_tree();
+        let mut iter = tree.iter_depth();
+        iter.nth(2);
+        let fork = iter.clone();
+        let rest = iter.map(|inode| inode.to_string()).collect::<Vec<_>>();
+        assert_eq!(rest, ["b", "c1", "c2", "c", "root"]);
+        let rest_fork = fork.map(|inode| format!("{}:{}", inode.depth, *inode)).collect::<Vec<_>>();
+        assert_eq!(rest_fork, ["1:b", "2:c1", "2:c2", "1:c", "0:root"]);
+    }
+
+    #[test]
+    fn double_ended() {
+        let tree = build_tree();
+        let result = tree.iter_depth().rev().map(|inode| format!("{}:{}", *inode, inode.depth)).collect::<Vec<_>>().join(",");
+        assert_eq!(result, "root:0,c:1,c2:2,c1:2,b:1,a:1,a2:2,a1:2");
+        let result = tree.iter_depth_simple_at(1).rev().map(|inode| inode.index).collect::<Vec<_>>();
+        assert_eq!(result, [1, 5, 4]);
+        let result = tree.iter_descendants(3).rev().map(|inode| (inode.index, inode.depth)).collect::<Vec<_>>();
+        assert_eq!(result, [(7, 1), (6, 1)]);
+        // both ends
+        let mut iter = tree.iter_depth();
+        let mut front = vec![];
+        let mut back = vec![];
+        while let Some(inode) = iter.next() {
+            front.push(inode.to_string());
+            if let Some(inode) = iter.next_back() {
+                back.push(inode.to_string());
+            }
+        }
+        assert_eq!(front, ["a1", "a2", "a", "b"]);
+        assert_eq!(back, ["root", "c", "c2", "c1"]);
+        assert!(iter.next_back().is_none());
+        let mut iter = tree.iter_depth_simple();
+        iter.nth(2);
+        iter.next_back();
+        assert_eq!(iter.size_hint(), (4, Some(4)));
+        assert_eq!(iter.clone().count(), 4);
+        assert_eq!(iter.clone().last().map(|inode| inode.index), Some(3));
+        assert_eq!(iter.peek(), Some((2, 1)));
+        iter.nth(3);
+        assert_eq!(iter.peek(), None);
+        assert!(VecTree::<i32>::new().iter_depth().next_back().is_none());
+    }
+
+    #[test]
+    fn iterator_traits() {
+        let mut tree = build_tree();
+        assert_eq!(tree.iter_depth_simple().count(), 8);
+        assert_eq!(tree.iter_depth_at(3).count(), 3);
+        assert_eq!(tree.iter_depth_mut().last().map(|inode| inode.to_string()), Some("root".to_string()));
+        let mut iter = tree.iter_depth_simple();
+        assert_eq!(iter.size_hint(), (1, None));
+        assert_eq!(iter.next().map(|inode| inode.index), Some(4));
+        assert_eq!(iter.size_hint(), (5, None));
+        let last = iter.last().unwrap();
+        assert_eq!((last.index, last.depth), (0, 0));
+        let mut iter = tree.iter_depth_simple_at(2);
+        assert!(format!("{iter:?}").starts_with("VecTreePoDfsIter"));
+        assert_eq!(iter.next().map(|inode| inode.index), Some(2));
+        assert!(iter.next().is_none());
+        assert!(iter.next().is_none());
+        assert!(iter.last().is_none());
+    }
+
+    #[test]
+    fn with_indices() {
+        let mut tree = build_tree();
+        let pairs = tree.iter_depth_simple_at(1).with_indices().collect::<Vec<_>>();
+        assert_eq!(pairs, [(4, &"a1".to_string()), (5, &"a2".to_string()), (1, &"a".to_string())]);
+        let mut refs = tree.iter_depth_simple_mut().with_indices().collect::<Vec<_>>();
+        refs[0].1.push('!');
+        *refs[7].1 = "ROOT".to_string();
+        assert_eq!(tree_to_string(&tree), "ROOT(a(a1!,a2),b,c(c1,c2))");
+    }
+
+    #[test]
+    fn post_order_cache() {
+        let mut tree = build_tree();
+        let expected = tree.iter_depth_simple().map(|inode| (inode.index, inode.depth)).collect::<Vec<_>>();
+        assert_eq!(tree.post_order(), expected);
+        let names = tree.iter_depth_simple_cached().map(|inode| inode.to_string()).collect::<Vec<_>>();
+        assert_eq!(names, ["a1", "a2", "a", "b", "c1", "c2", "c", "root"]);
+        // modifying the data keeps the cache:
+        *tree.get_mut(2) = "B".to_string();
+        assert_eq!(tree.post_order(), expected);
+        // modifying the structure invalidates it:
+        let d = tree.add(Some(2), "d".to_string());
+        assert_eq!(tree_to_string(&tree), "root(a(a1,a2),B(d),c(c1,c2))");
+        assert_eq!(tree.post_order()[3], (d, 2));
+        tree.children_mut(0).pop();
+        assert_eq!(tree.post_order().len(), 6);
+        tree.set_root(3);
+        assert_eq!(tree.post_order(), &[(6, 1), (7, 1), (3, 0)]);
+        tree.clear();
+        assert!(tree.post_order().is_empty());
+    }
+
+    #[test]
+    fn try_walk() {
+        let tree = build_tree();
+        let mut visited = vec![];
+        let result = tree.try_walk(|inode| {
+            visited.push(inode.index);
+            if inode.num_children() > 1 { ControlFlow::Break(inode.to_string()) } else { ControlFlow::Continue(()) }
+        });
+        assert_eq!(result, ControlFlow::Break("a".to_string()));
+        assert_eq!(visited, [4, 5, 1]);
+        assert_eq!(tree.try_walk_at(3, |_| ControlFlow::<()>::Continue(())), ControlFlow::Continue(()));
+        let result = tree.iter_depth_at(3).last().unwrap().try_walk(|inode| {
+            if inode.starts_with("c2") { ControlFlow::Break(inode.index) } else { ControlFlow::Continue(()) }
+        });
+        assert_eq!(result, ControlFlow::Break(7));
+    }
+
+    #[test]
+    fn try_walk_mut() {
+        let mut tree = build_tree();
+        let result = tree.try_walk_mut(|mut inode| {
+            if inode.index == 2 {
+                return ControlFlow::Break(inode.depth);
+            }
+            *inode = inode.to_uppercase();
+            ControlFlow::Continue(())
+        });
+        assert_eq!(result, ControlFlow::Break(1));
+        assert_eq!(tree_to_string(&tree), "root(A(A1,A2),b,c(c1,c2))");
+        let result = tree.try_walk_at_mut(3, |mut inode| {
+            *inode = inode.to_uppercase();
+            ControlFlow::<()>::Continue(())
+        });
+        assert_eq!(result, ControlFlow::Continue(()));
+        assert_eq!(tree_to_string(&tree), "root(A(A1,A2),b,C(C1,C2))");
+        assert_eq!(tree.borrows.get(), 0);
+    }
+
+    #[test]
+    fn try_for_each() {
+        let mut tree = build_tree();
+        let mut n = 0;
+        let result = tree.try_for_each(|inode| {
+            n += 1;
+            if inode.depth == 1 { Err(inode.index) } else { Ok(()) }
+        });
+        assert_eq!(result, Err(1));
+        assert_eq!(n, 3);
+        let result = tree.try_for_each_mut(|mut inode| {
+            inode.push('!');
+            Ok::<(), ()>(())
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(tree_to_string(&tree), "root!(a!(a1!,a2!),b!,c!(c1!,c2!))");
+    }
+
+    #[test]
+    fn for_each() {
+        let mut tree = build_tree();
+        let mut result = vec![];
+        tree.for_each(|index, depth, item| result.push(format!("{index}:{depth}:{item}")));
+        assert_eq!(result, ["4:2:a1", "5:2:a2", "1:1:a", "2:1:b", "6:2:c1", "7:2:c2", "3:1:c", "0:0:root"]);
+        result.clear();
+        tree.for_each_at(3, |index, depth, item| result.push(format!("{index}:{depth}:{item}")));
+        assert_eq!(result, ["6:1:c1", "7:1:c2", "3:0:c"]);
+        tree.for_each_mut(|_, depth, item| if depth == 1 { *item = item.to_uppercase() });
+        assert_eq!(tree_to_string(&tree), "root(A(a1,a2),B,C(c1,c2))");
+        tree.for_each_at_mut(1, |index, _, item| item.push_str(&index.to_string()));
+        assert_eq!(tree_to_string(&tree), "root(A1(a14,a25),B,C(c1,c2))");
+    }
+
+    #[test]
+    fn any_all_count_in_subtree() {
+        let tree = build_tree();
+        let mut n = 0;
+        assert!(tree.any_in_subtree(0, |s| { n += 1; s == "a" }));
+        assert_eq!(n, 3);
+        assert!(!tree.any_in_subtree(3, |s| s.starts_with('a')));
+        assert!(tree.all_in_subtree(3, |s| s.starts_with('c')));
+        n = 0;
+        assert!(!tree.all_in_subtree(0, |s| { n += 1; s.len() > 1 }));
+        assert_eq!(n, 3);
+        assert_eq!(tree.count_in_subtree(0, |s| s.len() == 2), 4);
+        assert_eq!(tree.count_in_subtree(1, |s| s.starts_with('a')), 3);
+        assert_eq!(tree.count_in_subtree(2, |_| true), 1);
+    }
+
+    #[test]
+    fn aggregate() {
+        let tree = build_tree();
+        assert_eq!(tree.aggregate(0, 0, |acc, s| acc + s.len()), 15);
+        assert_eq!(tree.aggregate(3, String::new(), |acc, s| acc + s), "c1c2c");
+        assert_eq!(tree.max_by_in_subtree(0, |a, b| a.len().cmp(&b.len())), Some(0));
+        assert_eq!(tree.max_by_in_subtree(1, |a, b| a.cmp(b)), Some(5));
+        assert_eq!(tree.min_by_in_subtree(0, |a, b| a.len().cmp(&b.len())), Some(1));
+        assert_eq!(tree.min_by_in_subtree(3, |a, b| a.cmp(b)), Some(3));
+        let mut tree = tree;
+        tree.remove_subtree(3);
+        assert_eq!(tree.max_by_in_subtree(3, |a, b| a.cmp(b)), None);
+        assert_eq!(tree.min_by_in_subtree(6, |a, b| a.cmp(b)), None);
+        assert_eq!(tree.max_by_in_subtree(0, |a, b| a.cmp(b)), Some(0));
+    }
+
+    #[test]
+    fn find_path() {
+        let tree = build_tree();
+        assert_eq!(tree.find_path(|s| s == "c2"), Some(vec![0, 3, 7]));
+        assert_eq!(tree.find_path(|s| s.starts_with('c')), Some(vec![0, 3, 6]));
+        assert_eq!(tree.find_path(|s| s == "b"), Some(vec![0, 2]));
+        assert_eq!(tree.find_path(|s| s == "root"), Some(vec![0]));
+        assert_eq!(tree.find_path(|s| s == "d"), None);
+        assert_eq!(VecTree::<String>::new().find_path(|_| true), None);
+    }
+
+    #[test]
+    fn path_between() {
+        let mut tree = build_tree();
+        assert_eq!(tree.path_between(4, 7), Some(vec![4, 1, 0, 3, 7]));
+        assert_eq!(tree.path_between(7, 4), Some(vec![7, 3, 0, 1, 4]));
+        assert_eq!(tree.path_between(6, 7), Some(vec![6, 3, 7]));
+        assert_eq!(tree.path_between(0, 5), Some(vec![0, 1, 5]));
+        assert_eq!(tree.path_between(2, 2), Some(vec![2]));
+        let loose = tree.add(None, "x".to_string());
+        assert_eq!(tree.path_between(loose, 2), None);
+    }
+
+    #[test]
+    fn depth_delta() {
+        let tree = build_tree();
+        let mut iter = tree.iter_depth();
+        let mut result = vec![];
+        while let Some(inode) = iter.next() {
+            result.push(iter.depth_delta());
+            assert_eq!(inode.depth as i32, result.iter().sum::<i32>());
+        }
+        assert_eq!(result, [2, 0, -1, 0, 1, 0, -1, -1]);
+
+        // emits the tree with closing brackets in a single streamed pass
+        let mut iter = tree.iter_depth_simple();
+        let mut text = String::new();
+        while let Some(inode) = iter.next() {
+            let delta = iter.depth_delta();
+            for _ in 0..delta {
+                text.push('[');
+            }
+            for _ in delta..0 {
+                text.push(']');
+            }
+            text.push_str(&inode);
+            text.push(' ');
+        }
+        assert_eq!(text, "[[a1 a2 ]a b [c1 c2 ]c ]root ");
+    }
+
+    #[test]
+    fn iter_traverse() {
+        let mut tree = build_tree();
+        let visit = |tree: &VecTree<String>, top: Option<usize>, order: Traversal| {
+            let iter = match top {
+                Some(top) => tree.iter_traverse_at(top, order),
+                None => tree.iter_traverse(order)
+            };
+            iter.map(|inode| format!("{}:{}", *inode, inode.depth)).collect::<Vec<_>>().join(",")
+        };
+        assert_eq!(visit(&tree, None, Traversal::PreOrder), "root:0,a:1,a1:2,a2:2,b:1,c:1,c1:2,c2:2");
+        assert_eq!(visit(&tree, None, Traversal::PostOrder), "a1:2,a2:2,a:1,b:1,c1:2,c2:2,c:1,root:0");
+        assert_eq!(visit(&tree, None, Traversal::BreadthFirst), "root:0,a:1,b:1,c:1,a1:2,a2:2,c1:2,c2:2");
+        assert_eq!(visit(&tree, Some(3), Traversal::PreOrder), "c:0,c1:1,c2:1");
+        assert_eq!(visit(&tree, Some(3), Traversal::BreadthFirst), "c:0,c1:1,c2:1");
+        let post_order = tree.iter_depth().map(|inode| inode.index).collect::<Vec<_>>();
+        assert_eq!(tree.iter_traverse(Traversal::PostOrder).map(|inode| inode.index).collect::<Vec<_>>(), post_order);
+        assert_eq!(tree.iter_traverse(Traversal::BreadthFirst).count(), 8);
+        for mut inode in tree.iter_traverse_mut(Traversal::BreadthFirst) {
+            let depth = inode.depth.to_string();
+            inode.push_str(&depth);
+        }
+        assert_eq!(tree_to_string(&tree), "root0(a1(a12,a22),b1,c1(c12,c22))");
+        for mut inode in tree.iter_traverse_at_mut(1, Traversal::PreOrder) {
+            inode.make_ascii_uppercase();
+        }
+        assert_eq!(tree_to_string(&tree), "root0(A1(A12,A22),b1,c1(c12,c22))");
+        assert_eq!(VecTree::<i32>::new().iter_traverse(Traversal::PreOrder).count(), 0);
+    }
+
+    #[test]
+    fn iter_breadth() {
+        let tree = build_tree();
+        let result = tree.iter_breadth().map(|inode| format!("{}:{}", *inode, inode.depth)).collect::<Vec<_>>().join(",");
+        assert_eq!(result, "root:0,a:1,b:1,c:1,a1:2,a2:2,c1:2,c2:2");
+        let result = tree.iter_breadth_at(1).map(|inode| format!("{}:{}", *inode, inode.depth)).collect::<Vec<_>>().join(",");
+        assert_eq!(result, "a:0,a1:1,a2:1");
+        // the proxies give access to the children, like those of iter_depth()
+        let leaves = tree.iter_breadth().filter(|inode| inode.iter_children().next().is_none()).map(|inode| inode.index).collect::<Vec<_>>();
+        assert_eq!(leaves, [2, 4, 5, 6, 7]);
+        assert_eq!(VecTree::<i32>::new().iter_breadth().count(), 0);
+    }
+
+    #[test]
+    fn walk() {
+        let tree = build_tree();
+        let visit = |mut walker: TreeWalker<'_, String>, skipped: &str| {
+            let mut visited = vec![];
+            while let Some(inode) = walker.next() {
+                if *inode == skipped {
+                    walker.skip_children();
+                    // a second call has no effect
+                    walker.skip_children();
+                }
+                visited.push(format!("{}:{}", *inode, inode.depth));
+            }
+            visited.join(",")
+        };
+        assert_eq!(visit(tree.walk(), "a"), "root:0,a:1,b:1,c:1,c1:2,c2:2");
+        assert_eq!(visit(tree.walk(), "c"), "root:0,a:1,a1:2,a2:2,b:1,c:1");
+        assert_eq!(visit(tree.walk(), "root"), "root:0");
+        assert_eq!(visit(tree.walk(), "b"), "root:0,a:1,a1:2,a2:2,b:1,c:1,c1:2,c2:2");
+        assert_eq!(visit(tree.walk_at(3), "c1"), "c:0,c1:1,c2:1");
+        assert_eq!(visit(tree.iter_traverse(Traversal::BreadthFirst), "c"), "root:0,a:1,b:1,c:1,a1:2,a2:2");
+        assert_eq!(visit(tree.iter_traverse(Traversal::PostOrder), "a"), "a1:2,a2:2,a:1,b:1,c1:2,c2:2,c:1,root:0");
+    }
+
+    #[test]
+    fn visit() {
+        let tree = build_tree();
+        let mut events = vec![];
+        tree.visit(|event| events.push(match event {
+            VisitEvent::Enter(inode) => format!("+{}:{}", *inode, inode.depth),
+            VisitEvent::Leave(inode) => format!("-{}", *inode),
+        }));
+        assert_eq!(events.join(","), "+root:0,+a:1,+a1:2,-a1,+a2:2,-a2,-a,+b:1,-b,+c:1,+c1:2,-c1,+c2:2,-c2,-c,-root");
+        events.clear();
+        tree.visit_at(3, |event| if let VisitEvent::Leave(inode) = event {
+            events.push(format!("{}:{}", inode.index, inode.depth));
+        });
+        assert_eq!(events.join(","), "6:1,7:1,3:0");
+        VecTree::<i32>::new().visit(|_| panic!("no node to visit"));
+    }
+
+    #[test]
+    fn iter_depth_with_parent() {
+        let tree = build_tree();
+        let parents = tree.iter_depth_with_parent().map(|(parent, inode)| (inode.index, parent)).collect::<Vec<_>>();
+        assert_eq!(parents, [(4, Some(1)), (5, Some(1)), (1, Some(0)), (2, Some(0)), (6, Some(3)), (7, Some(3)), (3, Some(0)), (0, None)]);
+        for (parent, inode) in tree.iter_depth_with_parent() {
+            assert_eq!(parent, tree.find_parent(inode.index));
+        }
+        let parents = tree.iter_depth_with_parent_at(3).map(|(parent, inode)| (inode.index, parent)).collect::<Vec<_>>();
+        assert_eq!(parents, [(6, Some(3)), (7, Some(3)), (3, None)]);
+        let mut iter = tree.iter_descendants(3);
+        iter.next();
+        assert_eq!(iter.parent(), Some(3));
+        iter.next();
+        assert_eq!(iter.parent(), Some(3));
+    }
+
+    #[test]
+    fn iter_euler() {
+        let tree = build_tree();
+        let tour = tree.iter_euler().map(|inode| format!("{}:{}", *inode, inode.depth)).collect::<Vec<_>>().join(",");
+        assert_eq!(tour, "root:0,a:1,a1:2,a:1,a2:2,a:1,root:0,b:1,root:0,c:1,c1:2,c:1,c2:2,c:1,root:0");
+        assert_eq!(tree.iter_euler().count(), 2 * tree.len() - 1);
+        let tour = tree.iter_euler_at(3).map(|inode| inode.index).collect::<Vec<_>>();
+        assert_eq!(tour, [3, 6, 3, 7, 3]);
+        assert_eq!(tree.iter_euler_at(2).map(|inode| inode.index).collect::<Vec<_>>(), [2]);
+        let mut iter = tree.iter_euler();
+        iter.nth(3);
+        assert_eq!(iter.size_hint(), (1, None));
+        assert_eq!(iter.clone().count(), 11);
+        assert_eq!(VecTree::<i32>::new().iter_euler().next().map(|inode| inode.index), None);
+    }
+
+    #[test]
+    fn iter_descendants() {
+        let tree = build_tree();
+        let visit = |top: usize| {
+            tree.iter_descendants(top).map(|inode| format!("{}:{}", *inode, inode.depth)).collect::<Vec<_>>().join(",")
+        };
+        assert_eq!(visit(0), "a1:2,a2:2,a:1,b:1,c1:2,c2:2,c:1");
+        assert_eq!(visit(3), "c1:1,c2:1");
+        assert_eq!(visit(2), "");
+        let mut iter = tree.iter_descendants(0);
+        assert_eq!(iter.peek(), Some((4, 2)));
+        assert_eq!(iter.next().map(|inode| iter.depth_delta() + inode.index as i32), Some(6));
+        assert_eq!(iter.size_hint(), (4, None));
+        assert_eq!(iter.clone().last().map(|inode| (inode.index, inode.depth)), Some((3, 1)));
+        assert_eq!(iter.count(), 6);
+    }
+
+    #[test]
+    fn iter_at_depth() {
+        let mut tree = build_tree();
+        tree.add(Some(4), "a11".to_string());
+        let visit = |tree: &VecTree<String>, depth: u32| {
+            tree.iter_at_depth(depth).map(|inode| format!("{}:{}", *inode, inode.depth)).collect::<Vec<_>>().join(",")
+        };
+        assert_eq!(visit(&tree, 0), "root:0");
+        assert_eq!(visit(&tree, 1), "a:1,b:1,c:1");
+        assert_eq!(visit(&tree, 2), "a1:2,a2:2,c1:2,c2:2");
+        assert_eq!(visit(&tree, 3), "a11:3");
+        assert_eq!(visit(&tree, 4), "");
+        for mut inode in tree.iter_at_depth_mut(1) {
+            let n = inode.iter_children().count();
+            inode.push_str(&n.to_string());
+        }
+        assert_eq!(tree_to_string(&tree), "root(a2(a1(a11),a2),b0,c2(c1,c2))");
+        assert_eq!(VecTree::<i32>::new().iter_at_depth(0).count(), 0);
+    }
+
+    #[test]
+    fn iter_breadth_mut() {
+        let mut tree = build_tree();
+        for (i, mut inode) in tree.iter_breadth_mut().enumerate() {
+            let suffix = format!("{}.{i}", inode.depth);
+            inode.push_str(&suffix);
+        }
+        assert_eq!(tree_to_string(&tree), "root0.0(a1.1(a12.4,a22.5),b1.2,c1.3(c12.6,c22.7))");
+        for mut inode in tree.iter_breadth_at_mut(3) {
+            let len = if inode.depth == 0 { 1 } else { 2 };
+            inode.truncate(len);
+        }
+        assert_eq!(tree_to_string(&tree), "root0.0(a1.1(a12.4,a22.5),b1.2,c(c1,c2))");
+    }
+}
+
+mod rows {
+    use super::*;
+
+    #[test]
+    fn flatten_visible() {
+        let tree = build_tree();
+        let rows = tree.flatten_visible(|_| true);
+        assert_eq!(rows.iter().map(|row| row.index).collect::<Vec<_>>(), [0, 1, 4, 5, 2, 3, 6, 7]);
+        assert_eq!(rows.iter().map(|row| row.depth).collect::<Vec<_>>(), [0, 1, 2, 2, 1, 1, 2, 2]);
+        assert_eq!(rows.iter().filter(|row| row.is_last_child).map(|row| row.index).collect::<Vec<_>>(), [0, 5, 3, 7]);
+        assert_eq!(rows.iter().filter(|row| row.has_children).map(|row| row.index).collect::<Vec<_>>(), [0, 1, 3]);
+        let rows = tree.flatten_visible(|index| index != 1);
+        assert_eq!(rows.iter().map(|row| row.index).collect::<Vec<_>>(), [0, 1, 2, 3, 6, 7]);
+        assert_eq!(tree.flatten_visible(|_| false).len(), 1);
+        assert!(VecTree::<String>::new().flatten_visible(|_| true).is_empty());
+    }
+
+    #[test]
+    fn iter_rows_range() {
+        let tree = build_tree();
+        let all = tree.flatten_visible(|index| index != 1);
+        for start in 0..=all.len() {
+            for len in 0..=all.len() - start {
+                let window = tree.iter_rows_range(|index| index != 1, start, len).collect::<Vec<_>>();
+                assert_eq!(window, &all[start..start + len], "start {start}, len {len}");
+            }
+        }
+        assert_eq!(tree.iter_rows_range(|_| true, 6, 10).count(), 2);
+        assert_eq!(tree.iter_rows_range(|_| true, 10, 10).count(), 0);
+    }
+
+    #[test]
+    fn expansion_state() {
+        let mut tree = build_tree();
+        assert_eq!(tree.visible_rows().len(), 1);
+        assert!(tree.toggle(0));
+        tree.set_expanded(3, true);
+        assert_eq!(tree.visible_rows(), tree.flatten_visible(|index| index == 0 || index == 3));
+        assert_eq!(tree.num_visible_rows(), 6);
+        tree.set_expanded(1, true);
+        let all = tree.visible_rows();
+        assert_eq!(all.len(), 8);
+        for start in 0..=all.len() {
+            for len in 0..=all.len() - start {
+                assert_eq!(tree.iter_visible_rows(start, len).collect::<Vec<_>>(), &all[start..start + len], "start {start}, len {len}");
+            }
+        }
+        assert_eq!(tree.iter_visible_rows(9, 2).count(), 0);
+        // the state follows the nodes when they're moved
+        tree.apply_permutation(&[0, 3, 2, 1, 4, 5, 6, 7]).unwrap();
+        assert!(tree.is_expanded(1) && tree.is_expanded(3));
+        tree.add(Some(1), "c3".to_string());
+        assert_eq!(tree.num_visible_rows(), 9);
+        assert!(!tree.toggle(1));
+        assert_eq!(tree.num_visible_rows(), 6);
+        tree.collapse_all_below(0);
+        assert!((0..tree.len()).all(|index| !tree.is_expanded(index)));
+        assert_eq!(tree.iter_visible_rows(0, 10).count(), 1);
+    }
+}
+
+#[cfg(feature = "futures")]
+mod stream {
+    use super::*;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+    use futures_core::Stream;
+
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker { RawWaker::new(std::ptr::null(), &VTABLE) }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        unsafe { Waker::from_raw(clone(std::ptr::null())) }
+    }
+
+    #[test]
+    fn iter_depth_stream() {
+        let tree = build_tree();
+        let mut stream = tree.iter_depth_stream(3);
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut result = vec![];
+        let mut pending = 0;
+        loop {
+            match Pin::new(&mut stream).poll_next(&mut cx) {
+                Poll::Ready(Some(inode)) => result.push(inode.to_string()),
+                Poll::Ready(None) => break,
+                Poll::Pending => pending += 1
+            }
+        }
+        assert_eq!(result, ["a1", "a2", "a", "b", "c1", "c2", "c", "root"]);
+        assert_eq!(pending, 2);
+    }
+}
+
+mod generators {
+    use super::*;
+    use crate::gen::*;
+
+    #[test]
+    fn from_sorted_slice() {
+        let items = (0..100).collect::<Vec<_>>();
+        for arity in [2, 3, 4, 7] {
+            let tree = VecTree::from_sorted_slice(&items, arity);
+            assert_eq!(tree.len(), 100);
+            assert_eq!(tree.validate(), Ok(()));
+            assert!(tree.nodes().iter().all(|node| node.children().len() <= arity));
+            // the children's subtrees cover increasing ranges:
+            for node in tree.nodes() {
+                let ranges = node.children().iter()
+                    .map(|&c| (tree.min_by_in_subtree(c, |a, b| a.cmp(b)), tree.max_by_in_subtree(c, |a, b| a.cmp(b))))
+                    .map(|(min, max)| (*tree.get(min.unwrap()), *tree.get(max.unwrap())))
+                    .collect::<Vec<_>>();
+                assert!(ranges.windows(2).all(|w| w[0].1 < w[1].0));
+            }
+        }
+        let items729 = (0..729).collect::<Vec<_>>();
+        let depths = [2, 3, 4, 5].map(|arity| VecTree::from_sorted_slice(&items729, arity).depth());
+        assert_eq!(depths, [Some(9), Some(6), Some(5), Some(4)]);
+        let tree = VecTree::from_sorted_slice(&items, 2);
+        assert_eq!(tree.depth(), Some(6));
+        // in-order traversal of the binary tree
+        let mut in_order = vec![];
+        let mut stack = vec![(0, false)];
+        while let Some((index, visited)) = stack.pop() {
+            let children = tree.children(index);
+            let value = *tree.get(index);
+            if visited {
+                in_order.push(value);
+                continue;
+            }
+            let (left, right) = match children {
+                [l, r] => (Some(*l), Some(*r)),
+                [c] if *tree.get(*c) < value => (Some(*c), None),
+                [c] => (None, Some(*c)),
+                _ => (None, None),
+            };
+            stack.extend(right.map(|r| (r, false)));
+            stack.push((index, true));
+            stack.extend(left.map(|l| (l, false)));
+        }
+        assert_eq!(in_order, items);
+        assert!(VecTree::<i32>::from_sorted_slice(&[], 2).is_empty());
+    }
+
+    #[test]
+    fn perfect() {
+        let tree = perfect_k_ary(2, 3);
+        assert_eq!(tree.len(), 13);
+        assert_eq!(tree.children(0), &[1, 2, 3]);
+        assert_eq!(tree.children(3), &[10, 11, 12]);
+        assert_eq!(tree.depth(), Some(2));
+        assert_eq!(perfect_k_ary(0, 3).len(), 1);
+        let tree = perfect_k_ary_with(1, 2, |i| format!("n{i}"));
+        assert_eq!(tree_to_string(&tree), "n0(n1,n2)");
+    }
+
+    #[test]
+    fn random() {
+        let mut rng = XorShift64::new(0);
+        for n in [0, 1, 2, 50, 500] {
+            let tree = random_tree(n, 3, &mut || rng.next_u64());
+            assert_eq!(tree.len(), n);
+            assert_eq!(tree.validate(), Ok(()));
+            assert_eq!(tree.iter_depth_simple().count(), n);
+            assert!(tree.nodes().iter().all(|node| node.children().len() <= 3));
+        }
+        let tree = random_tree(20, 1, &mut || rng.next_u64());
+        assert_eq!(tree.depth(), Some(19));
+    }
 }
@@ -0,0 +1,548 @@
+// Copyright 2025 Redglyph
+//
+
+//! Traversals in an order selected at runtime.
+
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter};
+use std::iter::FusedIterator;
+use crate::{IterData, IterDataMut, IterDataSimpleMut, NodeProxy, TreeDataIter, VecTree, VisitEvent, VisitNode};
+
+/// The order in which the nodes of a [VecTree] are visited by [`VecTree::iter_traverse()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Traversal {
+    /// Depth-first search, each node being visited before its children.
+    PreOrder,
+    /// Depth-first search, each node being visited after its children, like [`VecTree::iter_depth()`].
+    PostOrder,
+    /// Level by level, from the top of the traversal.
+    BreadthFirst,
+}
+
+/// A [VecTree] iterator whose order is given by a [Traversal] (see [`VecTree::iter_traverse()`]).
+///
+/// All the orders share the same engine: a double-ended queue of nodes waiting to be visited, which is used as
+/// a stack by the depth-first orders and as a queue by the breadth-first order.
+pub struct VecTreeTraversalIter<TData> {
+    order: Traversal,
+    pending: VecDeque<VisitNode<(usize, u32)>>,
+    only_depth: Option<u32>,
+    last_children: usize,               // number of children of the last node waiting in `pending`
+    data: TData
+}
+
+/// A pre-order [VecTree] iterator whose descent can be steered by [`VecTreeTraversalIter::skip_children()`]
+/// (see [`VecTree::walk()`]).
+pub type TreeWalker<'a, T> = VecTreeTraversalIter<IterData<'a, T>>;
+
+impl<TData> VecTreeTraversalIter<TData> {
+    fn with_data(top: Option<usize>, order: Traversal, data: TData) -> Self {
+        VecTreeTraversalIter {
+            order,
+            pending: top.map(|index| VisitNode::Down((index, 0))).into_iter().collect(),
+            only_depth: None,
+            last_children: 0,
+            data
+        }
+    }
+
+    /// Restricts the traversal to the nodes at `depth`, without visiting the nodes below them.
+    fn with_only_depth(mut self, depth: u32) -> Self {
+        self.only_depth = Some(depth);
+        self
+    }
+
+    /// Returns the order of the traversal.
+    pub fn order(&self) -> Traversal {
+        self.order
+    }
+
+    /// Prevents the traversal from visiting the nodes under the last node returned by the iterator.
+    ///
+    /// It has no effect in post-order, since the nodes under a node are visited before it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::{Traversal, VecTree};
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add_iter(Some(a), ["a1", "a2"]);
+    /// let b = tree.add(Some(root), "b");
+    /// tree.add_iter(Some(b), ["b1", "b2"]);
+    /// let mut iter = tree.iter_traverse(Traversal::BreadthFirst);
+    /// let mut visited = vec![];
+    /// while let Some(inode) = iter.next() {
+    ///     if *inode == "a" {
+    ///         iter.skip_children();
+    ///     }
+    ///     visited.push(*inode);
+    /// }
+    /// assert_eq!(visited, ["root", "a", "b", "b1", "b2"]);
+    /// ```
+    pub fn skip_children(&mut self) {
+        // the children of the last node are the last ones pushed in `pending`, whatever the order
+        let len = self.pending.len() - self.last_children;
+        self.pending.truncate(len);
+        self.last_children = 0;
+    }
+}
+
+impl<TData: TreeDataIter> VecTreeTraversalIter<TData> {
+    /// Moves to the next node and returns its index and depth, without creating a proxy.
+    fn advance(&mut self) -> Option<(usize, u32)> {
+        self.last_children = 0;
+        loop {
+            let node_dir = match self.order {
+                Traversal::BreadthFirst => self.pending.pop_front()?,
+                Traversal::PreOrder | Traversal::PostOrder => self.pending.pop_back()?
+            };
+            match node_dir {
+                VisitNode::Up(index_depth) => return Some(index_depth),
+                VisitNode::Down((index, depth)) => {
+                    let children = self.data.get_children(index);
+                    let children_dir = |&child: &usize| VisitNode::Down((child, depth + 1));
+                    if let Some(only_depth) = self.only_depth {
+                        // the nodes above that depth are only used to find the next ones
+                        if depth == only_depth {
+                            return Some((index, depth));
+                        }
+                        self.pending.extend(children.iter().rev().map(children_dir));
+                        continue;
+                    }
+                    match self.order {
+                        Traversal::PreOrder => {
+                            self.pending.extend(children.iter().rev().map(children_dir));
+                            self.last_children = children.len();
+                            return Some((index, depth));
+                        }
+                        Traversal::BreadthFirst => {
+                            self.pending.extend(children.iter().map(children_dir));
+                            self.last_children = children.len();
+                            return Some((index, depth));
+                        }
+                        Traversal::PostOrder => {
+                            if children.is_empty() {
+                                return Some((index, depth));
+                            }
+                            self.pending.push_back(VisitNode::Up((index, depth)));
+                            self.pending.extend(children.iter().rev().map(children_dir));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<TData: TreeDataIter> Iterator for VecTreeTraversalIter<TData> {
+    type Item = TData::TProxy;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.advance().map(|(index, depth)| self.data.create_proxy(index, depth))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // each pending node will be returned, but the number of nodes below them is unknown
+        match self.only_depth {
+            None => (self.pending.len(), None),
+            Some(_) => (0, None)
+        }
+    }
+
+    fn count(mut self) -> usize {
+        let mut count = 0;
+        while self.advance().is_some() {
+            count += 1;
+        }
+        count
+    }
+}
+
+impl<TData: TreeDataIter> FusedIterator for VecTreeTraversalIter<TData> {}
+
+impl<TData: Clone> Clone for VecTreeTraversalIter<TData> {
+    fn clone(&self) -> Self {
+        VecTreeTraversalIter {
+            order: self.order,
+            pending: self.pending.clone(),
+            only_depth: self.only_depth,
+            last_children: self.last_children,
+            data: self.data.clone()
+        }
+    }
+}
+
+impl<TData> Debug for VecTreeTraversalIter<TData> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VecTreeTraversalIter")
+            .field("order", &self.order)
+            .field("pending", &self.pending)
+            .field("only_depth", &self.only_depth)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A [VecTree] iterator which returns each node every time the depth-first search enters or comes back
+/// to it, which is the Euler tour of the tree (see [`VecTree::iter_euler()`]).
+pub struct VecTreeEulerIter<TData> {
+    top: Option<usize>,
+    stack: Vec<(usize, usize)>,         // (index, position of the next child to visit)
+    data: TData
+}
+
+impl<TData> VecTreeEulerIter<TData> {
+    fn with_data(top: Option<usize>, data: TData) -> Self {
+        VecTreeEulerIter { top, stack: Vec::new(), data }
+    }
+}
+
+impl<TData: TreeDataIter> VecTreeEulerIter<TData> {
+    /// Moves to the next node and returns its index and depth, without creating a proxy.
+    fn advance(&mut self) -> Option<(usize, u32)> {
+        if let Some(top) = self.top.take() {
+            self.stack.push((top, 0));
+            return Some((top, 0));
+        }
+        let (index, pos) = self.stack.last_mut()?;
+        if let Some(&child) = self.data.get_children(*index).get(*pos) {
+            *pos += 1;
+            self.stack.push((child, 0));
+        } else {
+            self.stack.pop();
+        }
+        // returns the node at the top of the stack, which is either the child or the parent
+        let (index, _) = *self.stack.last()?;
+        Some((index, self.stack.len() as u32 - 1))
+    }
+}
+
+impl<TData: TreeDataIter> Iterator for VecTreeEulerIter<TData> {
+    type Item = TData::TProxy;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.advance().map(|(index, depth)| self.data.create_proxy(index, depth))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // the search will come back to each node of the stack except the last one, but the number
+        // of nodes below them is unknown
+        (self.top.is_some() as usize + self.stack.len().saturating_sub(1), None)
+    }
+
+    fn count(mut self) -> usize {
+        let mut count = 0;
+        while self.advance().is_some() {
+            count += 1;
+        }
+        count
+    }
+}
+
+impl<TData: TreeDataIter> FusedIterator for VecTreeEulerIter<TData> {}
+
+impl<TData: Clone> Clone for VecTreeEulerIter<TData> {
+    fn clone(&self) -> Self {
+        VecTreeEulerIter {
+            top: self.top,
+            stack: self.stack.clone(),
+            data: self.data.clone()
+        }
+    }
+}
+
+impl<TData> Debug for VecTreeEulerIter<TData> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VecTreeEulerIter")
+            .field("top", &self.top)
+            .field("stack", &self.stack)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> VecTree<T> {
+    /// Iterates over all the nodes of the [VecTree] in the order given by `order`, starting at its root node.
+    ///
+    /// The iterator returns the same proxies as [`VecTree::iter_depth()`], whatever the order, so an algorithm
+    /// can take the order as a parameter. The depth of each node is relative to the top of the traversal.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::{Traversal, VecTree};
+    ///
+    /// fn labels(tree: &VecTree<&str>, order: Traversal) -> String {
+    ///     tree.iter_traverse(order).map(|inode| *inode).collect::<Vec<_>>().join(",")
+    /// }
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add_iter(Some(a), ["a1", "a2"]);
+    /// tree.add(Some(root), "b");
+    /// assert_eq!(labels(&tree, Traversal::PreOrder), "root,a,a1,a2,b");
+    /// assert_eq!(labels(&tree, Traversal::PostOrder), "a1,a2,a,b,root");
+    /// assert_eq!(labels(&tree, Traversal::BreadthFirst), "root,a,b,a1,a2");
+    /// ```
+    pub fn iter_traverse(&self, order: Traversal) -> VecTreeTraversalIter<IterData<'_, T>> {
+        VecTreeTraversalIter::with_data(self.root, order, IterData::new(self))
+    }
+
+    /// Iterates over all the nodes of the [VecTree] in the order given by `order`, starting at the node of
+    /// index `top` (see [`VecTree::iter_traverse()`]).
+    pub fn iter_traverse_at(&self, top: usize, order: Traversal) -> VecTreeTraversalIter<IterData<'_, T>> {
+        VecTreeTraversalIter::with_data(self.live_top(Some(top)), order, IterData::new(self))
+    }
+
+    /// Iterates over all the nodes of the [VecTree] in the order given by `order`, starting at its root node.
+    ///
+    /// The iterator returns the same proxies as [`VecTree::iter_depth_simple_mut()`], which give a mutable
+    /// reference only to each node. Unlike the post-order, the pre-order and breadth-first orders return a
+    /// node before its children, so a proxy giving access to the children could still be in use when the
+    /// children are returned.
+    pub fn iter_traverse_mut(&mut self, order: Traversal) -> VecTreeTraversalIter<IterDataSimpleMut<'_, T>> {
+        let root = self.root;
+        VecTreeTraversalIter::with_data(root, order, IterDataSimpleMut::new(self))
+    }
+
+    /// Iterates over all the nodes of the [VecTree] in the order given by `order`, starting at the node of
+    /// index `top` (see [`VecTree::iter_traverse_mut()`]).
+    pub fn iter_traverse_at_mut(&mut self, top: usize, order: Traversal) -> VecTreeTraversalIter<IterDataSimpleMut<'_, T>> {
+        let top = self.live_top(Some(top));
+        VecTreeTraversalIter::with_data(top, order, IterDataSimpleMut::new(self))
+    }
+
+    /// Iterates over all the nodes of the [VecTree] level by level, starting at its root node.
+    ///
+    /// The iterator returns the same proxies as [`VecTree::iter_depth()`]; the `depth` of each node is the
+    /// level being visited. It's equivalent to `iter_traverse(Traversal::BreadthFirst)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add_iter(Some(a), ["a1", "a2"]);
+    /// tree.add(Some(root), "b");
+    /// let result = tree.iter_breadth().map(|inode| format!("{}:{}", inode.depth, *inode)).collect::<Vec<_>>();
+    /// assert_eq!(result, ["0:root", "1:a", "1:b", "2:a1", "2:a2"]);
+    /// ```
+    pub fn iter_breadth(&self) -> VecTreeTraversalIter<IterData<'_, T>> {
+        self.iter_traverse(Traversal::BreadthFirst)
+    }
+
+    /// Iterates over all the nodes of the [VecTree] level by level, starting at the node of index `top`
+    /// (see [`VecTree::iter_breadth()`]). The depth of each node is relative to `top`.
+    pub fn iter_breadth_at(&self, top: usize) -> VecTreeTraversalIter<IterData<'_, T>> {
+        self.iter_traverse_at(top, Traversal::BreadthFirst)
+    }
+
+    /// Iterates over all the nodes of the [VecTree] level by level, starting at its root node, with a mutable
+    /// access to each node.
+    ///
+    /// The iterator returns the same proxies as [`VecTree::iter_depth_simple_mut()`], which give no access to
+    /// the children; the `depth` of each node is the level being visited.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root((0, 0));
+    /// let a = tree.add(Some(root), (0, 0));
+    /// tree.add_iter(Some(a), [(0, 0), (0, 0)]);
+    /// tree.add(Some(root), (0, 0));
+    /// // (row, column) of each node in a layout by level
+    /// let mut row_len = Vec::new();
+    /// for mut inode in tree.iter_breadth_mut() {
+    ///     let row = inode.depth as usize;
+    ///     if row == row_len.len() {
+    ///         row_len.push(0);
+    ///     }
+    ///     *inode = (row, row_len[row]);
+    ///     row_len[row] += 1;
+    /// }
+    /// assert_eq!(tree.values()[4], (1, 1));
+    /// assert_eq!(row_len, [1, 2, 2]);
+    /// ```
+    pub fn iter_breadth_mut(&mut self) -> VecTreeTraversalIter<IterDataSimpleMut<'_, T>> {
+        let root = self.root;
+        VecTreeTraversalIter::with_data(root, Traversal::BreadthFirst, IterDataSimpleMut::new(self))
+    }
+
+    /// Iterates over all the nodes of the [VecTree] level by level, starting at the node of index `top`, with a
+    /// mutable access to each node (see [`VecTree::iter_breadth_mut()`]). The depth of each node is relative to `top`.
+    pub fn iter_breadth_at_mut(&mut self, top: usize) -> VecTreeTraversalIter<IterDataSimpleMut<'_, T>> {
+        let top = self.live_top(Some(top));
+        VecTreeTraversalIter::with_data(top, Traversal::BreadthFirst, IterDataSimpleMut::new(self))
+    }
+
+    /// Walks through the nodes of the [VecTree] in pre-order, starting at its root node. After a node is
+    /// returned, [`VecTreeTraversalIter::skip_children()`] prevents the walker from descending into the
+    /// subtree under that node.
+    ///
+    /// The walker returns the same proxies as [`VecTree::iter_depth()`]. It's equivalent to
+    /// `iter_traverse(Traversal::PreOrder)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root(0);
+    /// let a = tree.add(Some(root), 10);
+    /// tree.add_iter(Some(a), [11, 12]);
+    /// let b = tree.add(Some(root), 25);
+    /// tree.add_iter(Some(b), [26, 27]);
+    /// // searches the values below 20, knowing the values increase downward
+    /// let mut walker = tree.walk();
+    /// let mut found = vec![];
+    /// while let Some(inode) = walker.next() {
+    ///     if *inode < 20 {
+    ///         found.push(*inode);
+    ///     } else {
+    ///         walker.skip_children();
+    ///     }
+    /// }
+    /// assert_eq!(found, [0, 10, 11, 12]);
+    /// ```
+    pub fn walk(&self) -> TreeWalker<'_, T> {
+        self.iter_traverse(Traversal::PreOrder)
+    }
+
+    /// Walks through the nodes of the [VecTree] in pre-order, starting at the node of index `top`
+    /// (see [`VecTree::walk()`]).
+    pub fn walk_at(&self, top: usize) -> TreeWalker<'_, T> {
+        self.iter_traverse_at(top, Traversal::PreOrder)
+    }
+
+    /// Iterates over the Euler tour of the [VecTree], starting at its root node: the depth-first search
+    /// returns each node when it enters it, then each time it comes back to it after visiting one of its
+    /// children. A tree of `n` nodes gives a sequence of `2n - 1` nodes.
+    ///
+    /// The iterator returns the same proxies as [`VecTree::iter_depth()`], with the index and the depth of
+    /// each node.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add_iter(Some(a), ["a1", "a2"]);
+    /// tree.add(Some(root), "b");
+    /// let tour = tree.iter_euler().map(|inode| format!("{}:{}", *inode, inode.depth)).collect::<Vec<_>>();
+    /// assert_eq!(tour, ["root:0", "a:1", "a1:2", "a:1", "a2:2", "a:1", "root:0", "b:1", "root:0"]);
+    /// ```
+    pub fn iter_euler(&self) -> VecTreeEulerIter<IterData<'_, T>> {
+        VecTreeEulerIter::with_data(self.root, IterData::new(self))
+    }
+
+    /// Iterates over the Euler tour of the subtree under the node of index `top` (see [`VecTree::iter_euler()`]).
+    pub fn iter_euler_at(&self, top: usize) -> VecTreeEulerIter<IterData<'_, T>> {
+        VecTreeEulerIter::with_data(self.live_top(Some(top)), IterData::new(self))
+    }
+
+    /// Traverses the [VecTree] depth-first, starting at its root node, and calls `f` with a
+    /// [`VisitEvent::Enter`] event when entering each node, before visiting its children, and with a
+    /// [`VisitEvent::Leave`] event when leaving it, after visiting its children.
+    ///
+    /// Each event holds the same proxy as [`VecTree::iter_depth()`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::{VecTree, VisitEvent};
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add_iter(Some(a), ["a1", "a2"]);
+    /// tree.add(Some(root), "b");
+    /// let mut text = String::new();
+    /// tree.visit(|event| match event {
+    ///     VisitEvent::Enter(inode) => {
+    ///         text.push_str(*inode);
+    ///         if inode.num_children() > 0 {
+    ///             text.push('(');
+    ///         }
+    ///     }
+    ///     VisitEvent::Leave(inode) => {
+    ///         if inode.num_children() > 0 {
+    ///             text.push(')');
+    ///         }
+    ///         text.push(' ');
+    ///     }
+    /// });
+    /// assert_eq!(text, "root(a(a1 a2 ) b ) ");
+    /// ```
+    pub fn visit<F: FnMut(VisitEvent<NodeProxy<'_, T>>)>(&self, f: F) {
+        self.visit_from(self.root, f);
+    }
+
+    /// Traverses the [VecTree] depth-first, starting at the node of index `top`, and calls `f` when entering
+    /// and when leaving each node (see [`VecTree::visit()`]).
+    pub fn visit_at<F: FnMut(VisitEvent<NodeProxy<'_, T>>)>(&self, top: usize, f: F) {
+        self.visit_from(self.live_top(Some(top)), f);
+    }
+
+    /// Traverses the subtree under `top`, if any (see [`VecTree::visit()`]).
+    fn visit_from<F: FnMut(VisitEvent<NodeProxy<'_, T>>)>(&self, top: Option<usize>, mut f: F) {
+        let Some(top) = top else { return };
+        let data = IterData::new(self);
+        f(VisitEvent::Enter(data.create_proxy(top, 0)));
+        // (index, position of the next child to visit)
+        let mut stack = vec![(top, 0)];
+        while let Some((index, pos)) = stack.last_mut() {
+            let index = *index;
+            if let Some(&child) = data.get_children(index).get(*pos) {
+                *pos += 1;
+                let depth = stack.len() as u32;
+                stack.push((child, 0));
+                f(VisitEvent::Enter(data.create_proxy(child, depth)));
+            } else {
+                stack.pop();
+                f(VisitEvent::Leave(data.create_proxy(index, stack.len() as u32)));
+            }
+        }
+    }
+
+    /// Iterates over the nodes of the [VecTree] at the level `depth`, from left to right, where the root
+    /// node is at depth 0.
+    ///
+    /// The iterator returns the same proxies as [`VecTree::iter_depth()`]. The nodes below that level aren't
+    /// visited.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vectree::VecTree;
+    /// let mut tree = VecTree::new();
+    /// let root = tree.add_root("root");
+    /// let a = tree.add(Some(root), "a");
+    /// tree.add_iter(Some(a), ["a1", "a2"]);
+    /// let b = tree.add(Some(root), "b");
+    /// tree.add(Some(b), "b1");
+    /// let result = tree.iter_at_depth(2).map(|inode| *inode).collect::<Vec<_>>();
+    /// assert_eq!(result, ["a1", "a2", "b1"]);
+    /// assert_eq!(tree.iter_at_depth(3).count(), 0);
+    /// ```
+    pub fn iter_at_depth(&self, depth: u32) -> VecTreeTraversalIter<IterData<'_, T>> {
+        self.iter_traverse(Traversal::PreOrder).with_only_depth(depth)
+    }
+
+    /// Iterates over the nodes of the [VecTree] at the level `depth`, from left to right, where the root
+    /// node is at depth 0 (see [`VecTree::iter_at_depth()`]).
+    ///
+    /// The iterator returns the same proxies as [`VecTree::iter_depth_mut()`], which give a mutable reference
+    /// to each node and an immutable reference to its children.
+    pub fn iter_at_depth_mut(&mut self, depth: u32) -> VecTreeTraversalIter<IterDataMut<'_, T>> {
+        // the nodes below `depth` are never returned, so the children of a node can't be given mutably
+        // by the iterator while its proxy gives access to them
+        let root = self.root;
+        VecTreeTraversalIter::with_data(root, Traversal::PreOrder, IterDataMut::new(self)).with_only_depth(depth)
+    }
+}